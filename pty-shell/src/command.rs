@@ -1,4 +1,8 @@
 use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 use std::{ffi, ptr};
 
 pub fn exec<S: AsRef<str>>(shell: S) {
@@ -7,14 +11,30 @@ pub fn exec<S: AsRef<str>>(shell: S) {
 }
 
 pub fn exec_with_env<S: AsRef<str>>(shell: S, env: Option<HashMap<String, String>>) {
-    // The command we are running.
-    let cmd = ffi::CString::new(shell.as_ref()).unwrap();
-
-    // The arguments to the command.
-    let args: Vec<*const libc::c_char> = vec![cmd.as_ptr(), ptr::null()];
+    exec_args_with_env(&[shell], env)
+}
 
+pub fn exec_args_with_env<S: AsRef<str>>(args: &[S], env: Option<HashMap<String, String>>) {
     // The environment we are running in.
     let v = env.unwrap_or_default();
+
+    // The command we are running, looked up in `PATH` if it isn't a path already.
+    let search_path = v
+        .get("PATH")
+        .cloned()
+        .or_else(|| env::var("PATH").ok())
+        .unwrap_or_default();
+    let program = find_executable(args[0].as_ref(), &search_path);
+    let cmd = ffi::CString::new(program).unwrap();
+
+    // The arguments to the command.
+    let cstring_args: Vec<ffi::CString> = args
+        .iter()
+        .map(|arg| ffi::CString::new(arg.as_ref()).unwrap())
+        .collect();
+    let mut arg_ptrs: Vec<*const libc::c_char> = cstring_args.iter().map(|c| c.as_ptr()).collect();
+    arg_ptrs.push(ptr::null());
+
     // We need to save the `CString`s in this scope, otherwise the pointers will be drefed
     // and all hell breaks loose.
     let cstring_vars: Vec<ffi::CString> = v
@@ -25,6 +45,26 @@ pub fn exec_with_env<S: AsRef<str>>(shell: S, env: Option<HashMap<String, String
     env_vars.push(ptr::null());
 
     unsafe {
-        libc::execve(cmd.as_ptr(), args.as_ptr(), env_vars.as_ptr());
+        libc::execve(cmd.as_ptr(), arg_ptrs.as_ptr(), env_vars.as_ptr());
+        // Only reached if `execve` failed. Never fall back into the parent's code.
+        libc::_exit(127);
+    }
+}
+
+fn find_executable(program: &str, search_path: &str) -> String {
+    if program.contains('/') {
+        return program.to_string();
     }
+
+    for dir in search_path.split(':').filter(|dir| !dir.is_empty()) {
+        let candidate = Path::new(dir).join(program);
+        let executable = fs::metadata(&candidate)
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false);
+        if executable {
+            return candidate.to_string_lossy().into_owned();
+        }
+    }
+
+    program.to_string()
 }
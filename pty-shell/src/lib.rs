@@ -11,22 +11,35 @@ pub use pty::prelude as tty;
 
 pub use self::error::*;
 use self::raw_handler::*;
+pub use self::signals::{forwarded_signal, ESCALATION_DELAY};
 pub use self::terminal::*;
 use self::winsize::Winsize;
 use std::collections::HashMap;
 use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::time::Duration;
 use std::{fmt, io, result, thread};
 
+/// How often `wait_for_exit` checks on the child.
+const WAIT_INTERVAL: Duration = Duration::from_millis(20);
+
 pub mod error;
 pub mod terminal;
 pub mod winsize;
 
 mod command;
 mod raw_handler;
+mod signals;
 
 pub type Result<T> = result::Result<T, Error>;
 
 pub trait PtyHandler {
+    /// Called with input before it reaches the child, returns what to write to it instead.
+    fn filter_input(&mut self, data: &[u8]) -> Vec<u8> {
+        data.to_vec()
+    }
     fn input(&mut self, _data: &[u8]) {}
     fn output(&mut self, _data: &[u8]) {}
     fn resize(&mut self, _winsize: &Winsize) {}
@@ -40,7 +53,15 @@ pub trait PtyShell {
         shell: S,
         env: Option<HashMap<String, String>>,
     ) -> Result<()>;
+    fn exec_args_with_env<S: AsRef<str>>(
+        &self,
+        args: &[S],
+        env: Option<HashMap<String, String>>,
+    ) -> Result<()>;
     fn proxy<H: PtyHandler + 'static>(&self, handler: H) -> Result<()>;
+    fn proxy_headless<H: PtyHandler + 'static>(&self, handler: H, winsize: &Winsize) -> Result<()>;
+    fn wait_for_exit(&self) -> Result<ExitStatus>;
+    fn forward_signals(&self, signals: &[libc::c_int]) -> Result<()>;
 }
 
 impl PtyShell for tty::Fork {
@@ -64,29 +85,118 @@ impl PtyShell for tty::Fork {
         Ok(())
     }
 
+    fn exec_args_with_env<S: AsRef<str>>(
+        &self,
+        args: &[S],
+        env: Option<HashMap<String, String>>,
+    ) -> Result<()> {
+        if self.is_child().is_ok() {
+            command::exec_args_with_env(args, env);
+        }
+
+        Ok(())
+    }
+
     fn proxy<H: PtyHandler + 'static>(&self, handler: H) -> Result<()> {
         if let Ok(master) = self.is_parent() {
             setup_terminal(master)?;
-            do_proxy(master, handler)?;
+            do_proxy(master, handler, true)?;
+        }
+        Ok(())
+    }
+
+    /// Like `proxy`, but the pty keeps the given size instead of following the
+    /// terminal's. Stdin is left as is unless it is a terminal, which is put in
+    /// raw mode like `proxy` does.
+    fn proxy_headless<H: PtyHandler + 'static>(&self, handler: H, winsize: &Winsize) -> Result<()> {
+        match self.is_parent() {
+            Ok(master) => {
+                if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+                    setup_raw_mode()?;
+                }
+                winsize::set(master.as_raw_fd(), winsize);
+                do_proxy(master, handler, false)?;
+            }
+            // Also set the size from the child, it may exec before the parent gets to it.
+            Err(_) => winsize::set(libc::STDIN_FILENO, winsize),
+        }
+        Ok(())
+    }
+
+    /// Like `wait`, but returns how the child exited.
+    ///
+    /// A child that outlives a forwarded signal by `ESCALATION_DELAY` (e.g. an
+    /// interactive shell, which ignores SIGTERM and SIGINT) is hung up on, as
+    /// if its terminal was closed, and killed if that isn't enough either.
+    fn wait_for_exit(&self) -> Result<ExitStatus> {
+        let pid = match *self {
+            tty::Fork::Parent(pid, _) => pid,
+            tty::Fork::Child(_) => return Err(tty::ForkError::IsChild.into()),
+        };
+        let mut escalation = signals::Escalation::new(pid);
+        let mut status = 0;
+        loop {
+            // Signals don't interrupt a blocking wait, they restart it.
+            match unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err.into());
+                    }
+                }
+                0 => {
+                    escalation.check();
+                    thread::sleep(WAIT_INTERVAL);
+                }
+                _ => return Ok(ExitStatus::from_raw(status)),
+            }
+        }
+    }
+
+    /// Forwards `signals` to the child (and the job in the foreground of its
+    /// pty) instead of letting them terminate us.
+    ///
+    /// The child then decides whether to exit, and when it does, `proxy` can
+    /// finish as usual. See `forwarded_signal` for whether any were forwarded.
+    fn forward_signals(&self, signals: &[libc::c_int]) -> Result<()> {
+        if let tty::Fork::Parent(pid, ref master) = *self {
+            signals::forward_signals(pid, master.as_raw_fd(), signals)?;
         }
         Ok(())
     }
 }
 
-fn do_proxy<H: PtyHandler + 'static>(pty: tty::Master, handler: H) -> Result<()> {
+/// `mio` pipes are non-blocking. The threads feeding the event loop should
+/// rather wait for it to catch up than fail when a pipe is full.
+fn set_blocking(fd: RawFd) -> io::Result<()> {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn do_proxy<H: PtyHandler + 'static>(
+    pty: tty::Master,
+    handler: H,
+    follow_resize: bool,
+) -> Result<()> {
     let mut event_loop = mio::EventLoop::new()?;
 
-    let mut writer = pty;
     let (input_reader, mut input_writer) = mio::unix::pipe()?;
+    set_blocking(input_writer.as_raw_fd())?;
 
     thread::spawn(move || {
-        handle_input(&mut writer, &mut input_writer).unwrap_or_else(|e| {
+        handle_input(&mut input_writer).unwrap_or_else(|e| {
             println!("{:?}", e);
         });
     });
 
     let mut reader = pty;
     let (output_reader, mut output_writer) = mio::unix::pipe()?;
+    set_blocking(output_writer.as_raw_fd())?;
     let message_sender = event_loop.channel();
 
     thread::spawn(move || {
@@ -109,11 +219,28 @@ fn do_proxy<H: PtyHandler + 'static>(pty: tty::Master, handler: H) -> Result<()>
         mio::EventSet::readable(),
         mio::PollOpt::level(),
     )?;
-    RawHandler::register_sigwinch_handler();
+    let (sigwinch_reader, sigwinch_writer) = mio::unix::pipe()?;
+    event_loop.register(
+        &sigwinch_reader,
+        SIGWINCH,
+        mio::EventSet::readable(),
+        mio::PollOpt::level(),
+    )?;
+    if follow_resize {
+        RawHandler::register_sigwinch_handler(&sigwinch_writer);
+    }
 
-    let mut raw_handler = RawHandler::new(input_reader, output_reader, pty, Box::new(handler));
+    let mut raw_handler = RawHandler::new(
+        input_reader,
+        output_reader,
+        sigwinch_reader,
+        pty,
+        Box::new(handler),
+    );
 
     thread::spawn(move || {
+        // Keep the write end of the pipe open for as long as the loop runs.
+        let _sigwinch_writer = sigwinch_writer;
         event_loop.run(&mut raw_handler).unwrap_or_else(|e| {
             println!("{:?}", e);
         });
@@ -122,17 +249,18 @@ fn do_proxy<H: PtyHandler + 'static>(pty: tty::Master, handler: H) -> Result<()>
     Ok(())
 }
 
-fn handle_input(
-    writer: &mut tty::Master,
-    handler_writer: &mut mio::unix::PipeWriter,
-) -> Result<()> {
+fn handle_input(handler_writer: &mut mio::unix::PipeWriter) -> Result<()> {
     let mut input = io::stdin();
     let mut buf = [0; 128];
 
     loop {
         let nread = input.read(&mut buf)?;
+        if nread == 0 {
+            // Stdin is closed, e.g. when it isn't a terminal.
+            return Ok(());
+        }
 
-        writer.write_all(&buf[..nread])?;
+        // The handler gets to filter the input before it is written to the pty.
         handler_writer.write_all(&buf[..nread])?;
     }
 }
@@ -145,12 +273,19 @@ fn handle_output(
     let mut buf = [0; 1024 * 10];
 
     loop {
-        let nread = reader.read(&mut buf)?;
+        let nread = match reader.read(&mut buf) {
+            Ok(n) => n,
+            // Linux reports `EIO` instead of EOF once the child has exited.
+            Err(ref e) if e.raw_os_error() == Some(libc::EIO) => 0,
+            Err(e) => return Err(e.into()),
+        };
 
         if nread == 0 {
             break;
         } else {
-            output.write_all(&buf[..nread])?;
+            // Keep going if the terminal is gone (e.g. its window was closed),
+            // the handler still wants the output.
+            let _ = output.write_all(&buf[..nread]);
             let _ = output.flush();
 
             handler_writer.write_all(&buf[..nread])?;
@@ -161,6 +296,7 @@ fn handle_output(
 }
 
 pub struct PtyCallbackData {
+    filter_input_handler: Box<dyn FnMut(&[u8]) -> Vec<u8>>,
     input_handler: Box<dyn FnMut(&[u8])>,
     output_handler: Box<dyn FnMut(&[u8])>,
     resize_handler: Box<dyn FnMut(&Winsize)>,
@@ -174,6 +310,10 @@ impl fmt::Debug for PtyCallbackData {
 }
 
 impl PtyHandler for PtyCallbackData {
+    fn filter_input(&mut self, data: &[u8]) -> Vec<u8> {
+        (&mut *self.filter_input_handler)(data)
+    }
+
     fn input(&mut self, data: &[u8]) {
         (&mut *self.input_handler)(data);
     }
@@ -195,6 +335,15 @@ impl PtyHandler for PtyCallbackData {
 pub struct PtyCallbackBuilder(PtyCallbackData);
 
 impl PtyCallbackBuilder {
+    pub fn filter_input<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&[u8]) -> Vec<u8> + 'static,
+    {
+        self.0.filter_input_handler = Box::new(handler);
+
+        self
+    }
+
     pub fn input<F>(mut self, handler: F) -> Self
     where
         F: FnMut(&[u8]) + 'static,
@@ -239,6 +388,7 @@ impl PtyCallbackBuilder {
 impl Default for PtyCallbackBuilder {
     fn default() -> Self {
         let data = PtyCallbackData {
+            filter_input_handler: Box::new(|data| data.to_vec()),
             input_handler: Box::new(|_| {}),
             output_handler: Box::new(|_| {}),
             resize_handler: Box::new(|_| {}),
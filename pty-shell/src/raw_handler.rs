@@ -1,25 +1,34 @@
 use super::PtyHandler;
 use mio::*;
 use nix::sys::signal;
-use std::io::Read;
-use std::os::unix::io::AsRawFd;
+use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
 
 use crate::tty;
 use crate::winsize;
 
 pub const INPUT: Token = Token(0);
 pub const OUTPUT: Token = Token(1);
+pub const SIGWINCH: Token = Token(2);
+
+const CTRL_D: u8 = 0x04;
 
 static mut SIGWINCH_COUNT: i32 = 0;
+static mut SIGWINCH_FD: RawFd = -1;
 extern "C" fn handle_sigwinch(_: i32) {
     unsafe {
         SIGWINCH_COUNT += 1;
+        // Wake up the event loop, it may be blocked polling for input or output.
+        if SIGWINCH_FD >= 0 {
+            let _ = libc::write(SIGWINCH_FD, [0u8].as_ptr() as *const libc::c_void, 1);
+        }
     }
 }
 
 pub struct RawHandler {
     pub input: unix::PipeReader,
     pub output: unix::PipeReader,
+    pub sigwinch: unix::PipeReader,
     pub pty: tty::Master,
     pub handler: Box<dyn PtyHandler>,
     pub resize_count: i32,
@@ -34,19 +43,25 @@ impl RawHandler {
     pub fn new(
         input: unix::PipeReader,
         output: unix::PipeReader,
+        sigwinch: unix::PipeReader,
         pty: tty::Master,
         handler: Box<dyn PtyHandler>,
     ) -> Self {
         RawHandler {
             input,
             output,
+            sigwinch,
             pty,
             handler,
             resize_count: Self::sigwich_count(),
         }
     }
 
-    pub fn register_sigwinch_handler() {
+    pub fn register_sigwinch_handler(notify: &unix::PipeWriter) {
+        unsafe {
+            SIGWINCH_FD = notify.as_raw_fd();
+        }
+
         let sig_action = signal::SigAction::new(
             signal::SigHandler::Handler(handle_sigwinch),
             signal::SaFlags::SA_RESTART,
@@ -76,25 +91,57 @@ impl Handler for RawHandler {
     fn ready(&mut self, event_loop: &mut EventLoop<RawHandler>, token: Token, events: EventSet) {
         match token {
             INPUT => {
-                if events.is_readable() {
+                // A closed pipe is reported as hup rather than readable.
+                if events.is_readable() || events.is_hup() {
                     let mut buf = [0; 128];
-                    let nread = self.input.read(&mut buf).unwrap();
+                    let nread = self.input.read(&mut buf).unwrap_or(0);
 
-                    (&mut *self.handler).input(&buf[..nread]);
+                    if nread == 0 {
+                        // No more input will come, stop polling for it and pass
+                        // the end of input on to the child (as `^D`).
+                        let _ = event_loop.deregister(&self.input);
+                        let _ = self.pty.write_all(&[CTRL_D]);
+                        return;
+                    }
+
+                    let data = (&mut *self.handler).filter_input(&buf[..nread]);
+                    if data.is_empty() {
+                        return;
+                    }
+                    match self.pty.write_all(&data) {
+                        Ok(()) => (&mut *self.handler).input(&data),
+                        // The child is gone (e.g. `EIO` once it exited), so is
+                        // whoever would read more input. Stop forwarding it like
+                        // at the end of input, the output still has to be read.
+                        Err(_) => {
+                            let _ = event_loop.deregister(&self.input);
+                        }
+                    }
                 }
             }
             OUTPUT => {
-                if events.is_readable() {
+                if events.is_readable() || events.is_hup() {
                     let mut buf = [0; 1024 * 10];
-                    let nread = self.output.read(&mut buf).unwrap_or(0);
 
-                    if nread == 0 {
-                        event_loop.shutdown();
-                    } else {
-                        (&mut *self.handler).output(&buf[..nread]);
+                    match self.output.read(&mut buf) {
+                        // The output thread sends `Message::Shutdown` when it is done,
+                        // which shuts down the loop after the handler had a chance to.
+                        Ok(0) => {
+                            let _ = event_loop.deregister(&self.output);
+                        }
+                        Ok(nread) => (&mut *self.handler).output(&buf[..nread]),
+                        // Spurious wakeup, nothing to read yet.
+                        Err(_) => (),
                     }
                 }
             }
+            SIGWINCH => {
+                if events.is_readable() {
+                    // Only used to wake up the loop, `tick` does the resizing.
+                    let mut buf = [0; 128];
+                    let _ = self.sigwinch.read(&mut buf);
+                }
+            }
             _ => unimplemented!(),
         }
     }
@@ -104,6 +151,16 @@ impl Handler for RawHandler {
             Message::Shutdown => {
                 event_loop.shutdown();
 
+                // The output thread is done, but the loop may not have read
+                // everything it wrote yet.
+                let mut buf = [0; 1024 * 10];
+                while let Ok(nread) = self.output.read(&mut buf) {
+                    if nread == 0 {
+                        break;
+                    }
+                    self.handler.output(&buf[..nread]);
+                }
+
                 (&mut *self.handler).shutdown();
             }
             Message::Resize => {
@@ -0,0 +1,105 @@
+use nix::sys::signal;
+use std::convert::TryFrom;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::Result;
+
+static CHILD_PID: AtomicI32 = AtomicI32::new(0);
+static PTY_FD: AtomicI32 = AtomicI32::new(-1);
+static FORWARDED_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn forward_signal(sig: libc::c_int) {
+    let pid = CHILD_PID.load(Ordering::SeqCst);
+    if pid > 0 {
+        signal_child(pid, sig);
+    }
+    FORWARDED_SIGNAL.store(sig, Ordering::SeqCst);
+}
+
+/// Like a terminal would, signals the foreground job of the pty (e.g. a
+/// command the child shell is waiting for) as well as the child itself.
+fn signal_child(pid: libc::pid_t, sig: libc::c_int) {
+    // `tcgetpgrp` and `kill` are async-signal-safe.
+    unsafe {
+        let pgrp = libc::tcgetpgrp(PTY_FD.load(Ordering::SeqCst));
+        if pgrp > 0 {
+            libc::kill(-pgrp, sig);
+        }
+        if pgrp != pid {
+            libc::kill(pid, sig);
+        }
+    }
+}
+
+/// Sends `signals` received by this process on to `pid` and the foreground
+/// job of its `pty` instead of letting them terminate us, so the child can
+/// exit and we can clean up after it.
+pub fn forward_signals(pid: libc::pid_t, pty: RawFd, signals: &[libc::c_int]) -> Result<()> {
+    CHILD_PID.store(pid, Ordering::SeqCst);
+    PTY_FD.store(pty, Ordering::SeqCst);
+
+    let sig_action = signal::SigAction::new(
+        signal::SigHandler::Handler(forward_signal),
+        signal::SaFlags::SA_RESTART,
+        signal::SigSet::empty(),
+    );
+    for &sig in signals {
+        let sig = signal::Signal::try_from(sig).map_err(nix_to_io)?;
+        unsafe {
+            signal::sigaction(sig, &sig_action).map_err(nix_to_io)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the last signal forwarded to the child, if any.
+pub fn forwarded_signal() -> Option<libc::c_int> {
+    match FORWARDED_SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        sig => Some(sig),
+    }
+}
+
+fn nix_to_io(err: nix::Error) -> std::io::Error {
+    std::io::Error::from_raw_os_error(err as i32)
+}
+
+/// How long a child may outlive a forwarded signal before it is sent a
+/// stronger one.
+pub const ESCALATION_DELAY: Duration = Duration::from_secs(1);
+
+/// Makes sure a child we forwarded a signal to exits, see `wait_for_exit`.
+#[derive(Debug)]
+pub struct Escalation {
+    pid: libc::pid_t,
+    /// The signals still to send if the child lives on, the next one first.
+    next: &'static [libc::c_int],
+    since: Option<Instant>,
+}
+
+impl Escalation {
+    pub fn new(pid: libc::pid_t) -> Self {
+        Escalation {
+            pid,
+            next: &[libc::SIGHUP, libc::SIGKILL],
+            since: None,
+        }
+    }
+
+    /// Called while the child is alive, sends it the next signal when due.
+    pub fn check(&mut self) {
+        if forwarded_signal().is_none() {
+            return;
+        }
+        let since = *self.since.get_or_insert_with(Instant::now);
+        if let Some((&sig, rest)) = self.next.split_first() {
+            if since.elapsed() >= ESCALATION_DELAY {
+                signal_child(self.pid, sig);
+                self.next = rest;
+                self.since = Some(Instant::now());
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use libc;
 use std::io::Result;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use termios::*;
 
 use crate::tty;
@@ -14,6 +14,17 @@ pub extern "C" fn restore_termios() {
 }
 
 pub fn setup_terminal(pty: tty::Master) -> Result<()> {
+    setup_raw_mode()?;
+
+    let winsize = winsize::from_fd(libc::STDIN_FILENO)?;
+    winsize::set(pty.as_raw_fd(), &winsize);
+
+    Ok(())
+}
+
+/// Puts the terminal on stdin in raw mode until we exit, so what is typed
+/// goes to the pty as is, to be echoed (or not) by it alone.
+pub fn setup_raw_mode() -> Result<()> {
     let termios = Termios::from_fd(libc::STDIN_FILENO)?;
 
     unsafe {
@@ -21,12 +32,17 @@ pub fn setup_terminal(pty: tty::Master) -> Result<()> {
         libc::atexit(restore_termios);
     };
 
-    enter_raw_mode(libc::STDIN_FILENO)?;
-
-    let winsize = winsize::from_fd(libc::STDIN_FILENO)?;
-    winsize::set(pty.as_raw_fd(), &winsize);
+    enter_raw_mode(libc::STDIN_FILENO)
+}
 
-    Ok(())
+/// Whether input to the terminal behind `fd` is hidden, e.g. at a password prompt.
+///
+/// That is the case when echo is off in canonical mode. Programs in raw mode
+/// (e.g. shells using readline, editors) turn echo off as well, but echo the
+/// input themselves.
+pub fn is_input_hidden(fd: RawFd) -> Result<bool> {
+    let termios = Termios::from_fd(fd)?;
+    Ok(termios.c_lflag & ECHO == 0 && termios.c_lflag & ICANON != 0)
 }
 
 fn enter_raw_mode(fd: libc::c_int) -> Result<()> {
@@ -0,0 +1,49 @@
+use pty_shell::{is_input_hidden, restore_termios, tty, PtyShell};
+use std::os::unix::io::AsRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn wait_for_hidden_input(fd: i32, hidden: bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(5) {
+        if is_input_hidden(fd).unwrap() == hidden {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+fn it_can_detect_hidden_input() {
+    let child = tty::Fork::from_ptmx().unwrap();
+    restore_termios();
+
+    child
+        .exec_args_with_env(
+            &["sh", "-c", "stty -echo; sleep 1; stty echo; sleep 1"],
+            None,
+        )
+        .unwrap();
+
+    let fd = child.is_parent().unwrap().as_raw_fd();
+    assert!(wait_for_hidden_input(fd, true));
+    assert!(wait_for_hidden_input(fd, false));
+    assert!(child.wait().is_ok());
+}
+
+#[test]
+fn it_does_not_treat_raw_mode_as_hidden_input() {
+    let child = tty::Fork::from_ptmx().unwrap();
+    restore_termios();
+
+    child
+        .exec_args_with_env(&["sh", "-c", "stty raw -echo; sleep 1"], None)
+        .unwrap();
+
+    let fd = child.is_parent().unwrap().as_raw_fd();
+    // Give `stty` time to run.
+    thread::sleep(Duration::from_millis(500));
+    assert!(!is_input_hidden(fd).unwrap());
+    assert!(child.wait().is_ok());
+}
@@ -0,0 +1,13 @@
+use pty_shell::{restore_termios, tty, PtyShell};
+
+#[test]
+fn it_can_exec_with_args() {
+    let child = tty::Fork::from_ptmx().unwrap();
+    restore_termios();
+
+    child
+        .exec_args_with_env(&["sh", "-c", "exit 0"], None)
+        .unwrap();
+
+    assert!(child.wait().is_ok());
+}
@@ -0,0 +1,63 @@
+use pty_shell::{forwarded_signal, restore_termios, tty, PtyShell, ESCALATION_DELAY};
+use std::os::unix::process::ExitStatusExt;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Signal handlers are process wide, so the tests installing them take turns.
+static SIGNALS: Mutex<()> = Mutex::new(());
+
+#[test]
+fn it_can_forward_signals() {
+    let _signals = SIGNALS.lock().unwrap();
+    let child = tty::Fork::from_ptmx().unwrap();
+    restore_termios();
+
+    child
+        .exec_args_with_env(
+            &[
+                "sh",
+                "-c",
+                "trap 'exit 5' USR1; while true; do sleep 0.1; done",
+            ],
+            None,
+        )
+        .unwrap();
+
+    child.forward_signals(&[libc::SIGUSR1]).unwrap();
+    // Give the child time to set up its trap.
+    thread::sleep(Duration::from_millis(500));
+    unsafe {
+        libc::kill(libc::getpid(), libc::SIGUSR1);
+    }
+
+    assert_eq!(child.wait_for_exit().unwrap().code(), Some(5));
+    assert_eq!(forwarded_signal(), Some(libc::SIGUSR1));
+}
+
+#[test]
+fn it_can_end_children_ignoring_signals() {
+    let _signals = SIGNALS.lock().unwrap();
+    let child = tty::Fork::from_ptmx().unwrap();
+    restore_termios();
+
+    // Like an interactive shell, which ignores SIGTERM.
+    child
+        .exec_args_with_env(
+            &["sh", "-c", "trap '' TERM; while true; do sleep 0.1; done"],
+            None,
+        )
+        .unwrap();
+
+    child.forward_signals(&[libc::SIGTERM]).unwrap();
+    // Give the child time to set up its trap.
+    thread::sleep(Duration::from_millis(500));
+    let start = Instant::now();
+    unsafe {
+        libc::kill(libc::getpid(), libc::SIGTERM);
+    }
+
+    let status = child.wait_for_exit().unwrap();
+    assert_eq!(status.signal(), Some(libc::SIGHUP));
+    assert!(start.elapsed() >= ESCALATION_DELAY);
+}
@@ -0,0 +1,36 @@
+use pty_shell::{restore_termios, tty, winsize::Winsize, PtyCallback, PtyShell};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+#[test]
+fn it_can_proxy_headless() {
+    let child = tty::Fork::from_ptmx().unwrap();
+    restore_termios();
+
+    let (tx, rx) = channel();
+    let winsize = Winsize {
+        ws_row: 24,
+        ws_col: 100,
+        ..Default::default()
+    };
+    child
+        .proxy_headless(
+            PtyCallback::default()
+                .output(move |data| tx.send(data.to_vec()).unwrap())
+                .build(),
+            &winsize,
+        )
+        .unwrap();
+    child.exec_args_with_env(&["stty", "size"], None).unwrap();
+
+    assert!(child.wait().is_ok());
+
+    let mut output = vec![];
+    while let Ok(data) = rx.recv_timeout(Duration::from_secs(5)) {
+        output.extend(data);
+        if String::from_utf8_lossy(&output).contains("24 100") {
+            return;
+        }
+    }
+    panic!("unexpected output: {:?}", String::from_utf8_lossy(&output));
+}
@@ -0,0 +1,26 @@
+use pty_shell::{restore_termios, tty, PtyShell};
+use std::os::unix::process::ExitStatusExt;
+
+#[test]
+fn it_can_wait_for_exit() {
+    let child = tty::Fork::from_ptmx().unwrap();
+    restore_termios();
+
+    child
+        .exec_args_with_env(&["sh", "-c", "exit 3"], None)
+        .unwrap();
+
+    assert_eq!(child.wait_for_exit().unwrap().code(), Some(3));
+}
+
+#[test]
+fn it_can_wait_for_signaled_exit() {
+    let child = tty::Fork::from_ptmx().unwrap();
+    restore_termios();
+
+    child
+        .exec_args_with_env(&["sh", "-c", "kill -TERM $$"], None)
+        .unwrap();
+
+    assert_eq!(child.wait_for_exit().unwrap().signal(), Some(libc::SIGTERM));
+}
@@ -1,3 +1,8 @@
+mod it_can_detect_hidden_input;
+mod it_can_exec_with_args;
+mod it_can_forward_signals;
 mod it_can_hook_stdout_with_callback;
 mod it_can_hook_stdout_with_handler;
+mod it_can_proxy_headless;
 mod it_can_spawn;
+mod it_can_wait_for_exit;
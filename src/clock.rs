@@ -9,6 +9,9 @@ pub(crate) struct Clock {
     instant: Instant,
     duration_override: Option<Duration>,
     now_override: Option<DateTime<Utc>>,
+    paused_at: Option<Duration>,
+    paused_for: Duration,
+    offset: Duration,
 }
 
 impl Clock {
@@ -17,6 +20,9 @@ impl Clock {
             instant: Instant::now(),
             duration_override: None,
             now_override: None,
+            paused_at: None,
+            paused_for: Duration::new(0, 0),
+            offset: Duration::new(0, 0),
         }
     }
     #[inline]
@@ -27,12 +33,36 @@ impl Clock {
         }
     }
     #[inline]
-    pub(crate) fn elapsed(&self) -> Duration {
+    fn running(&self) -> Duration {
         match self.duration_override {
             Some(d) => d,
             None => self.instant.elapsed(),
         }
     }
+    /// Time since the clock started plus its offset, not counting the time it
+    /// was paused.
+    #[inline]
+    pub(crate) fn elapsed(&self) -> Duration {
+        let total = self.paused_at.unwrap_or_else(|| self.running());
+        total.checked_sub(self.paused_for).unwrap_or_default() + self.offset
+    }
+    /// Makes the clock start counting from `offset` instead of zero.
+    pub(crate) fn set_offset(&mut self, offset: Duration) {
+        self.offset = offset;
+    }
+    pub(crate) fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(self.running());
+        }
+    }
+    pub(crate) fn resume(&mut self) {
+        if let Some(at) = self.paused_at.take() {
+            self.paused_for += self.running().checked_sub(at).unwrap_or_default();
+        }
+    }
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
     #[cfg(test)]
     pub(crate) fn set_duration_override(&mut self, duration: Duration) {
         self.duration_override = Some(duration);
@@ -60,4 +90,34 @@ mod tests {
         let result = get_elapsed_seconds(&d);
         assert_eq!(result, 42.000000123);
     }
+
+    #[test]
+    fn test_elapsed_does_not_advance_while_paused() {
+        let mut clock = Clock::new();
+        clock.set_duration_override(Duration::new(10, 0));
+        clock.pause();
+        assert!(clock.is_paused());
+        clock.set_duration_override(Duration::new(25, 0));
+        assert_eq!(clock.elapsed(), Duration::new(10, 0));
+    }
+
+    #[test]
+    fn test_elapsed_skips_paused_time_after_resume() {
+        let mut clock = Clock::new();
+        clock.set_duration_override(Duration::new(10, 0));
+        clock.pause();
+        clock.set_duration_override(Duration::new(25, 0));
+        clock.resume();
+        assert!(!clock.is_paused());
+        clock.set_duration_override(Duration::new(30, 0));
+        assert_eq!(clock.elapsed(), Duration::new(15, 0));
+    }
+
+    #[test]
+    fn test_elapsed_starts_at_offset() {
+        let mut clock = Clock::new();
+        clock.set_offset(Duration::new(100, 0));
+        clock.set_duration_override(Duration::new(5, 0));
+        assert_eq!(clock.elapsed(), Duration::new(105, 0));
+    }
 }
@@ -1,8 +1,10 @@
+use crate::compression::decompress;
+use crate::input_formats::{headerless_size, read_recording, Event};
 use crate::settings::ConcatenateSettings;
-use asciicast::{Entry, Header};
+use crate::terminal::{Height, Width};
+use crate::vt::transcript::Transcript;
 use failure::{Error, Fail};
 use reqwest::{self, StatusCode};
-use serde_json;
 use std::fs::File;
 use std::io::copy;
 use std::io::prelude::*;
@@ -10,12 +12,6 @@ use std::io::{self, BufReader, Write};
 use std::path::PathBuf;
 use tempfile::NamedTempFile;
 
-#[derive(Debug, Fail)]
-enum ConcatenateFailure {
-    #[fail(display = "header not found")]
-    HeaderNotFound,
-}
-
 #[derive(Debug, Fail)]
 enum ReqwestFailure {
     #[fail(display = "target resource not found: {}", res)]
@@ -24,7 +20,8 @@ enum ReqwestFailure {
     Others { stat: String },
 }
 
-pub fn get_file(location: PathBuf, temp: &mut NamedTempFile) -> Result<File, Error> {
+/// Opens a local or remote recording, decompressing it on the fly if it is compressed.
+pub fn get_file(location: PathBuf, temp: &mut NamedTempFile) -> Result<Box<dyn Read>, Error> {
     let file: File;
 
     let if_url = location.to_str().unwrap().starts_with("http");
@@ -52,7 +49,41 @@ pub fn get_file(location: PathBuf, temp: &mut NamedTempFile) -> Result<File, Err
     } else {
         file = File::open(location)?;
     }
-    Ok(file)
+    Ok(decompress(file)?)
+}
+
+/// Lists markers one per line as `<number>\t<seconds>\t<label>`.
+fn write_markers<W, I>(handle: &mut W, events: I) -> Result<(), Error>
+where
+    W: Write,
+    I: Iterator<Item = Result<Event, Error>>,
+{
+    let mut number = 0;
+    for event in events {
+        if let Event::Marker(time, label) = event? {
+            number += 1;
+            writeln!(handle, "{}\t{:.3}\t{}", number, time, label)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the output as a transcript: plain lines, as left on screen.
+fn write_plain<W, I>(handle: &mut W, width: Width, height: Height, events: I) -> Result<(), Error>
+where
+    W: Write,
+    I: Iterator<Item = Result<Event, Error>>,
+{
+    let mut transcript = Transcript::new(width, height);
+    for event in events {
+        for line in transcript.feed(&event?) {
+            writeln!(handle, "{}", line)?;
+        }
+    }
+    for line in transcript.finish() {
+        writeln!(handle, "{}", line)?;
+    }
+    Ok(())
 }
 
 pub fn go(settings: &ConcatenateSettings) -> Result<(), Error> {
@@ -65,21 +96,60 @@ pub fn go(settings: &ConcatenateSettings) -> Result<(), Error> {
     let stdout = io::stdout();
     let mut handle = stdout.lock();
 
-    let mut reader = BufReader::new(file);
-    let mut line = String::new();
+    let (header, events) = read_recording(
+        BufReader::new(file),
+        headerless_size(settings.cols, settings.rows),
+    )?;
 
-    // Skip the first line, and maybe Header is needed later.
-    let _len = reader.read_line(&mut line);
-    let res: Result<Header, serde_json::Error> = serde_json::from_str(line.as_str());
-    let _header = match res {
-        Ok(h) => h,
-        Err(_) => return Err(ConcatenateFailure::HeaderNotFound.into()),
-    };
+    if settings.markers {
+        return write_markers(&mut handle, events);
+    }
+    if settings.plain {
+        let (width, height) = (Width(header.width), Height(header.height));
+        return write_plain(&mut handle, width, height, events);
+    }
 
-    for line in reader.lines() {
-        let entry: Entry = serde_json::from_str(line.unwrap().as_str())?;
-        handle.write_all(entry.event_data.as_bytes())?;
+    for event in events {
+        match event? {
+            Event::Output(_, data) | Event::Input(_, data) => handle.write_all(data.as_bytes())?,
+            // Resizes and markers aren't part of the printed output.
+            Event::Resize(..) | Event::Marker(..) | Event::Exit(..) => (),
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_markers() {
+        let events = vec![
+            Ok(Event::Output(1.0, "hello".to_string())),
+            Ok(Event::Marker(2.5, "intro".to_string())),
+            Ok(Event::Resize(3.0, Width(80), Height(24))),
+            Ok(Event::Marker(61.25, "".to_string())),
+        ];
+        let mut out = vec![];
+        write_markers(&mut out, events.into_iter()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1\t2.500\tintro\n2\t61.250\t\n"
+        );
+    }
+
+    #[test]
+    fn prints_plain_lines() {
+        let events = vec![
+            Ok(Event::Output(1.0, "$ make\r\n".to_string())),
+            Ok(Event::Input(1.5, "y".to_string())),
+            Ok(Event::Output(2.0, "\x1b[1m10%\x1b[0m\r".to_string())),
+            Ok(Event::Output(3.0, "\x1b[1m100%\x1b[0m\r\n$ ".to_string())),
+        ];
+        let mut out = vec![];
+        write_plain(&mut out, Width(20), Height(2), events.into_iter()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "$ make\n100%\n$\n");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::commands::concatenate::get_file;
+use crate::export::theme::Theme;
+use crate::export::{gif, html, svg, Playback};
+use crate::input_formats::{headerless_size, read_recording, Event, EventStream};
+use crate::settings::ExportSettings;
+use failure::{Error, Fail};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+#[derive(Debug, Fail)]
+enum ExportFailure {
+    #[fail(display = "nothing to export (expected --html, --svg or --gif)")]
+    NoTarget,
+    #[fail(display = "invalid speed: {} (expected a number above 0)", speed)]
+    InvalidSpeed { speed: f64 },
+}
+
+/// What a recording can be exported to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Target {
+    Html,
+    Svg,
+    Gif,
+}
+
+/// Returns where to write an export given without a path: to the current
+/// directory, named after the recording.
+pub fn default_path(location: &Path, extension: &str) -> PathBuf {
+    let name = location
+        .file_stem()
+        .unwrap_or_else(|| OsStr::new("asciinema"));
+    // The stem of demo.cast.gz is demo.cast.
+    Path::new(name).with_extension(extension)
+}
+
+fn get_targets(settings: &ExportSettings) -> Vec<(Target, PathBuf)> {
+    let mut targets = vec![];
+    if let Some(ref path) = settings.html {
+        targets.push((Target::Html, path.clone()));
+    }
+    if let Some(ref path) = settings.svg {
+        let path = path
+            .clone()
+            .unwrap_or_else(|| default_path(&settings.location, "svg"));
+        targets.push((Target::Svg, path));
+    }
+    if let Some(ref path) = settings.gif {
+        let path = path
+            .clone()
+            .unwrap_or_else(|| default_path(&settings.location, "gif"));
+        targets.push((Target::Gif, path));
+    }
+    targets
+}
+
+fn get_playback(
+    settings: &ExportSettings,
+    idle_time_limit: Option<f64>,
+) -> Result<Playback, Error> {
+    let speed = settings.speed.unwrap_or(1.0);
+    if !(speed > 0.0 && speed.is_finite()) {
+        return Err(ExportFailure::InvalidSpeed { speed }.into());
+    }
+    Ok(Playback {
+        idle_time_limit: settings.idle_time_limit.or(idle_time_limit),
+        speed,
+    })
+}
+
+pub fn go(settings: &ExportSettings) -> Result<(), Error> {
+    let targets = get_targets(settings);
+    if targets.is_empty() {
+        return Err(ExportFailure::NoTarget.into());
+    }
+    let theme: Theme = match settings.theme {
+        Some(ref theme) => theme.parse()?,
+        None => Theme::default(),
+    };
+
+    let mut temp: NamedTempFile = NamedTempFile::new()?;
+    let file = get_file(settings.location.clone(), &mut temp)?;
+    let (header, events) = read_recording(
+        BufReader::new(file),
+        headerless_size(settings.cols, settings.rows),
+    )?;
+    let playback = get_playback(settings, header.idle_time_limit)?;
+    // Read once for all of the targets.
+    let events: Vec<Event> = events.collect::<Result<_, _>>()?;
+
+    for (target, path) in targets {
+        let events: EventStream = Box::new(events.clone().into_iter().map(Ok));
+        let out = BufWriter::new(File::create(&path)?);
+        match target {
+            Target::Html => html::write(out, &header, events, playback, &theme)?,
+            Target::Svg => svg::write(out, &header, events, playback, &theme)?,
+            Target::Gif => gif::write(out, &header, events, playback, &theme)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_exports_after_the_recording() {
+        assert_eq!(
+            default_path(Path::new("casts/demo.cast"), "svg"),
+            PathBuf::from("demo.svg")
+        );
+        assert_eq!(
+            default_path(Path::new("demo.cast.gz"), "svg"),
+            PathBuf::from("demo.svg")
+        );
+        assert_eq!(
+            default_path(Path::new("https://asciinema.org/a/123"), "svg"),
+            PathBuf::from("123.svg")
+        );
+    }
+}
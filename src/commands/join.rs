@@ -0,0 +1,215 @@
+use crate::compression::decompress;
+use crate::input_formats::{headerless_size, read_recording, Event};
+use crate::settings::JoinSettings;
+use asciicast::Header;
+use failure::{Error, Fail};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+#[derive(Debug, Fail)]
+enum JoinFailure {
+    #[fail(display = "unable to join {}: {}", path, error)]
+    Unreadable { path: String, error: Error },
+    #[fail(
+        display = "unable to join {}: size {}x{} differs from the first recording's {}x{}",
+        path, width, height, first_width, first_height
+    )]
+    SizeMismatch {
+        path: String,
+        width: u32,
+        height: u32,
+        first_width: u32,
+        first_height: u32,
+    },
+}
+
+/// Returns the asciicast v2 line of `event`, moved `offset` seconds later.
+fn event_line(event: &Event, offset: f64) -> Result<String, Error> {
+    let time = offset + event.time();
+    Ok(match *event {
+        Event::Output(_, ref data) => serde_json::to_string(&(time, "o", data))?,
+        Event::Input(_, ref data) => serde_json::to_string(&(time, "i", data))?,
+        Event::Resize(_, ref width, ref height) => {
+            serde_json::to_string(&(time, "r", format!("{}x{}", width.0, height.0)))?
+        }
+        Event::Marker(_, ref label) => serde_json::to_string(&(time, "m", label))?,
+        Event::Exit(_, code) => serde_json::to_string(&(time, "x", code.to_string()))?,
+    })
+}
+
+/// Writes `casts` to `out` as one asciicast v2 recording, in the given order.
+///
+/// Each recording continues from the last event of the one before it, which
+/// is where a segment of a split recording starts. The header of the first
+/// recording is used for the whole. Recordings in other formats are read
+/// like everywhere else, ttyrec ones being taken as 80x24. Exit events are
+/// left out unless `exit_event`, they aren't part of asciicast v2.
+fn join_casts<R, W>(casts: Vec<(String, R)>, exit_event: bool, out: &mut W) -> Result<(), Error>
+where
+    R: BufRead + 'static,
+    W: Write,
+{
+    let mut first: Option<Header> = None;
+    let mut offset = 0.0;
+    for (path, reader) in casts {
+        let (header, events) =
+            read_recording(reader, headerless_size(None, None)).map_err(|error| {
+                JoinFailure::Unreadable {
+                    path: path.clone(),
+                    error,
+                }
+            })?;
+        match first {
+            Some(ref f) if (f.width, f.height) != (header.width, header.height) => {
+                return Err(JoinFailure::SizeMismatch {
+                    path,
+                    width: header.width,
+                    height: header.height,
+                    first_width: f.width,
+                    first_height: f.height,
+                }
+                .into());
+            }
+            Some(_) => (),
+            None => {
+                let header = Header {
+                    version: 2,
+                    ..header
+                };
+                writeln!(out, "{}", serde_json::to_string(&header)?)?;
+                first = Some(header);
+            }
+        }
+
+        let mut last_time = 0.0;
+        for event in events {
+            let event = event?;
+            last_time = event.time();
+            if let Event::Exit(..) = event {
+                if !exit_event {
+                    continue;
+                }
+            }
+            writeln!(out, "{}", event_line(&event, offset)?)?;
+        }
+        offset += last_time;
+    }
+    Ok(())
+}
+
+pub fn go(settings: &JoinSettings) -> Result<(), Error> {
+    let casts = settings
+        .files
+        .iter()
+        .map(|path| {
+            let file = decompress(File::open(path)?)?;
+            Ok((path.to_string_lossy().into_owned(), BufReader::new(file)))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    join_casts(casts, settings.exit_event, &mut handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn join_with(casts: &[&str], exit_event: bool) -> Result<String, Error> {
+        let casts = casts
+            .iter()
+            .enumerate()
+            .map(|(i, cast)| (format!("{}.cast", i + 1), Cursor::new(cast.to_string())))
+            .collect();
+        let mut out = vec![];
+        join_casts(casts, exit_event, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn join(casts: &[&str]) -> Result<String, Error> {
+        join_with(casts, false)
+    }
+
+    #[test]
+    fn continues_from_the_previous_recording() {
+        let joined = join(&[
+            "{\"version\": 2, \"width\": 80, \"height\": 24, \"title\": \"demo\"}\n\
+             [0.5, \"o\", \"a\"]\n\
+             [1.0, \"m\", \"intro\"]\n",
+            "{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+             [0.25, \"o\", \"b\"]\n",
+        ])
+        .unwrap();
+        assert_eq!(
+            joined,
+            "{\"version\":2,\"width\":80,\"height\":24,\"title\":\"demo\"}\n\
+             [0.5,\"o\",\"a\"]\n\
+             [1.0,\"m\",\"intro\"]\n\
+             [1.25,\"o\",\"b\"]\n"
+        );
+    }
+
+    #[test]
+    fn keeps_exit_events_only_if_asked_to() {
+        let cast = "{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+                    [0.5, \"o\", \"a\"]\n\
+                    [1.0, \"x\", \"2\"]\n";
+        let header = "{\"version\":2,\"width\":80,\"height\":24}\n";
+        assert_eq!(
+            join(&[cast]).unwrap(),
+            format!("{}[0.5,\"o\",\"a\"]\n", header)
+        );
+        assert_eq!(
+            join_with(&[cast], true).unwrap(),
+            format!("{}[0.5,\"o\",\"a\"]\n[1.0,\"x\",\"2\"]\n", header)
+        );
+    }
+
+    #[test]
+    fn rejects_different_sizes() {
+        let result = join(&[
+            "{\"version\": 2, \"width\": 80, \"height\": 24}\n",
+            "{\"version\": 2, \"width\": 100, \"height\": 40}\n",
+        ]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "unable to join 2.cast: size 100x40 differs from the first recording's 80x24"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_headers() {
+        let result = join(&["[0.5, \"o\", \"a\"]\n"]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "unable to join 1.cast: header not found"
+        );
+        assert!(join(&[""]).is_err());
+    }
+
+    #[test]
+    fn joins_v1_recordings() {
+        let joined = join(&[
+            "{\"version\": 1, \"width\": 80, \"height\": 24, \"stdout\": [[0.5, \"a\"]]}\n",
+            "{\"version\": 2, \"width\": 80, \"height\": 24}\n[0.25, \"o\", \"b\"]\n",
+        ])
+        .unwrap();
+        assert_eq!(
+            joined,
+            "{\"version\":2,\"width\":80,\"height\":24}\n\
+             [0.5,\"o\",\"a\"]\n\
+             [0.75,\"o\",\"b\"]\n"
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let result = join(&["{\"version\": 3, \"width\": 80, \"height\": 24}\n"]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "unable to join 1.cast: unsupported asciicast version 3"
+        );
+    }
+}
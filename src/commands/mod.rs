@@ -1,5 +1,10 @@
 pub mod authenticate;
 pub mod concatenate;
+pub mod export;
+pub mod join;
 pub mod play;
+pub mod poster;
 pub mod record;
+pub mod repair;
+pub mod snapshot;
 pub mod upload;
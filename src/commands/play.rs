@@ -1,68 +1,111 @@
 use crate::clock::get_elapsed_seconds;
 use crate::commands::concatenate::get_file;
+use crate::compression::decompress;
+use crate::input_formats::{headerless_size, read_recording, script, Event, EventStream};
 use crate::settings::PlaySettings;
-use asciicast::{Entry, Header};
-use failure::{Error, Fail};
-use serde_json;
+use crate::terminal::{Height, Width};
+use failure::Error;
 use std::fs::File;
-use std::io::prelude::*;
 use std::io::{self, BufReader, StdoutLock, Write};
 use std::time::Instant;
 use tempfile::NamedTempFile;
 use termion;
 
-#[derive(Debug, Fail)]
-enum PlayFailure {
-    #[fail(display = "header not found")]
-    HeaderNotFound,
+/// The largest terminal size requested by resize events during playback.
+#[derive(Default)]
+struct RequestedSize {
+    cols: u32,
+    rows: u32,
+}
+
+fn write_event(
+    handle: &mut StdoutLock,
+    event: &Event,
+    requested: &mut RequestedSize,
+) -> Result<(), Error> {
+    match event {
+        Event::Output(_, data) | Event::Input(_, data) => {
+            handle.write_all(data.as_bytes())?;
+        }
+        Event::Resize(_, Width(cols), Height(rows)) => {
+            // Ask the terminal to resize itself (xterm window manipulation).
+            // Terminals that don't support it ignore the sequence and we warn
+            // once playback is done.
+            write!(handle, "\x1b[8;{};{}t", rows, cols)?;
+            requested.cols = requested.cols.max(*cols);
+            requested.rows = requested.rows.max(*rows);
+        }
+        // Markers only matter to tools navigating the recording.
+        Event::Marker(..) | Event::Exit(..) => (),
+    }
+    handle.flush()?;
+    Ok(())
+}
+
+fn warn_if_too_small(requested: &RequestedSize) {
+    if requested.cols == 0 && requested.rows == 0 {
+        return;
+    }
+    // Nothing to compare with when playing into a pipe or a file.
+    let (cols, rows) = match termion::terminal_size() {
+        Ok(size) => size,
+        Err(_) => return,
+    };
+    if u32::from(cols) < requested.cols || u32::from(rows) < requested.rows {
+        eprintln!(
+            "warning: the recording was resized to {}x{} but your terminal is {}x{}, \
+             playback may look wrong",
+            requested.cols, requested.rows, cols, rows
+        );
+    }
 }
 
 fn write_with_time_limit(
     handle: &mut StdoutLock,
-    reader: BufReader<File>,
+    events: EventStream,
     idle_time_limit: Option<f64>,
     speed_factor: f64,
-) -> Result<(), Error> {
+) -> Result<RequestedSize, Error> {
     let mut t = 0.0_f64;
     let mut last = 0.0_f64;
     let limit = idle_time_limit.unwrap();
     let base = Instant::now();
+    let mut requested = RequestedSize::default();
 
-    for line in reader.lines() {
-        let entry: Entry = serde_json::from_str(line.unwrap().as_str())?;
-        let delay = entry.time - last;
-        last = entry.time;
+    for event in events {
+        let event = event?;
+        let delay = event.time() - last;
+        last = event.time();
         t += limit.min(delay);
 
         loop {
             if t * speed_factor <= get_elapsed_seconds(&base.elapsed()) {
-                handle.write_all(entry.event_data.as_bytes())?;
-                handle.flush()?;
+                write_event(handle, &event, &mut requested)?;
                 break;
             }
         }
     }
-    Ok(())
+    Ok(requested)
 }
 
 fn write_without_time_limit(
     handle: &mut StdoutLock,
-    reader: BufReader<File>,
+    events: EventStream,
     speed_factor: f64,
-) -> Result<(), Error> {
+) -> Result<RequestedSize, Error> {
     let base = Instant::now();
-    for line in reader.lines() {
-        let entry: Entry = serde_json::from_str(line.unwrap().as_str())?;
+    let mut requested = RequestedSize::default();
+    for event in events {
+        let event = event?;
 
         loop {
-            if entry.time * speed_factor <= get_elapsed_seconds(&base.elapsed()) {
-                handle.write_all(entry.event_data.as_bytes())?;
-                handle.flush()?;
+            if event.time() * speed_factor <= get_elapsed_seconds(&base.elapsed()) {
+                write_event(handle, &event, &mut requested)?;
                 break;
             }
         }
     }
-    Ok(())
+    Ok(requested)
 }
 
 pub fn go(settings: &PlaySettings) -> Result<(), Error> {
@@ -81,15 +124,13 @@ pub fn go(settings: &PlaySettings) -> Result<(), Error> {
         handle.flush()?;
     }
 
-    let mut reader = BufReader::new(file);
-    let mut line = String::new();
-
-    // Skip the first line, and maybe Header is needed later.
-    let _len = reader.read_line(&mut line);
-    let res: Result<Header, serde_json::Error> = serde_json::from_str(line.as_str());
-    let header = match res {
-        Ok(h) => h,
-        Err(_) => return Err(PlayFailure::HeaderNotFound.into()),
+    let size = headerless_size(settings.cols, settings.rows);
+    let (header, events) = match settings.timing {
+        Some(ref timing) => {
+            let timing = decompress(File::open(timing)?)?;
+            script::read(BufReader::new(file), BufReader::new(timing), size)?
+        }
+        None => read_recording(BufReader::new(file), size)?,
     };
 
     let idle_time_limit = if settings.idle_time_limit.is_some() {
@@ -105,11 +146,11 @@ pub fn go(settings: &PlaySettings) -> Result<(), Error> {
         None => 1.0,
     };
 
-    if idle_time_limit.is_some() {
-        write_with_time_limit(&mut handle, reader, idle_time_limit, speed_factor)?;
+    let requested = if idle_time_limit.is_some() {
+        write_with_time_limit(&mut handle, events, idle_time_limit, speed_factor)?
     } else {
-        write_without_time_limit(&mut handle, reader, speed_factor)?;
-    }
+        write_without_time_limit(&mut handle, events, speed_factor)?
+    };
 
     // Restore the cursor if it was previously hidden.
     if settings.hide_cursor {
@@ -117,5 +158,7 @@ pub fn go(settings: &PlaySettings) -> Result<(), Error> {
         handle.flush()?;
     }
 
+    warn_if_too_small(&requested);
+
     Ok(())
 }
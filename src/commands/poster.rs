@@ -0,0 +1,51 @@
+use crate::commands::concatenate::get_file;
+use crate::commands::export::default_path;
+use crate::commands::snapshot::{screen_at, At};
+use crate::export::png;
+use crate::export::theme::Theme;
+use crate::input_formats::{headerless_size, read_recording};
+use crate::settings::PosterSettings;
+use failure::{Error, Fail};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use tempfile::NamedTempFile;
+
+/// The largest font scale, which already makes a 80x24 screen a 5904x3600
+/// image.
+const MAX_FONT_SCALE: usize = 8;
+
+#[derive(Debug, Fail)]
+enum PosterFailure {
+    #[fail(display = "invalid font scale: {} (expected 1 to {})", scale, max)]
+    InvalidFontScale { scale: usize, max: usize },
+}
+
+pub fn go(settings: &PosterSettings) -> Result<(), Error> {
+    let at: At = settings.at.parse()?;
+    let scale = settings.font_scale;
+    if scale == 0 || scale > MAX_FONT_SCALE {
+        return Err(PosterFailure::InvalidFontScale {
+            scale,
+            max: MAX_FONT_SCALE,
+        }
+        .into());
+    }
+    let theme: Theme = match settings.theme {
+        Some(ref theme) => theme.parse()?,
+        None => Theme::default(),
+    };
+
+    let mut temp: NamedTempFile = NamedTempFile::new()?;
+    let file = get_file(settings.location.clone(), &mut temp)?;
+    let (header, events) = read_recording(
+        BufReader::new(file),
+        headerless_size(settings.cols, settings.rows),
+    )?;
+    let vt = screen_at(&header, events, at)?;
+
+    let path = settings
+        .output
+        .clone()
+        .unwrap_or_else(|| default_path(&settings.location, "png"));
+    png::write(BufWriter::new(File::create(path)?), &vt, &theme, scale)
+}
@@ -1,6 +1,11 @@
-use crate::output_formats::asciicast::AsciicastOutput;
-use crate::output_formats::raw::RawOutput;
-use crate::output_formats::Output;
+use crate::compression::{decompress, Compression};
+use crate::hotkeys::{Action, Hotkeys, Key};
+use crate::input_formats::{read_asciicast, Event};
+use crate::output_formats::segmented::{
+    parse_duration, parse_size, segment_path, SegmentedOutput, Split,
+};
+use crate::output_formats::{Durability, Format, Msg, Output, Sink};
+use crate::redact::Redactor;
 use crate::session::Session;
 use crate::settings::RecordSettings;
 use crate::terminal::{Height, Width};
@@ -9,11 +14,21 @@ use failure::{err_msg, Error, Fail, ResultExt};
 use pty_shell::*;
 use std::collections::HashMap;
 use std::env;
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
+use std::io;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::io::BufReader;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
 use std::result::Result;
 use std::str;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
 use tempfile::NamedTempFile;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use termion;
@@ -23,6 +38,24 @@ use url::Url;
 enum RecordFailure {
     #[fail(display = "unable to write to file: {}: file exists", path)]
     FileExists { path: String },
+    #[fail(display = "invalid output: {} (expected FORMAT:PATH)", spec)]
+    InvalidOutput { spec: String },
+    #[fail(display = "unable to append to {}: {}", path, error)]
+    Unreadable { path: String, error: Error },
+    #[fail(
+        display = "unable to append to {}: asciicast v1 is a single document, nothing can follow it",
+        path
+    )]
+    UnappendableV1 { path: String },
+    #[fail(
+        display = "unable to append to {}: typescripts can't be continued, their timing would be off",
+        path
+    )]
+    UnappendableScript { path: String },
+    #[fail(display = "only asciicast recordings can be split")]
+    UnsplittableFormat,
+    #[fail(display = "{} of the outputs could not be written", count)]
+    OutputsFailed { count: usize },
 }
 
 fn capture_environment_vars(keys: Vec<&str>) -> HashMap<String, String> {
@@ -49,65 +82,420 @@ where
     child_env
 }
 
+/// Returns the command line to run, `shell` being the user's `SHELL` if set.
+fn get_command_for_child(command: Option<String>, shell: Option<String>) -> Vec<String> {
+    match command {
+        // Like the reference python client, let `sh` deal with quoting and
+        // `PATH` lookup of the command line.
+        Some(c) => vec!["sh".to_string(), "-c".to_string(), c],
+        None => vec![shell.unwrap_or_else(|| "sh".to_string())],
+    }
+}
+
 pub enum RecordLocation {
-    Local(PathBuf),
+    Local(Vec<PathBuf>),
     Remote(Url),
 }
 
+/// An extra output given with `--output FORMAT:PATH`.
+#[derive(Debug, PartialEq)]
+struct OutputSpec {
+    format: Format,
+    path: PathBuf,
+}
+
+impl FromStr for OutputSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(format), Some(path)) if !path.is_empty() => Ok(OutputSpec {
+                format: format.parse()?,
+                path: PathBuf::from(path),
+            }),
+            _ => Err(RecordFailure::InvalidOutput {
+                spec: s.to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+/// Where an existing recording left off, so appending can continue from there.
+#[derive(Debug, PartialEq)]
+struct Existing {
+    width: u32,
+    height: u32,
+    last_time: f64,
+}
+
+fn read_existing<R: BufRead + 'static>(
+    mut reader: R,
+    path: &Path,
+) -> Result<Option<Existing>, Error> {
+    // Appending to an empty file starts a new recording.
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let (header, events) = read_asciicast(reader).map_err(|error| RecordFailure::Unreadable {
+        path: path.to_string_lossy().into_owned(),
+        error,
+    })?;
+
+    let mut existing = Existing {
+        width: header.width,
+        height: header.height,
+        last_time: 0.0,
+    };
+    for event in events {
+        let event = event?;
+        // The size the recording ended with is what we continue from.
+        if let Event::Resize(_, ref width, ref height) = event {
+            existing.width = width.0;
+            existing.height = height.0;
+        }
+        existing.last_time = existing.last_time.max(event.time());
+    }
+    Ok(Some(existing))
+}
+
+/// Reads the asciicast recordings we are about to append to.
+///
+/// New events have to come after the latest event of any of them.
+fn get_existing(paths: &[&Path]) -> Result<Option<Existing>, Error> {
+    let mut result: Option<Existing> = None;
+    for path in paths {
+        if !path.exists() {
+            continue;
+        }
+        let file = decompress(File::open(path)?)?;
+        if let Some(existing) = read_existing(BufReader::new(file), path)? {
+            result = Some(match result {
+                Some(r) => Existing {
+                    last_time: r.last_time.max(existing.last_time),
+                    ..r
+                },
+                None => existing,
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// A running output thread and the name to report its failure under.
+struct RunningOutput {
+    name: String,
+    thread: JoinHandle<Result<(), Error>>,
+}
+
+/// A finished recording.
+pub struct Recording {
+    pub location: RecordLocation,
+    /// The recorded command's exit code, like a shell would report it.
+    pub exit_code: i32,
+}
+
+fn get_exit_code(status: &ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
 struct Shell {
-    session: Box<Session>,
+    session: Arc<Mutex<Session>>,
+    // Told once all output of the child went through the session.
+    finished: Sender<()>,
+    hotkeys: Option<Hotkeys>,
+    pty: Option<RawFd>,
+    // Whether the input being handled was typed with echo off, e.g. a password.
+    input_hidden: bool,
+}
+
+impl Shell {
+    fn session(&self) -> MutexGuard<'_, Session> {
+        self.session.lock().expect("session lock")
+    }
 }
 
 impl PtyHandler for Shell {
+    fn filter_input(&mut self, input: &[u8]) -> Vec<u8> {
+        // Check before the child gets the input, it may turn echo back on right after.
+        self.input_hidden = self
+            .pty
+            .map(|fd| is_input_hidden(fd).unwrap_or(false))
+            .unwrap_or(false);
+
+        let hotkeys = match self.hotkeys {
+            Some(ref mut h) => h,
+            None => return input.to_vec(),
+        };
+        let (forward, actions) = hotkeys.filter(input);
+        for action in actions {
+            match action {
+                Action::AddMarker(label) => {
+                    if label.is_some() {
+                        write_prompt("\r\n").expect("unable to write label");
+                    }
+                    self.session()
+                        .write_marker(label)
+                        .expect("unable to write marker")
+                }
+                Action::StartLabel => {
+                    write_prompt("\r\n[MARKER] label: ").expect("unable to write label")
+                }
+                Action::EchoLabel(echo) => write_prompt(&echo).expect("unable to write label"),
+                Action::CancelLabel => write_prompt("\r\n").expect("unable to write label"),
+                Action::TogglePause => {
+                    let paused = self
+                        .session()
+                        .toggle_pause()
+                        .expect("unable to pause recording");
+                    // The terminal is in raw mode by now, so we need explicit carriage returns.
+                    let (cols, _) = termion::terminal_size().unwrap_or((80, 24));
+                    if paused {
+                        write_banner(PAUSED_BANNER, Color::Yellow, cols, "\r\n")
+                    } else {
+                        write_banner(RECORDING_BANNER, Color::Red, cols, "\r\n")
+                    }
+                    .expect("unable to write banner");
+                }
+            }
+        }
+        forward
+    }
+
     fn input(&mut self, input: &[u8]) {
-        self.session
+        if self.input_hidden {
+            return;
+        }
+        self.session()
             .write_input(input)
             .expect("unable to write input");
     }
 
     fn output(&mut self, output: &[u8]) {
-        self.session
+        self.session()
             .write_output(output)
             .expect("unable to write output");
     }
 
-    fn resize(&mut self, _winsize: &winsize::Winsize) {
-        /* do something with winsize */
+    fn resize(&mut self, winsize: &winsize::Winsize) {
+        self.session()
+            .write_resize(
+                &Height(u32::from(winsize.ws_row)),
+                &Width(u32::from(winsize.ws_col)),
+            )
+            .expect("unable to write resize");
     }
 
     fn shutdown(&mut self) {
-        /* prepare for shutdown */
-        self.session.end().expect("unable to end session");
+        // The session is ended once we know how the child exited.
+        let _ = self.finished.send(());
+    }
+}
+
+const RECORDING_BANNER: &str = "  \u{1F534}  [RECORDING]  ";
+const PAUSED_BANNER: &str = "  \u{23F8}  [PAUSED]  ";
+
+fn write_banner(text: &str, color: Color, cols: u16, newline: &str) -> Result<(), Error> {
+    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    write!(&mut stdout, "{}", newline)?;
+    stdout.set_color(ColorSpec::new().set_fg(Some(color)))?;
+    let banner = format!("{:\u{2B07}^1$}", text, cols as usize);
+    write!(&mut stdout, "{}{}{}", banner, newline, newline)?;
+    stdout.reset()?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Writes to the terminal without it being recorded, e.g. a marker label
+/// being typed.
+fn write_prompt<T: AsRef<[u8]>>(text: T) -> Result<(), Error> {
+    let mut stdout = io::stdout();
+    stdout.write_all(text.as_ref())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Returns the terminal size to use when recording without a terminal.
+///
+/// That is the case when an explicit size is given or stdin isn't a terminal
+/// (e.g. on CI), so there is no size to take from it anyway.
+fn get_headless_size(settings: &RecordSettings, stdin_is_tty: bool) -> Option<(u16, u16)> {
+    if settings.cols.is_none() && settings.rows.is_none() && stdin_is_tty {
+        return None;
+    }
+    Some((settings.cols.unwrap_or(80), settings.rows.unwrap_or(24)))
+}
+
+fn get_output_specs(settings: &RecordSettings) -> Result<Vec<OutputSpec>, Error> {
+    settings.outputs.iter().map(|spec| spec.parse()).collect()
+}
+
+fn validate_path(path: &Path, settings: &RecordSettings) -> Result<(), Error> {
+    let exists = path.exists();
+    // Create a new file if it doesn't exist or we were told to overwrite.
+    if !exists || settings.overwrite {
+        return Ok(());
+    }
+    if exists && settings.append {
+        // Append to existing file if we are told to do so.
+        return Ok(());
+    }
+
+    Err(RecordFailure::FileExists {
+        path: path.to_string_lossy().into_owned(),
+    }
+    .into())
+}
+
+/// Returns when to start a new file, if the recording is split into several.
+fn get_split(settings: &RecordSettings) -> Result<Option<Split>, Error> {
+    if settings.split_every.is_none() && settings.max_segment_size.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(Split {
+        every: settings
+            .split_every
+            .as_deref()
+            .map(parse_duration)
+            .transpose()?,
+        max_size: settings
+            .max_segment_size
+            .as_deref()
+            .map(parse_size)
+            .transpose()?,
+    }))
+}
+
+/// Returns the format FILE is saved in.
+fn get_format(settings: &RecordSettings) -> Result<Format, Error> {
+    if settings.raw {
+        return Ok(Format::Raw);
+    }
+    match settings.format {
+        Some(ref f) => f.parse(),
+        None => Ok(Format::Asciicast),
+    }
+}
+
+fn validate_append(path: &Path, format: Format, settings: &RecordSettings) -> Result<(), Error> {
+    if !settings.append || !path.exists() {
+        return Ok(());
+    }
+    let path = path.to_string_lossy().into_owned();
+    match format {
+        Format::AsciicastV1 => Err(RecordFailure::UnappendableV1 { path }.into()),
+        // A typescript starts and ends with a line the timing file doesn't
+        // cover, so its byte offsets would be off after the first one.
+        Format::Script(_) => Err(RecordFailure::UnappendableScript { path }.into()),
+        _ => Ok(()),
     }
 }
 
 fn validate_output_path(settings: &RecordSettings) -> Result<(), Error> {
-    match settings.file {
-        Some(ref x) => {
-            let exists = x.as_path().exists();
-            // Create a new file if it doesn't exist or we were told to overwrite.
-            if !exists || settings.overwrite {
-                return Ok(());
+    if let Some(ref x) = settings.file {
+        let format = get_format(settings)?;
+        // Later segments are checked as they are started.
+        match get_split(settings)? {
+            Some(_) if format != Format::Asciicast => {
+                return Err(RecordFailure::UnsplittableFormat.into());
             }
-            if exists && settings.append {
-                // Append to existing file if we are told to do so.
-                return Ok(());
+            Some(_) => validate_path(&segment_path(x, 1), settings)?,
+            None => {
+                for path in format.paths(x) {
+                    validate_path(&path, settings)?;
+                }
             }
+        }
+        validate_append(x, format, settings)?;
+    }
+    for spec in get_output_specs(settings)? {
+        for path in spec.format.paths(&spec.path) {
+            validate_path(&path, settings)?;
+        }
+        validate_append(&spec.path, spec.format, settings)?;
+    }
+    Ok(())
+}
 
-            return Err(RecordFailure::FileExists {
-                path: x.as_path().to_string_lossy().into_owned(),
-            }
-            .into());
+fn get_durability(settings: &RecordSettings) -> Result<Durability, Error> {
+    match settings.durability {
+        Some(ref d) => d.parse(),
+        None => Ok(Durability::default()),
+    }
+}
+
+/// Returns the compression a local output at `path` is written with.
+fn get_compression(path: &Path, settings: &RecordSettings) -> Result<Option<Compression>, Error> {
+    match Compression::from_path(path) {
+        Some(c) => Ok(Some(c)),
+        None => settings.compress.as_deref().map(str::parse).transpose(),
+    }
+}
+
+/// Whether the output at `path` continues a recording already in its file,
+/// in which case it must not get another header.
+fn continues_recording(path: &Path, settings: &RecordSettings) -> bool {
+    settings.append && path.metadata().map(|m| m.len() > 0).unwrap_or(false)
+}
+
+fn open_output(path: &Path, settings: &RecordSettings) -> Result<Sink, Error> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(settings.overwrite)
+        .append(settings.append)
+        .open(path)?;
+    Ok(Sink::new(
+        file,
+        get_compression(path, settings)?,
+        get_durability(settings)?,
+    )?)
+}
+
+/// Keeps a recording that was meant to be uploaded so it can be uploaded later.
+fn keep_recording(tmp: NamedTempFile) -> Result<PathBuf, Error> {
+    let (_, path) = tmp.keep()?;
+    eprintln!(
+        "recording kept, upload it later with: asciinema upload {}",
+        path.to_string_lossy()
+    );
+    Ok(path)
+}
+
+/// Waits for every output to finish, reporting each one that failed.
+fn join_outputs(outputs: Vec<RunningOutput>) -> Result<(), Error> {
+    let mut count = 0;
+    for output in outputs {
+        if let Err(e) = output.thread.join().expect("join thread") {
+            eprintln!("unable to write {}: {}", output.name, e);
+            count += 1;
         }
-        None => Ok(()),
     }
+    if count > 0 {
+        return Err(RecordFailure::OutputsFailed { count }.into());
+    }
+    Ok(())
 }
 
-pub fn go(settings: &RecordSettings, builder: &mut UploadBuilder) -> Result<RecordLocation, Error> {
+pub fn go(settings: &RecordSettings, builder: &mut UploadBuilder) -> Result<Recording, Error> {
     // First check to see if we should even start recording.
     validate_output_path(settings)?;
+    let redactor = Redactor::new(&settings.redact)?;
+    let durability = get_durability(settings)?;
+    let hotkeys = match settings.prefix_key {
+        Some(ref k) => Some(Hotkeys::new(k.parse::<Key>()?)),
+        None => None,
+    };
 
-    let (cols, rows) = termion::terminal_size().context("Cannot get terminal size")?;
+    let headless_size = get_headless_size(settings, termion::is_tty(&io::stdin()));
+    let (cols, rows) = match headless_size {
+        Some(size) => size,
+        None => termion::terminal_size().context("Cannot get terminal size")?,
+    };
 
     // HACK: This is ugly, look away!
     // 1. We create a named temp file so we can get the path. Why? Because our uploader uses
@@ -121,83 +509,195 @@ pub fn go(settings: &RecordSettings, builder: &mut UploadBuilder) -> Result<Reco
     //    can process it.
     //
     // Sigh, I need to get better at Rust but this works.
-    let tmp = NamedTempFile::new()?;
+    let tmp = tempfile::Builder::new()
+        .prefix("asciinema-")
+        .suffix(".cast")
+        .tempfile()?;
     let tmp_path = tmp.path().to_path_buf();
-    let handle = match settings.file.clone() {
-        Some(p) => OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(settings.overwrite)
-            .append(settings.append)
-            .open(&p)?,
-        None => tmp.reopen()?,
+    let specs = get_output_specs(settings)?;
+    // Only upload when there is nowhere local to save to.
+    let upload = settings.file.is_none() && specs.is_empty();
+
+    // Read what we are appending to before any output touches it.
+    let existing = if settings.append {
+        let mut paths: Vec<&Path> = specs
+            .iter()
+            .filter(|spec| spec.format == Format::Asciicast)
+            .map(|spec| spec.path.as_path())
+            .collect();
+        if let (Some(ref p), Format::Asciicast) = (&settings.file, get_format(settings)?) {
+            paths.insert(0, p.as_path());
+        }
+        get_existing(&paths)?
+    } else {
+        None
     };
 
-    let output_thread;
-    let output_channel;
+    // Outputs starting a new file get a header, those appended to don't.
+    let mut channels: Vec<Sender<Msg>> = vec![];
+    let mut continued: Vec<Sender<Msg>> = vec![];
+    let mut outputs = vec![];
+    let mut spawn_output = |name: String, continues: bool, (channel, thread)| {
+        if continues {
+            continued.push(channel);
+        } else {
+            channels.push(channel);
+        }
+        outputs.push(RunningOutput { name, thread });
+    };
 
-    if settings.raw {
-        let output = RawOutput::new(handle);
-        output_channel = output.channel();
-        output_thread = output.spawn();
-    } else {
-        let output = AsciicastOutput::new(handle);
-        output_channel = output.channel();
-        output_thread = output.spawn();
+    let format = get_format(settings)?;
+    let mut segments = None;
+    match (&settings.file, get_split(settings)?) {
+        (Some(p), Some(split)) => {
+            let output = SegmentedOutput::new(
+                p.clone(),
+                settings.overwrite,
+                split,
+                get_compression(p, settings)?,
+                durability,
+                settings.exit_event,
+            );
+            segments = Some(output.segments());
+            // Segments are always new files.
+            spawn_output(
+                p.to_string_lossy().into_owned(),
+                false,
+                (output.channel(), output.spawn()),
+            );
+        }
+        (Some(p), None) => spawn_output(
+            p.to_string_lossy().into_owned(),
+            continues_recording(p, settings),
+            format.spawn(p, settings.exit_event, |p| open_output(p, settings))?,
+        ),
+        (None, _) if upload => spawn_output(
+            "recording".to_string(),
+            false,
+            // Uploads are never compressed.
+            format.spawn(&tmp_path, settings.exit_event, |_| {
+                Ok(Sink::new(tmp.reopen()?, None, durability)?)
+            })?,
+        ),
+        (None, _) => (),
+    }
+    for spec in &specs {
+        spawn_output(
+            spec.path.to_string_lossy().into_owned(),
+            continues_recording(&spec.path, settings),
+            spec.format.spawn(&spec.path, settings.exit_event, |p| {
+                open_output(p, settings)
+            })?,
+        );
     }
 
-    let mut session = Box::new(Session::new(vec![output_channel], settings.record_stdin));
+    let mut session = Session::new(channels, settings.record_stdin);
+    session.set_redactor(redactor);
+    session.write_header(
+        &Height(u32::from(rows)),
+        &Width(u32::from(cols)),
+        settings.idle_time_limit,
+        settings.command.clone(),
+        settings.title.clone(),
+        Some(capture_environment_vars(vec!["SHELL", "TERM"])),
+    )?;
+    session.add_outputs(continued);
 
-    if !settings.append {
-        session.write_header(
-            &Height(u32::from(rows)),
-            &Width(u32::from(cols)),
-            settings.idle_time_limit,
-            None, // TODO: Command.
-            settings.title.clone(),
-            Some(capture_environment_vars(vec!["SHELL", "TERM"])),
-        )?;
+    if let Some(ref e) = existing {
+        session.set_start_time(e.last_time);
+        if (e.width, e.height) != (u32::from(cols), u32::from(rows)) {
+            // Keep the recording correct for players, they resize with it.
+            eprintln!(
+                "warning: terminal size {}x{} differs from the recording's {}x{}",
+                cols, rows, e.width, e.height
+            );
+            session.write_resize(&Height(u32::from(rows)), &Width(u32::from(cols)))?;
+        }
     }
 
     // Write out the recording banner for interactive sessions.
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
-    writeln!(&mut stdout, "{}", "".to_string())?;
-    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-    let rec = format!(
-        "{:\u{2B07}^1$}",
-        "  \u{1F534}  [RECORDING]  ", cols as usize
-    );
-    writeln!(&mut stdout, "{}\n", rec)?;
-    stdout.reset()?;
-    stdout.flush()?;
+    if headless_size.is_none() {
+        write_banner(RECORDING_BANNER, Color::Red, cols, "\n")?;
+    }
 
     let child = tty::Fork::from_ptmx()?;
     restore_termios();
-    let shell = Shell { session };
-    child.proxy(shell)?;
+    // Let the child decide whether to exit, we finish the recording when it does.
+    child.forward_signals(&[libc::SIGINT, libc::SIGTERM, libc::SIGHUP])?;
+    let session = Arc::new(Mutex::new(session));
+    let (finished, finished_rx) = channel();
+    let shell = Shell {
+        session: session.clone(),
+        finished,
+        hotkeys,
+        pty: child.is_parent().ok().map(|master| master.as_raw_fd()),
+        input_hidden: false,
+    };
+    match headless_size {
+        Some(_) => {
+            let size = winsize::Winsize {
+                ws_row: rows,
+                ws_col: cols,
+                ..Default::default()
+            };
+            child.proxy_headless(shell, &size)?
+        }
+        None => child.proxy(shell)?,
+    }
 
     let child_env = get_environment_for_child(env::vars());
 
-    child.exec_with_env(
-        env::var("SHELL").unwrap_or_else(|_| "sh".to_string()),
+    child.exec_args_with_env(
+        &get_command_for_child(settings.command.clone(), env::var("SHELL").ok()),
         Some(child_env),
     )?;
-    child.wait()?;
+    let exit_code = get_exit_code(&child.wait_for_exit()?);
 
-    output_thread.join().expect("join thread")?;
+    // Wait for the rest of the output before recording the exit. If the
+    // handler is gone (it panicked) there is nothing more to wait for.
+    let _ = finished_rx.recv();
+    {
+        let mut session = session.lock().expect("session lock");
+        session.write_exit(exit_code)?;
+        session.end()?;
+    }
+
+    join_outputs(outputs)?;
 
     // Return where recorded asciicast can be found.
-    Ok(match settings.file.clone() {
-        Some(p) => {
-            // Written to the user-specified path.
-            RecordLocation::Local(p)
-        }
-        None => {
-            // Upload the temp file to a remote service.
-            // TODO: Prompt to upload like the python client does.
-            let uploader = builder.build().map_err(err_msg)?;
-            RecordLocation::Remote(uploader.upload_file(tmp_path)?)
+    let location = if upload && forwarded_signal().is_some() {
+        // We were asked to stop, don't start an upload now.
+        RecordLocation::Local(vec![keep_recording(tmp)?])
+    } else if upload {
+        // Upload the temp file to a remote service.
+        // TODO: Prompt to upload like the python client does.
+        let uploader = builder.build().map_err(err_msg)?;
+        match uploader.upload_file(tmp_path) {
+            Ok(url) => RecordLocation::Remote(url),
+            Err(e) => {
+                keep_recording(tmp)?;
+                return Err(e);
+            }
         }
+    } else {
+        // Written to the user-specified paths.
+        let files = match (&settings.file, segments) {
+            (Some(p), Some(count)) => (1..=count.load(Ordering::SeqCst))
+                .map(|n| segment_path(p, n))
+                .collect(),
+            (Some(p), None) => format.paths(p),
+            (None, _) => vec![],
+        };
+        RecordLocation::Local(
+            files
+                .into_iter()
+                .chain(specs.iter().flat_map(|spec| spec.format.paths(&spec.path)))
+                .collect(),
+        )
+    };
+    Ok(Recording {
+        location,
+        exit_code,
     })
 }
 
@@ -225,9 +725,21 @@ mod tests {
             append,
             overwrite,
             file,
+            outputs: vec![],
+            redact: vec![],
+            durability: None,
+            compress: None,
+            split_every: None,
+            max_segment_size: None,
+            exit_event: false,
+            command: None,
             force_yes: false,
+            prefix_key: None,
+            cols: None,
+            rows: None,
             idle_time_limit: None,
             raw: false,
+            format: None,
             title: None,
             record_stdin: false,
         }
@@ -296,6 +808,159 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_only_existing_recordings_are_continued() {
+        let empty = NamedTempFile::new().unwrap();
+        let mut recording = NamedTempFile::new().unwrap();
+        writeln!(
+            recording,
+            "{{\"version\": 2, \"width\": 80, \"height\": 24}}"
+        )
+        .unwrap();
+        let append = get_mock_settings(None, FileBehavior::Append);
+        assert!(continues_recording(recording.path(), &append));
+        // New and empty files get a header like any new recording.
+        assert!(!continues_recording(empty.path(), &append));
+        assert!(!continues_recording(
+            Path::new("/does_not_exist.cast"),
+            &append
+        ));
+        let overwrite = get_mock_settings(None, FileBehavior::Overwrite);
+        assert!(!continues_recording(recording.path(), &overwrite));
+    }
+
+    #[test]
+    fn test_scripts_cannot_be_appended_to() {
+        let typescript = NamedTempFile::new().unwrap();
+        let mut settings =
+            get_mock_settings(Some(typescript.path().to_path_buf()), FileBehavior::Append);
+        settings.format = Some("script".to_string());
+        assert!(validate_output_path(&settings).is_err());
+        settings.format = Some("script-advanced".to_string());
+        assert!(validate_output_path(&settings).is_err());
+        // A new typescript is fine.
+        settings.file = Some(PathBuf::from("/does_not_exist"));
+        assert!(validate_output_path(&settings).is_ok());
+
+        let mut settings = get_mock_settings(None, FileBehavior::Append);
+        settings.outputs = vec![format!("script:{}", typescript.path().display())];
+        assert!(validate_output_path(&settings).is_err());
+    }
+
+    #[test]
+    fn test_existent_extra_output_path() {
+        let mut settings = get_mock_settings(None, FileBehavior::NotSet);
+        settings.outputs = vec!["raw:.".to_string()];
+        assert!(validate_output_path(&settings).is_err());
+        settings.overwrite = true;
+        assert!(validate_output_path(&settings).is_ok());
+    }
+
+    #[test]
+    fn test_format_of_file() {
+        let mut settings = get_mock_settings(None, FileBehavior::NotSet);
+        assert_eq!(get_format(&settings).unwrap(), Format::Asciicast);
+        settings.format = Some("ttyrec".to_string());
+        assert_eq!(get_format(&settings).unwrap(), Format::Ttyrec);
+        settings.format = Some("gif".to_string());
+        assert!(get_format(&settings).is_err());
+    }
+
+    #[test]
+    fn test_only_asciicast_can_be_split() {
+        let mut settings = get_mock_settings(
+            Some(PathBuf::from("/does_not_exist.tty")),
+            FileBehavior::NotSet,
+        );
+        settings.format = Some("ttyrec".to_string());
+        settings.split_every = Some("10m".to_string());
+        assert_eq!(
+            validate_output_path(&settings).unwrap_err().to_string(),
+            "only asciicast recordings can be split"
+        );
+    }
+
+    #[test]
+    fn test_parse_output_spec() {
+        assert_eq!(
+            "raw:/tmp/out:1.log".parse::<OutputSpec>().unwrap(),
+            OutputSpec {
+                format: Format::Raw,
+                path: PathBuf::from("/tmp/out:1.log"),
+            }
+        );
+        assert!("asciicast".parse::<OutputSpec>().is_err());
+        assert!("asciicast:".parse::<OutputSpec>().is_err());
+        assert!("gif:out.gif".parse::<OutputSpec>().is_err());
+    }
+
+    #[test]
+    fn test_read_existing_finds_last_time_and_size() {
+        let cast = "{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+                    [1.5, \"o\", \"hello\"]\n\
+                    [2.25, \"r\", \"100x30\"]\n\
+                    [3.0, \"o\", \"world\"]\n";
+        let result = read_existing(cast.as_bytes(), Path::new("test.cast")).unwrap();
+        assert_eq!(
+            result,
+            Some(Existing {
+                width: 100,
+                height: 30,
+                last_time: 3.0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_existing_empty_file() {
+        let result = read_existing("".as_bytes(), Path::new("test.cast")).unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_read_existing_rejects_other_versions() {
+        let cast = "{\"version\": 1, \"width\": 80, \"height\": 24, \"stdout\": []}\n";
+        let result = read_existing(cast.as_bytes(), Path::new("test.cast"));
+        assert_eq!(
+            format!("{}", result.unwrap_err()),
+            "unable to append to test.cast: unsupported asciicast version 1"
+        );
+        let result = read_existing("hello\n".as_bytes(), Path::new("test.cast"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_not_headless_in_terminal() {
+        let settings = get_mock_settings(None, FileBehavior::NotSet);
+        assert_eq!(get_headless_size(&settings, true), None);
+    }
+
+    #[test]
+    fn test_headless_without_terminal() {
+        let settings = get_mock_settings(None, FileBehavior::NotSet);
+        assert_eq!(get_headless_size(&settings, false), Some((80, 24)));
+    }
+
+    #[test]
+    fn test_headless_with_explicit_size() {
+        let mut settings = get_mock_settings(None, FileBehavior::NotSet);
+        settings.cols = Some(120);
+        assert_eq!(get_headless_size(&settings, true), Some((120, 24)));
+        settings.rows = Some(40);
+        assert_eq!(get_headless_size(&settings, false), Some((120, 40)));
+    }
+
+    #[test]
+    fn test_exit_code_of_exited_command() {
+        assert_eq!(get_exit_code(&ExitStatus::from_raw(0)), 0);
+        assert_eq!(get_exit_code(&ExitStatus::from_raw(3 << 8)), 3);
+    }
+
+    #[test]
+    fn test_exit_code_of_signaled_command() {
+        assert_eq!(get_exit_code(&ExitStatus::from_raw(libc::SIGKILL)), 137);
+    }
+
     #[test]
     fn test_capturing_env_for_header() {
         env::set_var("THIS_IS_A_TEST_1", "1");
@@ -310,6 +975,26 @@ mod tests {
         assert_eq!(result.get("THIS_IS_A_TEST_3"), None);
     }
 
+    #[test]
+    fn test_command_runs_through_sh() {
+        let result = get_command_for_child(Some("echo 'hello world'".to_string()), None);
+        assert_eq!(
+            result,
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo 'hello world'".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_command_runs_shell() {
+        let result = get_command_for_child(None, Some("/bin/test-shell".to_string()));
+        assert_eq!(result, vec!["/bin/test-shell".to_string()]);
+        assert_eq!(get_command_for_child(None, None), vec!["sh".to_string()]);
+    }
+
     #[test]
     fn test_env_copies_parent() {
         let mut e = HashMap::new();
@@ -326,4 +1011,75 @@ mod tests {
         let result = get_environment_for_child(e.iter().map(|(a, b)| (a.clone(), b.clone())));
         assert_eq!(result.get("ASCIINEMA_REC"), Some(&"1".to_string()));
     }
+
+    /// Returns the master and slave ends of a new pty.
+    fn open_pty() -> (File, File) {
+        use std::os::unix::io::FromRawFd;
+        let (mut master, mut slave) = (0, 0);
+        let result = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(result, 0, "unable to open a pty");
+        unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) }
+    }
+
+    /// Waits for the program on the pty to turn echo off or back on.
+    fn wait_for_hidden_input(pty: RawFd, hidden: bool) {
+        for _ in 0..500 {
+            if is_input_hidden(pty).unwrap() == hidden {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("echo wasn't turned {}", if hidden { "off" } else { "on" });
+    }
+
+    #[test]
+    fn test_input_typed_with_echo_off_is_not_recorded() {
+        let (mut master, slave) = open_pty();
+        let pty = master.as_raw_fd();
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "stty -echo; read x; stty echo; read y"])
+            .stdin(slave)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let (tx, rx) = channel();
+        let (finished, _) = channel();
+        let mut shell = Shell {
+            session: Arc::new(Mutex::new(Session::new(vec![tx], true))),
+            finished,
+            hotkeys: None,
+            pty: Some(pty),
+            input_hidden: false,
+        };
+        let mut type_line = |line: &[u8]| {
+            let input = shell.filter_input(line);
+            master.write_all(&input).unwrap();
+            shell.input(&input);
+        };
+
+        wait_for_hidden_input(pty, true);
+        type_line(b"hunter2\n");
+        wait_for_hidden_input(pty, false);
+        type_line(b"visible\n");
+        assert!(child.wait().unwrap().success());
+        drop(shell);
+
+        let input: Vec<String> = rx
+            .iter()
+            .filter_map(|msg| match msg {
+                Msg::Input(e) => Some(e.event_data),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(input, vec!["visible\n".to_string()]);
+    }
 }
@@ -0,0 +1,278 @@
+use crate::compression::{decompress, Compression, Encoder};
+use crate::input_formats::asciicast::parse_event;
+use crate::input_formats::read_asciicast;
+use crate::settings::RepairSettings;
+use failure::Error;
+use std::fmt;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// A fix made to a recording, with the line number it was made at.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// The last line was cut short, e.g. by a crash while it was written.
+    TruncatedLine(usize),
+    /// A line that isn't a valid event was removed.
+    InvalidLine(usize),
+    /// An event was earlier than the one before it, so it was moved up to it.
+    Reordered { line: usize, from: f64, to: f64 },
+    /// The last line was complete but its newline wasn't.
+    MissingNewline(usize),
+    /// An event's text wasn't valid UTF-8, the invalid bytes were replaced with U+FFFD.
+    InvalidUtf8(usize),
+    /// The compressed stream ended early, so it was finished.
+    UnfinishedStream,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::TruncatedLine(line) => write!(f, "line {}: removed truncated event", line),
+            Change::InvalidLine(line) => write!(f, "line {}: removed invalid event", line),
+            Change::Reordered { line, from, to } => write!(
+                f,
+                "line {}: moved event at {} to {}, after the previous event",
+                line, from, to
+            ),
+            Change::MissingNewline(line) => write!(f, "line {}: added missing newline", line),
+            Change::InvalidUtf8(line) => write!(f, "line {}: replaced invalid UTF-8", line),
+            Change::UnfinishedStream => write!(f, "finished the cut short compressed stream"),
+        }
+    }
+}
+
+/// The outcome of repairing a recording.
+pub struct Repair {
+    pub file: PathBuf,
+    pub changes: Vec<Change>,
+}
+
+/// Fixes the events of an asciicast v2 recording.
+///
+/// The header has to be intact, there is nothing to rebuild it from. Events
+/// that can't be read are dropped and events that go back in time are moved
+/// up to the previous one, so they keep their order.
+fn repair_cast(data: &[u8]) -> Result<(String, Vec<Change>), Error> {
+    // Only the header is read here, the events are checked line by line.
+    let _ = read_asciicast(Cursor::new(data.to_vec()))?;
+    let ends_with_newline = data.ends_with(b"\n");
+    let mut lines: Vec<&[u8]> = data.split(|&b| b == b'\n').collect();
+    if ends_with_newline {
+        lines.pop();
+    }
+    let header_line = String::from_utf8_lossy(lines.remove(0));
+
+    let mut repaired = format!("{}\n", header_line);
+    let mut changes = vec![];
+    let mut last_time = 0.0;
+    for (i, bytes) in lines.iter().enumerate() {
+        let number = i + 2;
+        let is_last = i + 1 == lines.len();
+        // Decoded per line, so only the events that need it are reported.
+        let invalid_utf8 = std::str::from_utf8(bytes).is_err();
+        let line = String::from_utf8_lossy(bytes);
+        let line = line.as_ref();
+        // Tolerated by readers, so not worth reporting.
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Unknown event types are kept, but they still need a time and data.
+        let parsed = serde_json::from_str::<(f64, String, String)>(line)
+            .ok()
+            .filter(|_| parse_event(line).is_ok());
+        let (time, code, data) = match parsed {
+            Some(event) => event,
+            None if is_last && !ends_with_newline => {
+                changes.push(Change::TruncatedLine(number));
+                continue;
+            }
+            None => {
+                changes.push(Change::InvalidLine(number));
+                continue;
+            }
+        };
+
+        if invalid_utf8 {
+            changes.push(Change::InvalidUtf8(number));
+        }
+        if time < last_time {
+            changes.push(Change::Reordered {
+                line: number,
+                from: time,
+                to: last_time,
+            });
+            repaired.push_str(&serde_json::to_string(&(last_time, code, data))?);
+        } else {
+            last_time = time;
+            repaired.push_str(line);
+        }
+        repaired.push('\n');
+        if is_last && !ends_with_newline {
+            changes.push(Change::MissingNewline(number));
+        }
+    }
+    Ok((repaired, changes))
+}
+
+/// Replaces `path` with `contents`, so a crash can't leave it half written.
+fn replace_file(
+    path: &Path,
+    contents: &str,
+    compression: Option<Compression>,
+) -> Result<(), Error> {
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let tmp = NamedTempFile::new_in(dir)?;
+    let mut encoder = Encoder::new(tmp.reopen()?, compression)?;
+    encoder.write(contents.as_bytes())?;
+    encoder.finish()?.sync_all()?;
+    fs::set_permissions(tmp.path(), fs::metadata(path)?.permissions())?;
+    tmp.persist(path)?;
+    Ok(())
+}
+
+pub fn go(settings: &RepairSettings) -> Result<Repair, Error> {
+    let data = fs::read(&settings.file)?;
+    let compression = Compression::detect(&data);
+    // What could be read before a cut short compressed stream still counts.
+    let mut cast = vec![];
+    let unfinished = decompress(Cursor::new(data))?
+        .read_to_end(&mut cast)
+        .is_err();
+    let (repaired, mut changes) = repair_cast(&cast)?;
+    if unfinished {
+        changes.push(Change::UnfinishedStream);
+    }
+    if !changes.is_empty() {
+        replace_file(&settings.file, &repaired, compression)?;
+    }
+    Ok(Repair {
+        file: settings.file.clone(),
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "{\"version\": 2, \"width\": 80, \"height\": 24}";
+
+    fn cast(events: &[&str]) -> String {
+        let mut cast = format!("{}\n", HEADER);
+        for event in events {
+            cast.push_str(event);
+            cast.push('\n');
+        }
+        cast
+    }
+
+    #[test]
+    fn leaves_valid_recordings_alone() {
+        let valid = cast(&["[0.5, \"o\", \"a\"]", "[1.0, \"o\", \"b\"]"]);
+        let (repaired, changes) = repair_cast(valid.as_bytes()).unwrap();
+        assert_eq!(repaired, valid);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn removes_truncated_last_line() {
+        let truncated = cast(&["[0.5, \"o\", \"a\"]"]) + "[1.0, \"o\", \"b";
+        let (repaired, changes) = repair_cast(truncated.as_bytes()).unwrap();
+        assert_eq!(repaired, cast(&["[0.5, \"o\", \"a\"]"]));
+        assert_eq!(changes, vec![Change::TruncatedLine(3)]);
+    }
+
+    #[test]
+    fn adds_missing_newline() {
+        let unfinished = cast(&[]) + "[0.5, \"o\", \"a\"]";
+        let (repaired, changes) = repair_cast(unfinished.as_bytes()).unwrap();
+        assert_eq!(repaired, cast(&["[0.5, \"o\", \"a\"]"]));
+        assert_eq!(changes, vec![Change::MissingNewline(2)]);
+    }
+
+    #[test]
+    fn removes_invalid_events() {
+        let invalid = cast(&[
+            "[0.5, \"o\", \"a\"]",
+            "[0.7, \"o\"",
+            "[0.8, \"r\", \"80\"]",
+            "[1.0, \"o\", \"b\"]",
+        ]);
+        let (repaired, changes) = repair_cast(invalid.as_bytes()).unwrap();
+        assert_eq!(
+            repaired,
+            cast(&["[0.5, \"o\", \"a\"]", "[1.0, \"o\", \"b\"]"])
+        );
+        assert_eq!(
+            changes,
+            vec![Change::InvalidLine(3), Change::InvalidLine(4)]
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_events() {
+        let unknown = cast(&["[0.5, \"z\", \"?\"]"]);
+        let (repaired, changes) = repair_cast(unknown.as_bytes()).unwrap();
+        assert_eq!(repaired, unknown);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn replaces_invalid_utf8() {
+        let mut invalid = cast(&["[0.5, \"o\", \"a\"]"]).into_bytes();
+        invalid.extend_from_slice(b"[1.0, \"o\", \"caf\xE9\"]\n");
+        let (repaired, changes) = repair_cast(&invalid).unwrap();
+        assert_eq!(
+            repaired,
+            cast(&["[0.5, \"o\", \"a\"]", "[1.0, \"o\", \"caf\u{FFFD}\"]"])
+        );
+        assert_eq!(changes, vec![Change::InvalidUtf8(3)]);
+    }
+
+    #[test]
+    fn moves_events_that_go_back_in_time() {
+        let unordered = cast(&[
+            "[2.0, \"o\", \"a\"]",
+            "[1.5, \"o\", \"b\"]",
+            "[3.0, \"o\", \"c\"]",
+        ]);
+        let (repaired, changes) = repair_cast(unordered.as_bytes()).unwrap();
+        assert_eq!(
+            repaired,
+            cast(&[
+                "[2.0, \"o\", \"a\"]",
+                "[2.0,\"o\",\"b\"]",
+                "[3.0, \"o\", \"c\"]",
+            ])
+        );
+        assert_eq!(
+            changes,
+            vec![Change::Reordered {
+                line: 3,
+                from: 1.5,
+                to: 2.0
+            }]
+        );
+    }
+
+    #[test]
+    fn requires_a_header() {
+        assert!(repair_cast(b"[0.5, \"o\", \"a\"]\n").is_err());
+        assert!(repair_cast(b"").is_err());
+        assert!(repair_cast(b"{\"version\": 2, \"wid").is_err());
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        let v1 = b"{\"version\": 1, \"width\": 80, \"height\": 24, \"stdout\": []}\n";
+        assert_eq!(
+            repair_cast(v1).unwrap_err().to_string(),
+            "unsupported asciicast version 1"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use crate::commands::concatenate::get_file;
+use crate::input_formats::{headerless_size, read_recording, Event, EventStream};
+use crate::settings::SnapshotSettings;
+use crate::terminal::{Height, Width};
+use crate::vt::cell::{Line, Pen};
+use crate::vt::Vt;
+use asciicast::Header;
+use failure::{Error, Fail};
+use std::io::{self, BufReader, Write};
+use std::str::FromStr;
+use tempfile::NamedTempFile;
+
+#[derive(Debug, Fail)]
+enum SnapshotFailure {
+    #[fail(display = "invalid time: {} (expected seconds, end or marker:N)", at)]
+    InvalidTime { at: String },
+    #[fail(display = "no marker {} (the recording has {})", number, count)]
+    NoMarker { number: usize, count: usize },
+}
+
+/// The moment of a recording to show the screen at.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum At {
+    /// Seconds from the start of the recording.
+    Time(f64),
+    End,
+    /// The nth marker, counting from 1 as `cat --markers` does.
+    Marker(usize),
+}
+
+impl FromStr for At {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "end" {
+            return Ok(At::End);
+        }
+        if let Some(number) = s.strip_prefix("marker:") {
+            return match number.parse::<usize>() {
+                Ok(number) if number > 0 => Ok(At::Marker(number)),
+                _ => Err(SnapshotFailure::InvalidTime { at: s.to_string() }.into()),
+            };
+        }
+        match s.parse::<f64>() {
+            Ok(seconds) if seconds >= 0.0 => Ok(At::Time(seconds)),
+            _ => Err(SnapshotFailure::InvalidTime { at: s.to_string() }.into()),
+        }
+    }
+}
+
+/// Plays the events of a recording up to `at` and returns the screen then.
+pub fn screen_at(header: &Header, events: EventStream, at: At) -> Result<Vt, Error> {
+    let mut vt = Vt::new(Width(header.width), Height(header.height));
+    let mut markers = 0;
+    for event in events {
+        let event = event?;
+        match (at, &event) {
+            (At::Time(seconds), _) if event.time() > seconds => return Ok(vt),
+            (At::Marker(number), Event::Marker(..)) => {
+                markers += 1;
+                if markers == number {
+                    return Ok(vt);
+                }
+            }
+            _ => (),
+        }
+        vt.feed(&event);
+    }
+    match at {
+        At::Marker(number) => Err(SnapshotFailure::NoMarker {
+            number,
+            count: markers,
+        }
+        .into()),
+        _ => Ok(vt),
+    }
+}
+
+/// Writes the text on screen, without the blank lines at the bottom.
+fn write_plain<W: Write>(out: &mut W, vt: &Vt) -> Result<(), Error> {
+    let mut text = vt.text();
+    while text.last().is_some_and(String::is_empty) {
+        text.pop();
+    }
+    for line in text {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// Returns a line with the SGR sequences to draw it as it is on screen.
+fn colored_line(line: &Line) -> String {
+    // Blanks at the end only matter if they have a background.
+    let end = line
+        .cells
+        .iter()
+        .rposition(|cell| cell.ch != ' ' || cell.pen != Pen::default())
+        .map_or(0, |i| i + 1);
+    let mut text = String::new();
+    let mut pen = Pen::default();
+    for cell in line.cells[..end].iter().filter(|cell| cell.width > 0) {
+        if cell.pen != pen {
+            pen = cell.pen;
+            text.push_str(&pen.sgr());
+        }
+        text.push(cell.ch);
+    }
+    if pen != Pen::default() {
+        text.push_str("\x1b[0m");
+    }
+    text
+}
+
+/// Writes the screen with its colors and attributes as SGR sequences.
+fn write_colored<W: Write>(out: &mut W, vt: &Vt) -> Result<(), Error> {
+    let mut lines: Vec<String> = vt.lines().iter().map(colored_line).collect();
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+pub fn go(settings: &SnapshotSettings) -> Result<(), Error> {
+    let at: At = settings.at.parse()?;
+    let mut temp: NamedTempFile = NamedTempFile::new()?;
+    let file = get_file(settings.location.clone(), &mut temp)?;
+    let (header, events) = read_recording(
+        BufReader::new(file),
+        headerless_size(settings.cols, settings.rows),
+    )?;
+    let vt = screen_at(&header, events, at)?;
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    if settings.color {
+        write_colored(&mut handle, &vt)
+    } else {
+        write_plain(&mut handle, &vt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(events: Vec<Event>) -> EventStream {
+        Box::new(events.into_iter().map(Ok))
+    }
+
+    fn header() -> Header {
+        Header {
+            version: 2,
+            width: 20,
+            height: 3,
+            timestamp: None,
+            duration: None,
+            idle_time_limit: None,
+            command: None,
+            title: None,
+            env: None,
+        }
+    }
+
+    fn snapshot(at: At, color: bool) -> String {
+        let recording = vec![
+            Event::Output(1.0, "$ ls\r\n".to_string()),
+            Event::Output(2.0, "\x1b[1;34mdocs\x1b[0m  README\r\n".to_string()),
+            Event::Marker(2.5, "listed".to_string()),
+            Event::Output(3.0, "\x1b[2J\x1b[Hcleared".to_string()),
+        ];
+        let vt = screen_at(&header(), events(recording), at).unwrap();
+        let mut out = vec![];
+        if color {
+            write_colored(&mut out, &vt).unwrap();
+        } else {
+            write_plain(&mut out, &vt).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn parses_times() {
+        assert_eq!("12.5".parse::<At>().unwrap(), At::Time(12.5));
+        assert_eq!("end".parse::<At>().unwrap(), At::End);
+        assert!("-1".parse::<At>().is_err());
+        assert_eq!("marker:2".parse::<At>().unwrap(), At::Marker(2));
+        assert!("start".parse::<At>().is_err());
+        assert!("marker:0".parse::<At>().is_err());
+        assert!("marker:".parse::<At>().is_err());
+    }
+
+    #[test]
+    fn shows_the_screen_at_a_time() {
+        assert_eq!(snapshot(At::Time(2.5), false), "$ ls\ndocs  README\n");
+        assert_eq!(snapshot(At::Time(2.0), false), "$ ls\ndocs  README\n");
+        assert_eq!(snapshot(At::Time(0.5), false), "");
+        assert_eq!(snapshot(At::End, false), "cleared\n");
+    }
+
+    #[test]
+    fn shows_the_screen_at_a_marker() {
+        assert_eq!(snapshot(At::Marker(1), false), "$ ls\ndocs  README\n");
+        let recording = events(vec![Event::Marker(1.0, "only".to_string())]);
+        assert!(screen_at(&header(), recording, At::Marker(2)).is_err());
+    }
+
+    #[test]
+    fn shows_colors() {
+        assert_eq!(
+            snapshot(At::Time(2.5), true),
+            "$ ls\n\x1b[0;1;34mdocs\x1b[0m  README\n"
+        );
+    }
+}
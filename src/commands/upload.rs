@@ -1,9 +1,20 @@
+use crate::commands::concatenate::get_file;
 use crate::settings::UploadSettings;
 use crate::uploader::UploadBuilder;
 use failure::{err_msg, Error};
+use std::io;
+use tempfile::NamedTempFile;
 use url::Url;
 
 pub fn go(settings: &UploadSettings, builder: &mut UploadBuilder) -> Result<Url, Error> {
     let uploader = builder.build().map_err(err_msg)?;
-    uploader.upload_file(settings.file.clone())
+    // The server only takes plain recordings, so upload a decompressed copy.
+    let mut temp = NamedTempFile::new()?;
+    let mut reader = get_file(settings.file.clone(), &mut temp)?;
+    let mut cast = tempfile::Builder::new()
+        .prefix("asciinema-")
+        .suffix(".cast")
+        .tempfile()?;
+    io::copy(&mut reader, &mut cast)?;
+    uploader.upload_file(cast.path().to_path_buf())
 }
@@ -0,0 +1,215 @@
+use failure::{Error, Fail};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Fail)]
+enum CompressionFailure {
+    #[fail(display = "unknown compression: {} (expected gzip or zstd)", name)]
+    Unknown { name: String },
+}
+
+/// The compressions recordings can be written and read in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err(CompressionFailure::Unknown {
+                name: s.to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+impl Compression {
+    /// Returns the compression the extension of `path` asks for, e.g. `demo.cast.gz`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Some(Compression::Gzip),
+            Some("zst") => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Returns the compression `data` is in, going by how it starts.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if data.starts_with(ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns `reader` decompressed on the fly if it is compressed, as is otherwise.
+pub fn decompress<R: Read + 'static>(mut reader: R) -> io::Result<Box<dyn Read>> {
+    // Read the magic bytes up front, a pipe may hand them over in pieces.
+    let mut magic = vec![];
+    reader
+        .by_ref()
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    let compression = Compression::detect(&magic);
+    let reader = Cursor::new(magic).chain(reader);
+    Ok(match compression {
+        // Appending to a recording adds another member or frame to it.
+        Some(Compression::Gzip) => Box::new(MultiGzDecoder::new(reader)),
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::new(reader)?),
+        None => Box::new(reader),
+    })
+}
+
+/// Writes to a file, compressed if asked to.
+pub enum Encoder {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+}
+
+impl Encoder {
+    pub fn new(file: File, compression: Option<Compression>) -> io::Result<Self> {
+        Ok(match compression {
+            None => Encoder::Plain(file),
+            Some(Compression::Gzip) => {
+                Encoder::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Some(Compression::Zstd) => Encoder::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    /// Writes `data` to the file.
+    ///
+    /// Plain data goes straight to the file, so a reader following it or a
+    /// crash right after sees all of it. Compressed data is held back until
+    /// `flush` or `finish`: every flush ends a compressed block, and flushing
+    /// after each event would make the file several times bigger.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        match *self {
+            Encoder::Plain(ref mut f) => f.write_all(data),
+            Encoder::Gzip(ref mut e) => e.write_all(data),
+            Encoder::Zstd(ref mut e) => e.write_all(data),
+        }
+    }
+
+    /// Writes what compression held back to the file.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Encoder::Plain(_) => Ok(()),
+            Encoder::Gzip(ref mut e) => e.flush(),
+            Encoder::Zstd(ref mut e) => e.flush(),
+        }
+    }
+
+    pub fn file(&self) -> &File {
+        match *self {
+            Encoder::Plain(ref f) => f,
+            Encoder::Gzip(ref e) => e.get_ref(),
+            Encoder::Zstd(ref e) => e.get_ref(),
+        }
+    }
+
+    /// Ends the compressed stream and returns the file.
+    pub fn finish(self) -> io::Result<File> {
+        match self {
+            Encoder::Plain(f) => Ok(f),
+            Encoder::Gzip(e) => e.finish(),
+            Encoder::Zstd(e) => e.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Seek;
+    use std::path::PathBuf;
+
+    fn round_trip(compression: Option<Compression>, chunks: &[&str]) -> String {
+        let tmp = tempfile::tempfile().unwrap();
+        let mut encoder = Encoder::new(tmp.try_clone().unwrap(), compression).unwrap();
+        for chunk in chunks {
+            encoder.write(chunk.as_bytes()).unwrap();
+        }
+        let mut file = encoder.finish().unwrap();
+        file.rewind().unwrap();
+        let mut text = String::new();
+        decompress(file).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn parses_compressions() {
+        assert_eq!("gzip".parse::<Compression>().unwrap(), Compression::Gzip);
+        assert_eq!("zst".parse::<Compression>().unwrap(), Compression::Zstd);
+        assert!("bzip2".parse::<Compression>().is_err());
+    }
+
+    #[test]
+    fn gets_compression_from_path() {
+        let path = |p: &str| PathBuf::from(p);
+        assert_eq!(
+            Compression::from_path(&path("demo.cast.gz")),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_path(&path("demo.cast.zst")),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_path(&path("demo.cast")), None);
+    }
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let chunks = ["{\"version\":2}\n", "[0.5,\"o\",\"a\"]\n"];
+        let expected = chunks.concat();
+        assert_eq!(round_trip(None, &chunks), expected);
+        assert_eq!(round_trip(Some(Compression::Gzip), &chunks), expected);
+        assert_eq!(round_trip(Some(Compression::Zstd), &chunks), expected);
+    }
+
+    #[test]
+    fn reads_appended_streams() {
+        for &compression in &[Compression::Gzip, Compression::Zstd] {
+            let tmp = tempfile::tempfile().unwrap();
+            for chunk in &["a\n", "b\n"] {
+                let mut encoder =
+                    Encoder::new(tmp.try_clone().unwrap(), Some(compression)).unwrap();
+                encoder.write(chunk.as_bytes()).unwrap();
+                encoder.finish().unwrap();
+            }
+            let mut file = tmp;
+            file.rewind().unwrap();
+            let mut text = String::new();
+            decompress(file).unwrap().read_to_string(&mut text).unwrap();
+            assert_eq!(text, "a\nb\n");
+        }
+    }
+
+    #[test]
+    fn passes_short_input_through() {
+        let mut text = String::new();
+        decompress(Cursor::new("{}"))
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "{}");
+    }
+}
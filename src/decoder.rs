@@ -0,0 +1,103 @@
+use std::str;
+
+/// Incrementally decodes a stream of UTF-8 bytes read in arbitrary chunks.
+///
+/// Multibyte characters split across reads are carried over to the next read
+/// and invalid bytes are replaced with U+FFFD, so decoding never fails.
+#[derive(Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn decode(&mut self, data: &[u8]) -> String {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(data);
+
+        let mut decoded = String::with_capacity(bytes.len());
+        let mut rest = &bytes[..];
+        loop {
+            match str::from_utf8(rest) {
+                Ok(s) => {
+                    decoded.push_str(s);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after_valid) = rest.split_at(e.valid_up_to());
+                    // Safe to unwrap, `valid_up_to` guarantees this is valid.
+                    decoded.push_str(str::from_utf8(valid).unwrap());
+                    match e.error_len() {
+                        Some(len) => {
+                            decoded.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after_valid[len..];
+                        }
+                        None => {
+                            // An incomplete sequence at the end, wait for more data.
+                            self.pending = after_valid.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        decoded
+    }
+
+    /// Flushes a trailing incomplete sequence as U+FFFD, if any.
+    pub fn finish(&mut self) -> String {
+        if self.pending.is_empty() {
+            String::new()
+        } else {
+            self.pending.clear();
+            char::REPLACEMENT_CHARACTER.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_complete_input() {
+        let mut decoder = Utf8Decoder::new();
+        assert_eq!(decoder.decode("héllo 🦀".as_bytes()), "héllo 🦀");
+        assert_eq!(decoder.finish(), "");
+    }
+
+    #[test]
+    fn carries_split_characters_over() {
+        let crab = "🦀".as_bytes();
+        let mut decoder = Utf8Decoder::new();
+        assert_eq!(decoder.decode(&[b'a', crab[0]]), "a");
+        assert_eq!(decoder.decode(&crab[1..3]), "");
+        assert_eq!(decoder.decode(&[crab[3], b'b']), "🦀b");
+        assert_eq!(decoder.finish(), "");
+    }
+
+    #[test]
+    fn replaces_invalid_bytes() {
+        let mut decoder = Utf8Decoder::new();
+        // Latin-1 "café" and a lone continuation byte.
+        assert_eq!(decoder.decode(b"caf\xE9 \x80!"), "caf\u{FFFD} \u{FFFD}!");
+    }
+
+    #[test]
+    fn replaces_truncated_sequence_followed_by_ascii() {
+        let mut decoder = Utf8Decoder::new();
+        assert_eq!(decoder.decode(b"\xF0\x9F"), "");
+        assert_eq!(decoder.decode(b"x"), "\u{FFFD}x");
+    }
+
+    #[test]
+    fn finish_flushes_incomplete_sequence() {
+        let mut decoder = Utf8Decoder::new();
+        assert_eq!(decoder.decode(b"ok\xE2\x82"), "ok");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+        assert_eq!(decoder.finish(), "");
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// The size of a glyph, in pixels.
+pub const WIDTH: usize = 9;
+pub const HEIGHT: usize = 18;
+/// How many rows of a glyph are above the baseline.
+pub const BASELINE: usize = 14;
+
+/// The glyphs of the built-in font, see the file for where they come from.
+const GLYPHS: &str = include_str!("fixed-9x18.hex");
+
+/// The rows of a glyph from the top, the highest bit being the leftmost
+/// pixel.
+pub type Glyph = [u16; HEIGHT];
+
+/// A monospace bitmap font, to draw terminals without any font installed.
+pub struct Font {
+    glyphs: HashMap<char, Glyph>,
+}
+
+impl Font {
+    /// Returns the built-in font, read on first use.
+    pub fn builtin() -> &'static Font {
+        static FONT: OnceLock<Font> = OnceLock::new();
+        FONT.get_or_init(|| Font::parse(GLYPHS))
+    }
+
+    /// Reads glyphs given as `CODEPOINT:ROWS` lines, all in hex.
+    fn parse(text: &str) -> Font {
+        let glyphs = text
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (code, rows) = line.split_at(line.find(':').expect("glyph code point"));
+                let code = u32::from_str_radix(code, 16).expect("hex code point");
+                let mut glyph = [0; HEIGHT];
+                for (i, row) in glyph.iter_mut().enumerate() {
+                    let digits = &rows[1 + i * 4..1 + (i + 1) * 4];
+                    *row = u16::from_str_radix(digits, 16).expect("hex glyph row");
+                }
+                (std::char::from_u32(code).expect("valid code point"), glyph)
+            })
+            .collect();
+        Font { glyphs }
+    }
+
+    /// Returns the glyph of `ch`, or the replacement character's if the
+    /// font doesn't have it.
+    pub fn glyph(&self, ch: char) -> &Glyph {
+        self.glyphs
+            .get(&ch)
+            .unwrap_or_else(|| &self.glyphs[&std::char::REPLACEMENT_CHARACTER])
+    }
+}
+
+/// Whether the pixel at `x` of a glyph row is drawn.
+pub fn is_set(row: u16, x: usize) -> bool {
+    x < 16 && row & (0x8000 >> x) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(ch: char) -> Vec<String> {
+        Font::builtin()
+            .glyph(ch)
+            .iter()
+            .map(|&row| {
+                (0..WIDTH)
+                    .map(|x| if is_set(row, x) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_the_builtin_font() {
+        let font = Font::builtin();
+        assert!(font.glyphs.len() > 1000);
+        assert_eq!(
+            draw('│'),
+            vec!["....#...."; HEIGHT],
+            "box drawing lines join up"
+        );
+        let l = draw('L');
+        assert_eq!(&l[3..5], [".........", ".#......."]);
+        assert_eq!(l[BASELINE - 1], ".#######.");
+        assert!(l[BASELINE..].iter().all(|row| row == "........."));
+    }
+
+    #[test]
+    fn replaces_missing_glyphs() {
+        let font = Font::builtin();
+        assert_eq!(font.glyph('世'), font.glyph('\u{fffd}'));
+        assert_ne!(font.glyph('a'), font.glyph('\u{fffd}'));
+    }
+}
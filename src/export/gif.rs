@@ -0,0 +1,264 @@
+use super::raster::{Canvas, Renderer};
+use super::theme::{Rgb, Theme};
+use super::{for_each_frame, largest_size, Playback, END_PAUSE};
+use crate::input_formats::{Event, EventStream};
+use crate::vt::cell::{Line, Pen};
+use ::gif::{DisposalMethod, Encoder, Frame, Repeat};
+use asciicast::Header;
+use failure::{Error, Fail};
+use std::collections::HashMap;
+use std::io::Write;
+
+#[derive(Debug, Fail)]
+enum GifFailure {
+    #[fail(
+        display = "the recording is too large for a GIF: {}x{} pixels (at most 65535 each way)",
+        width, height
+    )]
+    TooLarge { width: usize, height: usize },
+}
+
+/// Frames closer than this are merged, in seconds: GIF delays are in
+/// hundredths of a second, and most viewers slow down shorter ones.
+const MIN_DELAY: f64 = 0.02;
+
+/// Returns a time in hundredths of a second, as GIF delays are.
+fn centiseconds(time: f64) -> u64 {
+    (time * 100.0).round() as u64
+}
+
+/// Part of an image, as its left, top, width and height in pixels.
+type Area = (usize, usize, usize, usize);
+
+/// Returns the smallest area of `canvas` that differs from `previous`.
+fn changed_area(previous: &Canvas, canvas: &Canvas) -> Option<Area> {
+    // Left, top, right and bottom.
+    let mut bounds: Option<Area> = None;
+    for y in 0..canvas.height {
+        for x in 0..canvas.width {
+            if previous.pixel(x, y) == canvas.pixel(x, y) {
+                continue;
+            }
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+    }
+    bounds.map(|(left, top, right, bottom)| (left, top, right - left + 1, bottom - top + 1))
+}
+
+/// Returns a frame of part of `canvas`, with a palette of its own when it
+/// has few enough colors, which terminals nearly always do.
+fn frame(canvas: &Canvas, (left, top, width, height): Area) -> Frame<'static> {
+    let mut colors: HashMap<Rgb, u8> = HashMap::new();
+    let mut palette: Vec<u8> = vec![];
+    let mut indexes: Option<Vec<u8>> = Some(Vec::with_capacity(width * height));
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in top..top + height {
+        for x in left..left + width {
+            let color = canvas.pixel(x, y);
+            rgb.extend_from_slice(&[color.0, color.1, color.2]);
+            if let Some(ref mut pixels) = indexes {
+                let next = colors.len();
+                if next == 256 && !colors.contains_key(&color) {
+                    indexes = None;
+                    continue;
+                }
+                let index = *colors.entry(color).or_insert_with(|| {
+                    palette.extend_from_slice(&[color.0, color.1, color.2]);
+                    next as u8
+                });
+                pixels.push(index);
+            }
+        }
+    }
+    let mut frame = match indexes {
+        Some(pixels) => {
+            Frame::from_palette_pixels(width as u16, height as u16, pixels, palette, None)
+        }
+        None => Frame::from_rgb_speed(width as u16, height as u16, &rgb, 10),
+    };
+    frame.left = left as u16;
+    frame.top = top as u16;
+    frame.dispose = DisposalMethod::Keep;
+    frame
+}
+
+/// Writes GIF frames, each one only holding what changed since the last.
+struct Frames<W: Write> {
+    encoder: Encoder<W>,
+    /// What the image looks like after the frames written so far.
+    shown: Option<Canvas>,
+}
+
+impl<W: Write> Frames<W> {
+    fn write(&mut self, canvas: Canvas, delay: u64) -> Result<(), Error> {
+        let area = match self.shown {
+            Some(ref shown) => changed_area(shown, &canvas).unwrap_or((0, 0, 1, 1)),
+            None => (0, 0, canvas.width, canvas.height),
+        };
+        let mut frame = frame(&canvas, area);
+        frame.delay = delay.min(u64::from(u16::MAX)) as u16;
+        self.encoder.write_frame(&frame)?;
+        self.shown = Some(canvas);
+        Ok(())
+    }
+}
+
+/// Writes an animated GIF of the recording, drawn with the built-in font.
+///
+/// Frames last until the next one and the last one for a little longer,
+/// then the animation starts over. A GIF can't change size, so it fits the
+/// largest screen of the recording.
+pub fn write<W: Write>(
+    out: W,
+    header: &Header,
+    events: EventStream,
+    playback: Playback,
+    theme: &Theme,
+) -> Result<(), Error> {
+    let events = events.collect::<Result<Vec<Event>, _>>()?;
+    let (cols, rows) = largest_size(header, &events);
+    let renderer = Renderer::new(theme, cols, rows, 1);
+    let (width, height) = renderer.size();
+    if width > usize::from(u16::MAX) || height > usize::from(u16::MAX) {
+        return Err(GifFailure::TooLarge { width, height }.into());
+    }
+    let mut encoder = Encoder::new(out, width as u16, height as u16, &[])?;
+    encoder.set_repeat(Repeat::Infinite)?;
+    let mut frames = Frames {
+        encoder,
+        shown: None,
+    };
+
+    let blank = vec![Line::blank(header.width as usize, Pen::default()); header.height as usize];
+    // The frame waiting for the next one to know how long it lasts.
+    let mut pending = (0.0, renderer.draw(&blank, None));
+    let events: EventStream = Box::new(events.into_iter().map(Ok));
+    let duration = for_each_frame(header, events, playback, |time, vt| {
+        let canvas = renderer.draw(vt.lines(), Some(vt.cursor()));
+        if time - pending.0 < MIN_DELAY {
+            pending.1 = canvas;
+            return Ok(());
+        }
+        let (start, shown) = std::mem::replace(&mut pending, (time, canvas));
+        frames.write(shown, centiseconds(time) - centiseconds(start))
+    })?;
+    let (start, last) = pending;
+    frames.write(
+        last,
+        centiseconds(duration + END_PAUSE) - centiseconds(start),
+    )?;
+
+    let mut out = frames.encoder.into_inner()?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::{Height, Width};
+    use ::gif::DecodeOptions;
+
+    /// Returns the delay, position and size of each frame of a GIF, and its
+    /// size.
+    fn decode(gif: &[u8]) -> (Vec<[u16; 5]>, (u16, u16)) {
+        let mut decoder = DecodeOptions::new().read_info(gif).unwrap();
+        let size = (decoder.width(), decoder.height());
+        let mut frames = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push([
+                frame.delay,
+                frame.left,
+                frame.top,
+                frame.width,
+                frame.height,
+            ]);
+        }
+        (frames, size)
+    }
+
+    fn export(events: Vec<Event>, playback: Playback) -> Vec<u8> {
+        let mut gif = vec![];
+        write(
+            &mut gif,
+            &super::super::tests::header(10, 2),
+            Box::new(events.into_iter().map(Ok)),
+            playback,
+            &Theme::default(),
+        )
+        .unwrap();
+        gif
+    }
+
+    #[test]
+    fn animates_frames() {
+        let gif = export(
+            vec![
+                Event::Output(1.0, "a".to_string()),
+                Event::Output(2.5, "b".to_string()),
+            ],
+            Playback::default(),
+        );
+        let (frames, size) = decode(&gif);
+        assert_eq!(size, (10 * 9 + 18, 2 * 18 + 18));
+        assert_eq!(frames.len(), 3);
+        // The blank screen has its cursor drawn by the first output.
+        assert_eq!(frames[0], [100, 0, 0, 108, 54]);
+        // Then only what changed: "a" and the cursor after it, which "b"
+        // takes the place of.
+        assert_eq!(frames[1], [150, 10, 9, 17, 18]);
+        assert_eq!(frames[2], [200, 18, 9, 18, 18]);
+    }
+
+    #[test]
+    fn merges_close_frames() {
+        let gif = export(
+            vec![
+                Event::Output(1.0, "a".to_string()),
+                Event::Output(1.01, "b".to_string()),
+                Event::Output(1.5, "\x1b[?25l".to_string()),
+            ],
+            Playback::default(),
+        );
+        let (frames, _) = decode(&gif);
+        // "a" shows for too short a time to have a frame of its own.
+        assert_eq!(
+            frames.iter().map(|f| f[0]).collect::<Vec<_>>(),
+            vec![100, 50, 200]
+        );
+    }
+
+    #[test]
+    fn fits_the_largest_screen() {
+        let gif = export(
+            vec![
+                Event::Output(1.0, "a\x1b[?25l".to_string()),
+                Event::Resize(2.0, Width(20), Height(3)),
+                Event::Output(3.0, "\x1b[3;16Hb".to_string()),
+            ],
+            Playback::default(),
+        );
+        let (frames, size) = decode(&gif);
+        assert_eq!(size, (20 * 9 + 18, 3 * 18 + 18));
+        // "b" is drawn past the size of the header, not left out.
+        let b = frames.last().unwrap();
+        assert!(b[1] >= 9 + 15 * 9 && b[2] >= 9 + 2 * 18);
+    }
+
+    #[test]
+    fn rejects_huge_screens() {
+        let result = write(
+            vec![],
+            &super::super::tests::header(10000, 2),
+            Box::new(vec![].into_iter()),
+            Playback::default(),
+            &Theme::default(),
+        );
+        assert!(result.is_err());
+    }
+}
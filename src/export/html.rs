@@ -0,0 +1,228 @@
+use super::theme::{hex, Theme};
+use super::{escape, for_each_frame, Playback};
+use crate::input_formats::EventStream;
+use crate::vt::cell::{Line, Pen};
+use crate::vt::Vt;
+use asciicast::Header;
+use failure::Error;
+use regex::{Captures, Regex};
+use std::io::Write;
+
+/// The page, with `{{name}}` where the recording goes.
+const TEMPLATE: &str = include_str!("player.html");
+
+/// Returns the CSS drawing text with `pen`, empty for the default pen.
+fn style(pen: &Pen, theme: &Theme) -> String {
+    let (fg, bg) = theme.colors(pen);
+    let mut style = vec![];
+    if fg != theme.fg {
+        style.push(format!("color:{}", hex(fg)));
+    }
+    if bg != theme.bg {
+        style.push(format!("background:{}", hex(bg)));
+    }
+    if pen.bold {
+        style.push("font-weight:bold".to_string());
+    }
+    if pen.faint {
+        style.push("opacity:0.5".to_string());
+    }
+    if pen.italic {
+        style.push("font-style:italic".to_string());
+    }
+    match (pen.underline, pen.strikethrough) {
+        (true, true) => style.push("text-decoration:underline line-through".to_string()),
+        (true, false) => style.push("text-decoration:underline".to_string()),
+        (false, true) => style.push("text-decoration:line-through".to_string()),
+        (false, false) => (),
+    }
+    style.join(";")
+}
+
+/// Returns the HTML of a line, `cursor` being the column of the cursor if
+/// it is shown on it.
+fn line_html(line: &Line, cursor: Option<usize>, theme: &Theme) -> String {
+    let cells = &line.cells;
+    // Blanks at the end only matter if they have a background, or the
+    // cursor is on one of them.
+    let end = cells
+        .iter()
+        .rposition(|cell| cell.ch != ' ' || cell.pen != Pen::default())
+        .map_or(0, |i| i + 1)
+        .max(cursor.map_or(0, |col| (col + 1).min(cells.len())));
+
+    let mut html = String::new();
+    let mut run = String::new();
+    let mut run_style = String::new();
+    for (col, cell) in cells[..end].iter().enumerate() {
+        if cell.width == 0 {
+            continue;
+        }
+        let pen = if cursor == Some(col) {
+            Pen {
+                inverse: !cell.pen.inverse,
+                ..cell.pen
+            }
+        } else {
+            cell.pen
+        };
+        let cell_style = style(&pen, theme);
+        if cell_style != run_style {
+            push_run(&mut html, &run, &run_style);
+            run.clear();
+            run_style = cell_style;
+        }
+        run.push(cell.ch);
+    }
+    push_run(&mut html, &run, &run_style);
+    html
+}
+
+fn push_run(html: &mut String, text: &str, style: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if style.is_empty() {
+        html.push_str(&escape(text));
+    } else {
+        html.push_str(&format!(
+            "<span style=\"{}\">{}</span>",
+            style,
+            escape(text)
+        ));
+    }
+}
+
+/// Returns the HTML of every line on screen.
+fn screen_html(vt: &Vt, theme: &Theme) -> Vec<String> {
+    let cursor = vt.cursor();
+    vt.lines()
+        .iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let col = Some(cursor.col).filter(|_| cursor.visible && cursor.row == row);
+            line_html(line, col, theme)
+        })
+        .collect()
+}
+
+/// Writes a web page playing the recording, with everything it needs in it.
+///
+/// The screen is rendered here rather than by a script in the page, so the
+/// page holds, for each frame, its time, its height and the lines that
+/// changed since the frame before.
+pub fn write<W: Write>(
+    mut out: W,
+    header: &Header,
+    events: EventStream,
+    playback: Playback,
+    theme: &Theme,
+) -> Result<(), Error> {
+    type Frame = (f64, usize, Vec<(usize, String)>);
+    let mut frames: Vec<Frame> = vec![];
+    let mut shown: Vec<String> = vec![String::new(); header.height as usize];
+    let duration = for_each_frame(header, events, playback, |time, vt| {
+        let lines = screen_html(vt, theme);
+        let changes = lines
+            .iter()
+            .enumerate()
+            .filter(|&(row, line)| shown.get(row) != Some(line))
+            .map(|(row, line)| (row, line.clone()))
+            .collect();
+        frames.push(((time * 1000.0).round() / 1000.0, lines.len(), changes));
+        shown = lines;
+        Ok(())
+    })?;
+
+    // The frames go in a script element, which must not see a closing tag.
+    let frames = serde_json::to_string(&frames)?.replace('<', "\\u003c");
+    let title = header.title.as_deref().unwrap_or("asciinema");
+    let page =
+        Regex::new(r"\{\{(\w+)\}\}")?.replace_all(TEMPLATE, |caps: &Captures| match &caps[1] {
+            "title" => escape(title),
+            "fg" => hex(theme.fg),
+            "bg" => hex(theme.bg),
+            "cols" => header.width.to_string(),
+            "rows" => header.height.to_string(),
+            "duration" => format!("{:.3}", duration),
+            "frames" => frames.clone(),
+            _ => caps[0].to_string(),
+        });
+    out.write_all(page.as_bytes())?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_formats::Event;
+    use crate::terminal::{Height, Width};
+    use crate::vt::cell::Color;
+
+    fn render(data: &str) -> Vec<String> {
+        let mut vt = Vt::new(Width(12), Height(2));
+        vt.feed_str(data);
+        screen_html(&vt, &Theme::default())
+    }
+
+    #[test]
+    fn renders_lines() {
+        assert_eq!(
+            render("a<b> \x1b[1;31mred\x1b[0m\r\n\x1b[44m \x1b[0m\x1b[?25l"),
+            vec![
+                "a&lt;b&gt; <span style=\"color:#dd3c69;font-weight:bold\">red</span>",
+                "<span style=\"background:#26b0d7\"> </span>",
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_the_cursor() {
+        assert_eq!(
+            render("$ ls\r\n$ "),
+            vec![
+                "$ ls",
+                "$ <span style=\"color:#121314;background:#cccccc\"> </span>"
+            ]
+        );
+    }
+
+    #[test]
+    fn styles_attributes() {
+        let pen = Pen {
+            fg: Color::Rgb(1, 2, 3),
+            italic: true,
+            underline: true,
+            strikethrough: true,
+            ..Pen::default()
+        };
+        assert_eq!(
+            style(&pen, &Theme::default()),
+            "color:#010203;font-style:italic;text-decoration:underline line-through"
+        );
+    }
+
+    #[test]
+    fn writes_a_page_with_the_changes() {
+        let mut header = super::super::tests::header(10, 2);
+        header.title = Some("<demo>".to_string());
+        let events = vec![
+            Event::Output(1.0, "\x1b[?25la".to_string()),
+            Event::Output(2.0, "\r\nb</script>".to_string()),
+        ];
+        let mut page = vec![];
+        write(
+            &mut page,
+            &header,
+            Box::new(events.into_iter().map(Ok)),
+            Playback::default(),
+            &Theme::default(),
+        )
+        .unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.contains("<title>&lt;demo&gt;</title>"));
+        assert!(page.contains(r#"[[1.0,2,[[0,"a"]]],[2.0,2,[[1,"b&lt;/script&gt;"]]]]"#));
+        assert!(!page.contains("{{"));
+    }
+}
@@ -0,0 +1,157 @@
+//! Turns recordings into files to show them outside of a terminal.
+//!
+//! Exporters don't parse output themselves: recordings are played through
+//! the virtual terminal and they get the screen every time it changes.
+
+use crate::input_formats::{Event, EventStream};
+use crate::terminal::{Height, Width};
+use crate::vt::cell::Line;
+use crate::vt::{Cursor, Vt};
+use asciicast::Header;
+use failure::Error;
+
+pub mod font;
+pub mod gif;
+pub mod html;
+pub mod png;
+pub mod raster;
+pub mod svg;
+pub mod theme;
+
+/// How long animations stay on their last frame before starting over, in
+/// seconds.
+pub const END_PAUSE: f64 = 2.0;
+
+/// How the time of a recording is changed when exporting it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Playback {
+    /// Pauses are cut down to this many seconds.
+    pub idle_time_limit: Option<f64>,
+    pub speed: f64,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            idle_time_limit: None,
+            speed: 1.0,
+        }
+    }
+}
+
+/// Escapes text for HTML and SVG.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Returns the largest size the screen of a recording has, in columns and
+/// rows, as it may be resized along the way.
+pub fn largest_size(header: &Header, events: &[Event]) -> (u32, u32) {
+    events.iter().fold(
+        (header.width, header.height),
+        |(cols, rows), event| match *event {
+            Event::Resize(_, width, height) => (cols.max(width.0), rows.max(height.0)),
+            _ => (cols, rows),
+        },
+    )
+}
+
+/// Plays a recording and calls `frame` with the time and the terminal
+/// every time the screen or the cursor changes.
+///
+/// Times are those of `playback`, and so is how long the recording lasts,
+/// which is returned.
+pub fn for_each_frame<F>(
+    header: &Header,
+    events: EventStream,
+    playback: Playback,
+    mut frame: F,
+) -> Result<f64, Error>
+where
+    F: FnMut(f64, &Vt) -> Result<(), Error>,
+{
+    let mut vt = Vt::new(Width(header.width), Height(header.height));
+    let mut shown: Option<(Vec<Line>, Cursor)> = None;
+    let mut last = 0.0_f64;
+    let mut time = 0.0_f64;
+    for event in events {
+        let event = event?;
+        let delay = (event.time() - last).max(0.0);
+        last = event.time();
+        time += playback
+            .idle_time_limit
+            .map_or(delay, |limit| delay.min(limit));
+
+        vt.feed(&event);
+        let changed = match shown {
+            Some((ref lines, cursor)) => lines.as_slice() != vt.lines() || cursor != vt.cursor(),
+            None => true,
+        };
+        if changed {
+            frame(time / playback.speed, &vt)?;
+            shown = Some((vt.lines().to_vec(), vt.cursor()));
+        }
+    }
+    Ok(time / playback.speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_formats::Event;
+
+    pub(super) fn header(width: u32, height: u32) -> Header {
+        Header {
+            version: 2,
+            width,
+            height,
+            timestamp: None,
+            duration: None,
+            idle_time_limit: None,
+            command: None,
+            title: None,
+            env: None,
+        }
+    }
+
+    #[test]
+    fn calls_back_on_changes_only() {
+        let events = vec![
+            Event::Output(1.0, "a".to_string()),
+            Event::Marker(1.5, "intro".to_string()),
+            Event::Output(2.0, "\x1b[1m".to_string()),
+            Event::Output(12.0, "b".to_string()),
+        ];
+        let mut frames = vec![];
+        let duration = for_each_frame(
+            &header(10, 2),
+            Box::new(events.into_iter().map(Ok)),
+            Playback {
+                idle_time_limit: Some(3.0),
+                speed: 2.0,
+            },
+            |time, vt| {
+                frames.push((time, vt.text()[0].clone()));
+                Ok(())
+            },
+        )
+        .unwrap();
+        // The pause before "b" is cut down to 3 seconds, then all of it
+        // goes twice as fast.
+        assert_eq!(
+            frames,
+            vec![(0.5, "a".to_string()), (2.5, "ab".to_string())]
+        );
+        assert_eq!(duration, 2.5);
+    }
+}
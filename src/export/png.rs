@@ -0,0 +1,105 @@
+use super::raster::Renderer;
+use super::theme::Theme;
+use crate::vt::Vt;
+use ::png::{BitDepth, ColorType, Encoder};
+use failure::{Error, Fail};
+use std::io::Write;
+
+/// The most pixels an image may have, which takes a few hundred megabytes
+/// to draw and encode.
+const MAX_PIXELS: usize = 50_000_000;
+
+#[derive(Debug, Fail)]
+enum PngFailure {
+    #[fail(
+        display = "the image would be too large: {}x{} pixels (at most {} in all, try a smaller font scale)",
+        width, height, max
+    )]
+    TooLarge {
+        width: usize,
+        height: usize,
+        max: usize,
+    },
+}
+
+/// Writes a PNG image of the screen, e.g. as the poster of a recording,
+/// drawn with the built-in font scaled up `scale` times. The image is the
+/// size of the screen, which may have been resized since the recording's
+/// header.
+pub fn write<W: Write>(out: W, vt: &Vt, theme: &Theme, scale: usize) -> Result<(), Error> {
+    let (cols, rows) = vt.size();
+    let renderer = Renderer::new(theme, cols.0, rows.0, scale);
+    let (width, height) = renderer.size();
+    if width.saturating_mul(height) > MAX_PIXELS {
+        return Err(PngFailure::TooLarge {
+            width,
+            height,
+            max: MAX_PIXELS,
+        }
+        .into());
+    }
+    let canvas = renderer.draw(vt.lines(), Some(vt.cursor()));
+    let mut encoder = Encoder::new(out, canvas.width as u32, canvas.height as u32);
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&canvas.to_rgb())?;
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::{Height, Width};
+    use ::png::Decoder;
+
+    #[test]
+    fn draws_the_screen() {
+        let mut vt = Vt::new(Width(10), Height(2));
+        vt.feed_str("\x1b[41m \x1b[0m");
+        let theme = Theme::default();
+        let mut png = vec![];
+        write(&mut png, &vt, &theme, 2).unwrap();
+
+        let mut reader = Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(
+            (info.width, info.height),
+            ((10 * 9 + 18) * 2, (2 * 18 + 18) * 2)
+        );
+        let pixel = |x: usize, y: usize| {
+            let i = (y * info.width as usize + x) * 3;
+            (pixels[i], pixels[i + 1], pixels[i + 2])
+        };
+        assert_eq!(pixel(0, 0), theme.bg);
+        // The red cell, past the padding.
+        assert_eq!(pixel(18, 18), theme.palette[1]);
+    }
+
+    #[test]
+    fn draws_resized_screens() {
+        let mut vt = Vt::new(Width(10), Height(2));
+        vt.resize(Width(20), Height(3));
+        vt.feed_str("\x1b[3;20H\x1b[41m \x1b[0m\x1b[?25l");
+        let theme = Theme::default();
+        let mut png = vec![];
+        write(&mut png, &vt, &theme, 1).unwrap();
+
+        let mut reader = Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (20 * 9 + 18, 3 * 18 + 18));
+        // The red cell in the bottom right corner, past the padding.
+        let i = ((9 + 2 * 18) * info.width as usize + 9 + 19 * 9) * 3;
+        assert_eq!((pixels[i], pixels[i + 1], pixels[i + 2]), theme.palette[1]);
+    }
+
+    #[test]
+    fn rejects_huge_images() {
+        let vt = Vt::new(Width(500), Height(200));
+        let result = write(vec![], &vt, &Theme::default(), 8);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use super::font::{self, Font};
+use super::theme::{Rgb, Theme};
+use crate::vt::cell::{Line, Pen};
+use crate::vt::Cursor;
+
+/// Room around the screen, in unscaled pixels.
+const PADDING: usize = font::WIDTH;
+/// The rows of a glyph lines through text are drawn on.
+const UNDERLINE: usize = font::BASELINE + 1;
+const STRIKETHROUGH: usize = font::BASELINE - 4;
+
+/// An image, as its pixels row by row.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize, color: Rgb) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for y in y..(y + height).min(self.height) {
+            let row = y * self.width;
+            for x in x..(x + width).min(self.width) {
+                self.pixels[row + x] = color;
+            }
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Returns the pixels as red, green and blue bytes.
+    pub fn to_rgb(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&(r, g, b)| [r, g, b])
+            .collect()
+    }
+}
+
+/// Returns the color halfway between two others, for faint text.
+fn blend((r1, g1, b1): Rgb, (r2, g2, b2): Rgb) -> Rgb {
+    let half = |a: u8, b: u8| ((u16::from(a) + u16::from(b)) / 2) as u8;
+    (half(r1, r2), half(g1, g2), half(b1, b2))
+}
+
+/// Draws screens with the built-in font, for exports that are images.
+pub struct Renderer<'a> {
+    theme: &'a Theme,
+    font: &'static Font,
+    cols: usize,
+    rows: usize,
+    /// How many pixels of the image a pixel of the font takes, each way.
+    scale: usize,
+}
+
+impl<'a> Renderer<'a> {
+    /// Returns a renderer of screens of up to `cols` by `rows` cells, e.g.
+    /// the largest size of a recording. Smaller screens are drawn at the top
+    /// left, whatever is outside of it is left out.
+    pub fn new(theme: &'a Theme, cols: u32, rows: u32, scale: usize) -> Self {
+        Renderer {
+            theme,
+            font: Font::builtin(),
+            cols: cols as usize,
+            rows: rows as usize,
+            scale: scale.max(1),
+        }
+    }
+
+    /// The size of the images, in pixels.
+    pub fn size(&self) -> (usize, usize) {
+        (
+            (self.cols * font::WIDTH + 2 * PADDING) * self.scale,
+            (self.rows * font::HEIGHT + 2 * PADDING) * self.scale,
+        )
+    }
+
+    /// Draws the lines of a screen, and the cursor if it is given.
+    pub fn draw(&self, lines: &[Line], cursor: Option<Cursor>) -> Canvas {
+        let (width, height) = self.size();
+        let mut canvas = Canvas::new(width, height, self.theme.bg);
+        for (row, line) in lines.iter().take(self.rows).enumerate() {
+            for (col, cell) in line.cells.iter().take(self.cols).enumerate() {
+                let on_cursor = cursor.is_some_and(|c| c.visible && c.row == row && c.col == col);
+                let pen = if on_cursor {
+                    Pen {
+                        inverse: !cell.pen.inverse,
+                        ..cell.pen
+                    }
+                } else {
+                    cell.pen
+                };
+                // The right half of a wide character only has its background.
+                let ch = if cell.width == 0 { ' ' } else { cell.ch };
+                self.draw_cell(&mut canvas, col, row, ch, cell.width as usize, &pen);
+            }
+        }
+        canvas
+    }
+
+    fn draw_cell(
+        &self,
+        canvas: &mut Canvas,
+        col: usize,
+        row: usize,
+        ch: char,
+        width: usize,
+        pen: &Pen,
+    ) {
+        let scale = self.scale;
+        let x0 = (PADDING + col * font::WIDTH) * scale;
+        let y0 = (PADDING + row * font::HEIGHT) * scale;
+        let (mut fg, bg) = self.theme.colors(pen);
+        if pen.faint {
+            fg = blend(fg, bg);
+        }
+        if bg != self.theme.bg {
+            canvas.fill(x0, y0, font::WIDTH * scale, font::HEIGHT * scale, bg);
+        }
+        if fg == bg {
+            return;
+        }
+
+        // Wide characters are drawn in the middle of both of their cells.
+        let x0 = x0 + font::WIDTH.saturating_sub(1) * width.saturating_sub(1) / 2 * scale;
+        if ch != ' ' {
+            let glyph = self.font.glyph(ch);
+            for (y, &bits) in glyph.iter().enumerate() {
+                for x in 0..font::WIDTH {
+                    // Bold is drawn twice, one pixel apart.
+                    let set =
+                        font::is_set(bits, x) || (pen.bold && x > 0 && font::is_set(bits, x - 1));
+                    if set {
+                        canvas.fill(x0 + x * scale, y0 + y * scale, scale, scale, fg);
+                    }
+                }
+            }
+        }
+        for &(drawn, y) in &[
+            (pen.underline, UNDERLINE),
+            (pen.strikethrough, STRIKETHROUGH),
+        ] {
+            if drawn {
+                canvas.fill(x0, y0 + y * scale, font::WIDTH * scale, scale, fg);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::{Height, Width};
+    use crate::vt::Vt;
+
+    fn render(data: &str, scale: usize) -> Canvas {
+        let mut vt = Vt::new(Width(3), Height(1));
+        vt.feed_str(data);
+        let theme = Theme::default();
+        Renderer::new(&theme, 3, 1, scale).draw(vt.lines(), Some(vt.cursor()))
+    }
+
+    /// The colors of a row of pixels, as characters: ' ' for the
+    /// background, '#' for the foreground and '?' for anything else.
+    fn row(canvas: &Canvas, y: usize) -> String {
+        let theme = Theme::default();
+        (0..canvas.width)
+            .map(|x| match canvas.pixel(x, y) {
+                c if c == theme.bg => ' ',
+                c if c == theme.fg => '#',
+                _ => '?',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sizes_images() {
+        let theme = Theme::default();
+        let renderer = Renderer::new(&theme, 80, 24, 2);
+        assert_eq!(renderer.size(), ((80 * 9 + 18) * 2, (24 * 18 + 18) * 2));
+    }
+
+    #[test]
+    fn draws_glyphs() {
+        let canvas = render("L\x1b[?25l", 1);
+        // The bottom of the L, padding excluded.
+        let bottom = PADDING + font::BASELINE - 1;
+        assert_eq!(row(&canvas, bottom)[PADDING..PADDING + 9], *" ####### ");
+        let bold = render("\x1b[1mL\x1b[?25l", 1);
+        assert_eq!(row(&bold, bottom)[PADDING..PADDING + 9], *" ########");
+    }
+
+    #[test]
+    fn scales_glyphs() {
+        let canvas = render("L\x1b[?25l", 2);
+        let bottom = (PADDING + font::BASELINE - 1) * 2;
+        for y in &[bottom, bottom + 1] {
+            assert_eq!(
+                row(&canvas, *y)[PADDING * 2..(PADDING + 9) * 2],
+                *"  ##############  "
+            );
+        }
+    }
+
+    #[test]
+    fn draws_the_cursor_and_backgrounds() {
+        let canvas = render("a\x1b[44m \x1b[0m", 1);
+        let y = PADDING + 1;
+        let row = row(&canvas, y);
+        // The cursor is a block in the last column, after a blue cell.
+        assert_eq!(&row[PADDING + 9..PADDING + 18], "?????????");
+        assert_eq!(&row[PADDING + 18..PADDING + 27], "#########");
+        assert_eq!(canvas.pixel(PADDING + 9, y), Theme::default().palette[4]);
+    }
+
+    #[test]
+    fn draws_underlines() {
+        let canvas = render("\x1b[4m \x1b[?25l", 1);
+        let line = row(&canvas, PADDING + UNDERLINE);
+        assert_eq!(&line[PADDING..PADDING + 9], "#########");
+    }
+}
@@ -0,0 +1,314 @@
+use super::theme::{hex, Theme};
+use super::{escape, for_each_frame, Playback, END_PAUSE};
+use crate::input_formats::EventStream;
+use crate::vt::cell::{Line, Pen};
+use asciicast::Header;
+use failure::Error;
+use std::collections::HashMap;
+use std::io::Write;
+
+const FONT_SIZE: u32 = 15;
+/// The size of a character cell, in pixels.
+const CELL_WIDTH: u32 = 9;
+const LINE_HEIGHT: u32 = 18;
+/// Where the text sits in its line.
+const BASELINE: u32 = 14;
+const PADDING: u32 = 10;
+
+/// Returns the attributes drawing text with `pen`, besides its color.
+fn text_attributes(pen: &Pen) -> String {
+    let mut attributes = String::new();
+    if pen.bold {
+        attributes.push_str(" font-weight=\"bold\"");
+    }
+    if pen.faint {
+        attributes.push_str(" opacity=\"0.5\"");
+    }
+    if pen.italic {
+        attributes.push_str(" font-style=\"italic\"");
+    }
+    match (pen.underline, pen.strikethrough) {
+        (true, true) => attributes.push_str(" text-decoration=\"underline line-through\""),
+        (true, false) => attributes.push_str(" text-decoration=\"underline\""),
+        (false, true) => attributes.push_str(" text-decoration=\"line-through\""),
+        (false, false) => (),
+    }
+    attributes
+}
+
+/// Returns the SVG drawing a line at the top of its frame: its backgrounds,
+/// then its text in runs of the same style.
+fn line_svg(line: &Line, row: usize, theme: &Theme) -> String {
+    let y = row as u32 * LINE_HEIGHT;
+    let mut svg = String::new();
+
+    // (column, width, background)
+    let mut backgrounds: Vec<(usize, usize, String)> = vec![];
+    // (column, text, fill, attributes)
+    let mut runs: Vec<(usize, String, String, String)> = vec![];
+    for (col, cell) in line.cells.iter().enumerate() {
+        if cell.width == 0 {
+            continue;
+        }
+        let (fg, bg) = theme.colors(&cell.pen);
+        if bg != theme.bg {
+            let bg = hex(bg);
+            match backgrounds.last_mut() {
+                Some(last) if last.0 + last.1 == col && last.2 == bg => {
+                    last.1 += cell.width as usize
+                }
+                _ => backgrounds.push((col, cell.width as usize, bg)),
+            }
+        }
+        let fill = if fg == theme.fg {
+            String::new()
+        } else {
+            hex(fg)
+        };
+        let attributes = text_attributes(&cell.pen);
+        match runs.last_mut() {
+            Some(last) if last.2 == fill && last.3 == attributes => last.1.push(cell.ch),
+            _ => runs.push((col, cell.ch.to_string(), fill, attributes)),
+        }
+    }
+
+    for (col, width, fill) in backgrounds {
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+            col as u32 * CELL_WIDTH,
+            y,
+            width as u32 * CELL_WIDTH,
+            LINE_HEIGHT,
+            fill
+        ));
+    }
+    for (col, text, fill, attributes) in runs {
+        // Blanks at the end of a run take no room, blank runs none at all.
+        let text = text.trim_end();
+        if text.is_empty() {
+            continue;
+        }
+        let fill = if fill.is_empty() {
+            fill
+        } else {
+            format!(" fill=\"{}\"", fill)
+        };
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\"{}{}>{}</text>",
+            col as u32 * CELL_WIDTH,
+            y + BASELINE,
+            fill,
+            attributes,
+            escape(text)
+        ));
+    }
+    svg
+}
+
+/// Returns the SVG drawing a screen.
+fn screen_svg(lines: &[Line], theme: &Theme) -> String {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(row, line)| line_svg(line, row, theme))
+        .collect()
+}
+
+/// Writes an animated SVG of the recording, e.g. to show it in a README.
+///
+/// Every different screen is drawn once, one below the other, and CSS
+/// keyframes move them into view in turn. The cursor isn't drawn, so
+/// screens only differing by it are the same frame.
+pub fn write<W: Write>(
+    mut out: W,
+    header: &Header,
+    events: EventStream,
+    playback: Playback,
+    theme: &Theme,
+) -> Result<(), Error> {
+    let blank = vec![Line::blank(header.width as usize, Pen::default()); header.height as usize];
+    let mut screens = vec![screen_svg(&blank, theme)];
+    let mut indexes: HashMap<String, usize> = HashMap::new();
+    indexes.insert(screens[0].clone(), 0);
+    // When each frame starts and which of the screens it shows.
+    let mut frames: Vec<(f64, usize)> = vec![(0.0, 0)];
+
+    let duration = for_each_frame(header, events, playback, |time, vt| {
+        let screen = screen_svg(vt.lines(), theme);
+        let index = match indexes.get(&screen) {
+            Some(&index) => index,
+            None => {
+                screens.push(screen.clone());
+                indexes.insert(screen, screens.len() - 1);
+                screens.len() - 1
+            }
+        };
+        if frames.last().map(|&(_, last)| last) != Some(index) {
+            frames.push((time, index));
+        }
+        Ok(())
+    })?;
+
+    let screen_width = header.width * CELL_WIDTH;
+    let screen_height = header.height * LINE_HEIGHT;
+    let width = screen_width + 2 * PADDING;
+    let height = screen_height + 2 * PADDING;
+
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         viewBox=\"0 0 {w} {h}\" xml:space=\"preserve\">",
+        w = width,
+        h = height
+    )?;
+    if let Some(ref title) = header.title {
+        writeln!(out, "<title>{}</title>", escape(title))?;
+    }
+    writeln!(out, "<style>")?;
+    writeln!(
+        out,
+        "text {{ font-family: \"SF Mono\", Menlo, Consolas, \"DejaVu Sans Mono\", monospace; \
+         font-size: {}px; white-space: pre; }}",
+        FONT_SIZE
+    )?;
+    if frames.len() > 1 {
+        let total = duration + END_PAUSE;
+        writeln!(out, "@keyframes play {{")?;
+        for &(time, index) in &frames {
+            writeln!(
+                out,
+                "{:.3}% {{ transform: translateY(-{}px); }}",
+                time / total * 100.0,
+                index as u32 * screen_height
+            )?;
+        }
+        // Holds the last frame until the end.
+        let last = frames[frames.len() - 1].1;
+        writeln!(
+            out,
+            "100% {{ transform: translateY(-{}px); }}",
+            last as u32 * screen_height
+        )?;
+        writeln!(out, "}}")?;
+        writeln!(
+            out,
+            ".frames {{ animation: play {:.3}s step-end infinite; }}",
+            total
+        )?;
+    }
+    writeln!(out, "</style>")?;
+    writeln!(
+        out,
+        "<rect width=\"{}\" height=\"{}\" rx=\"4\" fill=\"{}\"/>",
+        width,
+        height,
+        hex(theme.bg)
+    )?;
+    // Nested SVG elements clip what they hold, screens included.
+    writeln!(
+        out,
+        "<svg x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\">",
+        PADDING, PADDING, screen_width, screen_height
+    )?;
+    writeln!(out, "<g class=\"frames\" fill=\"{}\">", hex(theme.fg))?;
+    for (index, screen) in screens.iter().enumerate() {
+        writeln!(
+            out,
+            "<svg y=\"{}\" width=\"{}\" height=\"{}\">{}</svg>",
+            index as u32 * screen_height,
+            screen_width,
+            screen_height,
+            screen
+        )?;
+    }
+    writeln!(out, "</g>")?;
+    writeln!(out, "</svg>")?;
+    writeln!(out, "</svg>")?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_formats::Event;
+    use crate::terminal::{Height, Width};
+    use crate::vt::Vt;
+
+    fn export(events: Vec<Event>, playback: Playback) -> String {
+        let mut svg = vec![];
+        write(
+            &mut svg,
+            &super::super::tests::header(10, 2),
+            Box::new(events.into_iter().map(Ok)),
+            playback,
+            &Theme::default(),
+        )
+        .unwrap();
+        String::from_utf8(svg).unwrap()
+    }
+
+    #[test]
+    fn draws_lines() {
+        let mut vt = Vt::new(Width(10), Height(1));
+        vt.feed_str("a<b \x1b[1;31;44mred\x1b[0m  x");
+        assert_eq!(
+            screen_svg(vt.lines(), &Theme::default()),
+            "<rect x=\"36\" y=\"0\" width=\"27\" height=\"18\" fill=\"#26b0d7\"/>\
+             <text x=\"0\" y=\"14\">a&lt;b</text>\
+             <text x=\"36\" y=\"14\" fill=\"#dd3c69\" font-weight=\"bold\">red</text>\
+             <text x=\"63\" y=\"14\">  x</text>"
+        );
+    }
+
+    #[test]
+    fn animates_frames() {
+        let svg = export(
+            vec![
+                Event::Output(1.0, "a".to_string()),
+                Event::Output(2.0, "b".to_string()),
+                Event::Output(3.0, "\x08 \x08".to_string()),
+            ],
+            Playback::default(),
+        );
+        assert!(svg
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"110\" height=\"56\""));
+        // Erasing "b" brings back the screen with only "a", which is drawn
+        // once and shown twice.
+        assert_eq!(svg.matches("<svg y=").count(), 3);
+        assert!(svg.contains(
+            "@keyframes play {\n\
+             0.000% { transform: translateY(-0px); }\n\
+             20.000% { transform: translateY(-36px); }\n\
+             40.000% { transform: translateY(-72px); }\n\
+             60.000% { transform: translateY(-36px); }\n\
+             100% { transform: translateY(-36px); }\n\
+             }\n\
+             .frames { animation: play 5.000s step-end infinite; }"
+        ));
+    }
+
+    #[test]
+    fn speeds_up_and_limits_idle_time() {
+        let svg = export(
+            vec![
+                Event::Output(1.0, "a".to_string()),
+                Event::Output(11.0, "b".to_string()),
+            ],
+            Playback {
+                idle_time_limit: Some(2.0),
+                speed: 2.0,
+            },
+        );
+        // "b" comes 1.5 seconds in, and the animation lasts 1.5 + 2 seconds.
+        assert!(svg.contains("42.857% { transform: translateY(-72px); }"));
+        assert!(svg.contains("animation: play 3.500s"));
+    }
+
+    #[test]
+    fn does_not_animate_a_single_frame() {
+        let svg = export(vec![], Playback::default());
+        assert!(!svg.contains("@keyframes"));
+        assert_eq!(svg.matches("<svg y=").count(), 1);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::vt::cell::{Color, Pen};
+use failure::{Error, Fail};
+use std::str::FromStr;
+
+#[derive(Debug, Fail)]
+enum ThemeFailure {
+    #[fail(
+        display = "unknown theme: {} (expected {} or BG,FG followed by 8 or 16 colors in hex)",
+        name, names
+    )]
+    Unknown { name: String, names: String },
+}
+
+/// A color as red, green and blue.
+pub type Rgb = (u8, u8, u8);
+
+/// The built-in themes, as their background, foreground and 16 colors.
+const THEMES: &[(&str, &str)] = &[
+    (
+        "asciinema",
+        "121314,cccccc,000000,dd3c69,4ebf22,ddaf3c,26b0d7,b954e1,54e1b9,d9d9d9,\
+         4d4d4d,dd3c69,4ebf22,ddaf3c,26b0d7,b954e1,54e1b9,ffffff",
+    ),
+    (
+        "dracula",
+        "282a36,f8f8f2,21222c,ff5555,50fa7b,f1fa8c,bd93f9,ff79c6,8be9fd,f8f8f2,\
+         6272a4,ff6e6e,69ff94,ffffa5,d6acff,ff92df,a4ffff,ffffff",
+    ),
+    (
+        "monokai",
+        "272822,f8f8f2,272822,f92672,a6e22e,f4bf75,66d9ef,ae81ff,a1efe4,f8f8f2,\
+         75715e,f92672,a6e22e,f4bf75,66d9ef,ae81ff,a1efe4,f9f8f5",
+    ),
+    (
+        "nord",
+        "2e3440,eceff4,3b4252,bf616a,a3be8c,ebcb8b,81a1c1,b48ead,88c0d0,e5e9f0,\
+         4c566a,bf616a,a3be8c,ebcb8b,81a1c1,b48ead,8fbcbb,eceff4",
+    ),
+    (
+        "solarized-dark",
+        "002b36,839496,073642,dc322f,859900,b58900,268bd2,d33682,2aa198,eee8d5,\
+         002b36,cb4b16,586e75,657b83,839496,6c71c4,93a1a1,fdf6e3",
+    ),
+    (
+        "solarized-light",
+        "fdf6e3,657b83,073642,dc322f,859900,b58900,268bd2,d33682,2aa198,eee8d5,\
+         002b36,cb4b16,586e75,657b83,839496,6c71c4,93a1a1,fdf6e3",
+    ),
+    (
+        "tango",
+        "121314,cccccc,000000,cc0000,4e9a06,c4a000,3465a4,75507b,06989a,d3d7cf,\
+         555753,ef2929,8ae234,fce94f,729fcf,ad7fa8,34e2e2,eeeeec",
+    ),
+];
+
+/// The colors a recording is shown with.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Theme {
+    pub fg: Rgb,
+    pub bg: Rgb,
+    /// The 16 ANSI colors, normal then bright.
+    pub palette: [Rgb; 16],
+}
+
+impl Default for Theme {
+    /// The colors of the asciinema web player.
+    fn default() -> Self {
+        custom(THEMES[0].1).expect("valid built-in theme")
+    }
+}
+
+fn parse_hex(color: &str) -> Option<Rgb> {
+    let color = color.trim().trim_start_matches('#');
+    if color.len() != 6 || !color.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&color[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Parses a theme given as `BG,FG` and its 8 or 16 colors in hex. With 8,
+/// the bright colors are the same as the normal ones.
+fn custom(spec: &str) -> Option<Theme> {
+    let colors = spec
+        .split(',')
+        .map(parse_hex)
+        .collect::<Option<Vec<Rgb>>>()?;
+    if colors.len() != 10 && colors.len() != 18 {
+        return None;
+    }
+    let mut palette = [(0, 0, 0); 16];
+    for (i, color) in palette.iter_mut().enumerate() {
+        *color = colors[2 + i % (colors.len() - 2)];
+    }
+    Some(Theme {
+        bg: colors[0],
+        fg: colors[1],
+        palette,
+    })
+}
+
+impl FromStr for Theme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        THEMES
+            .iter()
+            .find(|&&(name, _)| name == s)
+            .and_then(|&(_, spec)| custom(spec))
+            .or_else(|| custom(s))
+            .ok_or_else(|| {
+                let names: Vec<&str> = THEMES.iter().map(|&(name, _)| name).collect();
+                ThemeFailure::Unknown {
+                    name: s.to_string(),
+                    names: names.join(", "),
+                }
+                .into()
+            })
+    }
+}
+
+impl Theme {
+    /// Returns the RGB value of `color`, `default` being the one to use for
+    /// the terminal's own color.
+    pub fn rgb(&self, color: Color, default: Rgb) -> Rgb {
+        match color {
+            Color::Default => default,
+            Color::Indexed(n) if n < 16 => self.palette[n as usize],
+            // The 6x6x6 color cube.
+            Color::Indexed(n) if n < 232 => {
+                let level = |i: u8| if i == 0 { 0 } else { 55 + i * 40 };
+                let n = n - 16;
+                (level(n / 36), level(n / 6 % 6), level(n % 6))
+            }
+            // The grayscale ramp.
+            Color::Indexed(n) => {
+                let gray = 8 + (n - 232) * 10;
+                (gray, gray, gray)
+            }
+            Color::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+
+    /// Returns the foreground and background colors text is drawn with.
+    ///
+    /// Like most terminals, bold text uses the bright variant of the first
+    /// 8 colors.
+    pub fn colors(&self, pen: &Pen) -> (Rgb, Rgb) {
+        let fg = match pen.fg {
+            Color::Indexed(n) if n < 8 && pen.bold => Color::Indexed(n + 8),
+            fg => fg,
+        };
+        let mut fg = self.rgb(fg, self.fg);
+        let mut bg = self.rgb(pen.bg, self.bg);
+        if pen.inverse {
+            std::mem::swap(&mut fg, &mut bg);
+        }
+        if pen.hidden {
+            fg = bg;
+        }
+        (fg, bg)
+    }
+}
+
+/// Returns a color as CSS and SVG write it.
+pub fn hex((r, g, b): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_themes() {
+        for &(name, _) in THEMES {
+            assert!(name.parse::<Theme>().is_ok(), "{}", name);
+        }
+        let dracula: Theme = "dracula".parse().unwrap();
+        assert_eq!(
+            (dracula.bg, dracula.fg),
+            ((0x28, 0x2a, 0x36), (0xf8, 0xf8, 0xf2))
+        );
+
+        let theme: Theme = "000000,#ffffff,010101,020202,030303,040404,050505,060606,070707,080808"
+            .parse()
+            .unwrap();
+        assert_eq!(theme.bg, (0, 0, 0));
+        assert_eq!(theme.fg, (255, 255, 255));
+        assert_eq!(theme.palette[1], (2, 2, 2));
+        assert_eq!(theme.palette[9], (2, 2, 2));
+
+        assert!("nope".parse::<Theme>().is_err());
+        assert!("000000,ffffff".parse::<Theme>().is_err());
+        assert!("000000,fffffg,0,0,0,0,0,0,0,0".parse::<Theme>().is_err());
+    }
+
+    #[test]
+    fn converts_colors() {
+        let theme = Theme::default();
+        let default = (1, 2, 3);
+        assert_eq!(theme.rgb(Color::Default, default), default);
+        assert_eq!(theme.rgb(Color::Indexed(1), default), theme.palette[1]);
+        assert_eq!(theme.rgb(Color::Indexed(16), default), (0, 0, 0));
+        assert_eq!(theme.rgb(Color::Indexed(196), default), (255, 0, 0));
+        assert_eq!(theme.rgb(Color::Indexed(110), default), (135, 175, 215));
+        assert_eq!(theme.rgb(Color::Indexed(232), default), (8, 8, 8));
+        assert_eq!(theme.rgb(Color::Indexed(255), default), (238, 238, 238));
+        assert_eq!(theme.rgb(Color::Rgb(4, 5, 6), default), (4, 5, 6));
+        assert_eq!(hex((255, 16, 0)), "#ff1000");
+    }
+
+    #[test]
+    fn applies_attributes_to_colors() {
+        let theme = Theme::default();
+        let pen = Pen {
+            fg: Color::Indexed(1),
+            bold: true,
+            ..Pen::default()
+        };
+        assert_eq!(theme.colors(&pen), (theme.palette[9], theme.bg));
+        let pen = Pen {
+            inverse: true,
+            ..Pen::default()
+        };
+        assert_eq!(theme.colors(&pen), (theme.bg, theme.fg));
+        let pen = Pen {
+            hidden: true,
+            ..Pen::default()
+        };
+        assert_eq!(theme.colors(&pen), (theme.bg, theme.bg));
+    }
+}
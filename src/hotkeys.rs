@@ -0,0 +1,273 @@
+use failure::{Error, Fail};
+use std::mem;
+use std::str::FromStr;
+
+#[derive(Debug, Fail)]
+enum HotkeyFailure {
+    #[fail(
+        display = "invalid key: {} (expected e.g. `C-\\`, `^]` or a single character)",
+        key
+    )]
+    InvalidKey { key: String },
+}
+
+/// A single key, as the byte the terminal sends for it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Key(pub u8);
+
+impl FromStr for Key {
+    type Err = Error;
+
+    /// Parses `C-x` / `^x` control key notation or a single ASCII character.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || HotkeyFailure::InvalidKey { key: s.to_string() };
+        let control = s
+            .strip_prefix("C-")
+            .or_else(|| s.strip_prefix('^').filter(|rest| !rest.is_empty()));
+
+        let bytes = control.unwrap_or(s).as_bytes();
+        if bytes.len() != 1 || !bytes[0].is_ascii() {
+            return Err(invalid().into());
+        }
+
+        match control {
+            Some(_) => match bytes[0].to_ascii_uppercase() {
+                // `C-@` through `C-_` map to 0x00 - 0x1F.
+                c @ b'@'..=b'_' => Ok(Key(c - b'@')),
+                b'?' => Ok(Key(0x7F)),
+                _ => Err(invalid().into()),
+            },
+            None => Ok(Key(bytes[0])),
+        }
+    }
+}
+
+/// Actions triggered by a key pressed after the prefix key.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Action {
+    /// Adds a marker, with the label typed for it if any.
+    AddMarker(Option<String>),
+    TogglePause,
+    /// The label of a marker is typed next, until Enter adds it or Escape
+    /// cancels it.
+    StartLabel,
+    /// Shows what the label being typed changed by, e.g. a character.
+    EchoLabel(Vec<u8>),
+    CancelLabel,
+}
+
+/// Where we are in a key sequence.
+enum State {
+    Idle,
+    /// The prefix key was pressed.
+    Prefix,
+    /// A marker label is being typed, as the bytes typed so far.
+    Label(Vec<u8>),
+}
+
+/// Intercepts prefix key sequences in the input on its way to the child.
+///
+/// Pressing the prefix key twice sends it to the child. A prefix followed by
+/// a key without an action is passed through untouched. While a marker label
+/// is typed, nothing goes to the child.
+pub struct Hotkeys {
+    prefix: Key,
+    state: State,
+}
+
+impl Hotkeys {
+    pub fn new(prefix: Key) -> Self {
+        Hotkeys {
+            prefix,
+            state: State::Idle,
+        }
+    }
+
+    /// Returns the input to forward to the child and the actions to run.
+    pub fn filter(&mut self, data: &[u8]) -> (Vec<u8>, Vec<Action>) {
+        let mut forward = Vec::with_capacity(data.len());
+        let mut actions = vec![];
+        for &byte in data {
+            self.state = match mem::replace(&mut self.state, State::Idle) {
+                State::Idle if byte == self.prefix.0 => State::Prefix,
+                State::Idle => {
+                    forward.push(byte);
+                    State::Idle
+                }
+                State::Prefix if byte == b'M' => {
+                    actions.push(Action::StartLabel);
+                    State::Label(vec![])
+                }
+                State::Prefix => {
+                    match byte {
+                        b'm' => actions.push(Action::AddMarker(None)),
+                        b'p' => actions.push(Action::TogglePause),
+                        _ if byte == self.prefix.0 => forward.push(byte),
+                        _ => forward.extend_from_slice(&[self.prefix.0, byte]),
+                    }
+                    State::Idle
+                }
+                State::Label(label) => type_label(label, byte, &mut actions),
+            };
+        }
+        (forward, actions)
+    }
+}
+
+/// Applies a key typed while labelling a marker and returns the next state.
+fn type_label(mut label: Vec<u8>, byte: u8, actions: &mut Vec<Action>) -> State {
+    match byte {
+        b'\r' | b'\n' => {
+            let label = String::from_utf8_lossy(&label).trim().to_string();
+            actions.push(Action::AddMarker(Some(label).filter(|l| !l.is_empty())));
+            return State::Idle;
+        }
+        // Escape.
+        0x1B => {
+            actions.push(Action::CancelLabel);
+            return State::Idle;
+        }
+        // Backspace removes a whole character, however many bytes it takes.
+        0x7F | 0x08 => {
+            if !label.is_empty() {
+                while label.pop().is_some_and(|b| b & 0xC0 == 0x80) {}
+                actions.push(Action::EchoLabel(b"\x08 \x08".to_vec()));
+            }
+        }
+        // Other control keys don't belong in a label.
+        0x00..=0x1F => (),
+        _ => {
+            label.push(byte);
+            actions.push(Action::EchoLabel(vec![byte]));
+        }
+    }
+    State::Label(label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_control_keys() {
+        assert_eq!("C-\\".parse::<Key>().unwrap(), Key(0x1C));
+        assert_eq!("C-a".parse::<Key>().unwrap(), Key(0x01));
+        assert_eq!("^]".parse::<Key>().unwrap(), Key(0x1D));
+        assert_eq!("^?".parse::<Key>().unwrap(), Key(0x7F));
+    }
+
+    #[test]
+    fn parses_plain_keys() {
+        assert_eq!("^".parse::<Key>().unwrap(), Key(b'^'));
+        assert_eq!("x".parse::<Key>().unwrap(), Key(b'x'));
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert!("".parse::<Key>().is_err());
+        assert!("C-".parse::<Key>().is_err());
+        assert!("C-ab".parse::<Key>().is_err());
+        assert!("ab".parse::<Key>().is_err());
+        assert!("C-1".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn passes_through_without_prefix() {
+        let mut hotkeys = Hotkeys::new(Key(0x1C));
+        assert_eq!(hotkeys.filter(b"ls -la\r"), (b"ls -la\r".to_vec(), vec![]));
+    }
+
+    #[test]
+    fn intercepts_marker_key() {
+        let mut hotkeys = Hotkeys::new(Key(0x1C));
+        assert_eq!(
+            hotkeys.filter(b"a\x1Cmb"),
+            (b"ab".to_vec(), vec![Action::AddMarker(None)])
+        );
+    }
+
+    #[test]
+    fn intercepts_pause_key() {
+        let mut hotkeys = Hotkeys::new(Key(0x1C));
+        assert_eq!(
+            hotkeys.filter(b"\x1Cpsecret\x1Cp"),
+            (
+                b"secret".to_vec(),
+                vec![Action::TogglePause, Action::TogglePause]
+            )
+        );
+    }
+
+    #[test]
+    fn intercepts_across_reads() {
+        let mut hotkeys = Hotkeys::new(Key(0x1C));
+        assert_eq!(hotkeys.filter(b"a\x1C"), (b"a".to_vec(), vec![]));
+        assert_eq!(
+            hotkeys.filter(b"m"),
+            (vec![], vec![Action::AddMarker(None)])
+        );
+    }
+
+    #[test]
+    fn types_marker_labels() {
+        let mut hotkeys = Hotkeys::new(Key(0x1C));
+        assert_eq!(
+            hotkeys.filter(b"\x1CMab"),
+            (
+                vec![],
+                vec![
+                    Action::StartLabel,
+                    Action::EchoLabel(b"a".to_vec()),
+                    Action::EchoLabel(b"b".to_vec())
+                ]
+            )
+        );
+        assert_eq!(
+            hotkeys.filter("\x7Fé\x7Fc\rx".as_bytes()),
+            (
+                b"x".to_vec(),
+                vec![
+                    Action::EchoLabel(b"\x08 \x08".to_vec()),
+                    Action::EchoLabel(vec![0xC3]),
+                    Action::EchoLabel(vec![0xA9]),
+                    Action::EchoLabel(b"\x08 \x08".to_vec()),
+                    Action::EchoLabel(b"c".to_vec()),
+                    Action::AddMarker(Some("ac".to_string()))
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn cancels_marker_labels() {
+        let mut hotkeys = Hotkeys::new(Key(0x1C));
+        assert_eq!(
+            hotkeys.filter(b"\x1CMa\x1bb"),
+            (
+                b"b".to_vec(),
+                vec![
+                    Action::StartLabel,
+                    Action::EchoLabel(b"a".to_vec()),
+                    Action::CancelLabel
+                ]
+            )
+        );
+        // An empty label is no label.
+        assert_eq!(
+            hotkeys.filter(b"\x1CM\r").1,
+            vec![Action::StartLabel, Action::AddMarker(None)]
+        );
+    }
+
+    #[test]
+    fn double_prefix_sends_prefix() {
+        let mut hotkeys = Hotkeys::new(Key(0x1C));
+        assert_eq!(hotkeys.filter(b"\x1C\x1C"), (b"\x1C".to_vec(), vec![]));
+    }
+
+    #[test]
+    fn unknown_key_is_passed_through() {
+        let mut hotkeys = Hotkeys::new(Key(0x1C));
+        assert_eq!(hotkeys.filter(b"\x1Cz"), (b"\x1Cz".to_vec(), vec![]));
+    }
+}
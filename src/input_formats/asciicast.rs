@@ -0,0 +1,148 @@
+use super::Event;
+use crate::terminal::{Height, Width};
+use failure::{Error, Fail};
+use std::io::{BufRead, Lines};
+
+#[derive(Debug, Fail)]
+enum AsciicastFailure {
+    #[fail(display = "invalid terminal size: {}", size)]
+    InvalidSize { size: String },
+    #[fail(display = "invalid exit status: {}", status)]
+    InvalidExitStatus { status: String },
+}
+
+fn parse_size(size: &str) -> Result<(Width, Height), Error> {
+    let invalid = || AsciicastFailure::InvalidSize {
+        size: size.to_string(),
+    };
+    let mut parts = size.splitn(2, 'x');
+    let cols = parts.next().ok_or_else(invalid)?;
+    let rows = parts.next().ok_or_else(invalid)?;
+    Ok((
+        Width(cols.parse().map_err(|_| invalid())?),
+        Height(rows.parse().map_err(|_| invalid())?),
+    ))
+}
+
+/// Parses a single asciicast v2 event line.
+///
+/// Event types we don't know about are skipped as the spec suggests, so
+/// `None` is returned for them.
+pub fn parse_event(line: &str) -> Result<Option<Event>, Error> {
+    let (time, code, data): (f64, String, String) = serde_json::from_str(line)?;
+    Ok(match code.as_str() {
+        "o" => Some(Event::Output(time, data)),
+        "i" => Some(Event::Input(time, data)),
+        "r" => {
+            let (width, height) = parse_size(&data)?;
+            Some(Event::Resize(time, width, height))
+        }
+        "m" => Some(Event::Marker(time, data)),
+        "x" => {
+            let code = data
+                .parse()
+                .map_err(|_| AsciicastFailure::InvalidExitStatus { status: data })?;
+            Some(Event::Exit(time, code))
+        }
+        _ => None,
+    })
+}
+
+/// Iterates over the events of an asciicast whose header was already read.
+pub struct Events<R> {
+    lines: Lines<R>,
+}
+
+impl<R: BufRead> Events<R> {
+    pub fn new(reader: R) -> Self {
+        Events {
+            lines: reader.lines(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Events<R> {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(l) => l,
+                Err(e) => return Some(Err(e.into())),
+            };
+            // Tolerate blank lines, e.g. a trailing newline.
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_event(&line) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn parses_output_event() {
+        let event = parse_event("[1.5, \"o\", \"hello\"]").unwrap();
+        assert_eq!(event, Some(Event::Output(1.5, "hello".to_string())));
+    }
+
+    #[test]
+    fn parses_input_event() {
+        let event = parse_event("[2.0, \"i\", \"ls\\r\"]").unwrap();
+        assert_eq!(event, Some(Event::Input(2.0, "ls\r".to_string())));
+    }
+
+    #[test]
+    fn parses_resize_event() {
+        let event = parse_event("[3.25, \"r\", \"100x50\"]").unwrap();
+        assert_eq!(event, Some(Event::Resize(3.25, Width(100), Height(50))));
+    }
+
+    #[test]
+    fn rejects_invalid_resize_event() {
+        assert!(parse_event("[3.25, \"r\", \"100\"]").is_err());
+        assert!(parse_event("[3.25, \"r\", \"axb\"]").is_err());
+    }
+
+    #[test]
+    fn parses_marker_event() {
+        let event = parse_event("[5.5, \"m\", \"intro\"]").unwrap();
+        assert_eq!(event, Some(Event::Marker(5.5, "intro".to_string())));
+    }
+
+    #[test]
+    fn parses_exit_event() {
+        let event = parse_event("[9.0, \"x\", \"130\"]").unwrap();
+        assert_eq!(event, Some(Event::Exit(9.0, 130)));
+        assert!(parse_event("[9.0, \"x\", \"oops\"]").is_err());
+    }
+
+    #[test]
+    fn skips_unknown_event() {
+        let event = parse_event("[4.0, \"z\", \"whatever\"]").unwrap();
+        assert_eq!(event, None);
+    }
+
+    #[test]
+    fn iterates_events() {
+        let data = "[1.0, \"o\", \"a\"]\n[1.5, \"z\", \"b\"]\n\n[2.0, \"r\", \"10x5\"]\n";
+        let events: Vec<Event> = Events::new(Cursor::new(data))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(1.0, "a".to_string()),
+                Event::Resize(2.0, Width(10), Height(5)),
+            ]
+        );
+    }
+}
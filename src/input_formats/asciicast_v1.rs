@@ -0,0 +1,92 @@
+use super::{Event, EventStream};
+use asciicast::Header;
+use failure::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// An asciicast v1 recording: one JSON document with all of the output in
+/// `stdout`, as `[delay, data]` frames timed from the frame before.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Asciicast {
+    pub version: u8,
+    pub width: u32,
+    pub height: u32,
+    pub duration: Option<f64>,
+    pub command: Option<String>,
+    pub title: Option<String>,
+    // Unset variables were saved as `null`.
+    #[serde(default)]
+    pub env: HashMap<String, Option<String>>,
+    pub stdout: Vec<(f64, String)>,
+}
+
+/// Reads a v1 recording, turning its header into a v2 one (with version 1)
+/// and its frames into output events.
+pub fn read(value: serde_json::Value) -> Result<(Header, EventStream), Error> {
+    let cast: Asciicast = serde_json::from_value(value)?;
+    let env: HashMap<String, String> = cast
+        .env
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|v| (key, v)))
+        .collect();
+    let header = Header {
+        version: 1,
+        width: cast.width,
+        height: cast.height,
+        timestamp: None,
+        duration: cast.duration,
+        idle_time_limit: None,
+        command: cast.command,
+        title: cast.title,
+        env: if env.is_empty() { None } else { Some(env) },
+    };
+
+    let mut time = 0.0;
+    let events: Vec<Result<Event, Error>> = cast
+        .stdout
+        .into_iter()
+        .map(|(delay, data)| {
+            time += delay;
+            Ok(Event::Output(time, data))
+        })
+        .collect();
+    Ok((header, Box::new(events.into_iter())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_frames_as_output_events() {
+        let value = serde_json::json!({
+            "version": 1,
+            "width": 80,
+            "height": 24,
+            "duration": 1.5,
+            "command": null,
+            "title": "demo",
+            "env": {"TERM": "xterm", "SHELL": null},
+            "stdout": [[0.5, "a"], [0.25, "b"], [0.75, "c"]]
+        });
+        let (header, events) = read(value).unwrap();
+        assert_eq!((header.version, header.width, header.height), (1, 80, 24));
+        assert_eq!(header.duration, Some(1.5));
+        assert_eq!(header.title, Some("demo".to_string()));
+        assert_eq!(header.env.unwrap().len(), 1);
+        assert_eq!(
+            events.collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![
+                Event::Output(0.5, "a".to_string()),
+                Event::Output(0.75, "b".to_string()),
+                Event::Output(1.5, "c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_recordings_without_frames() {
+        let value = serde_json::json!({"version": 1, "width": 80, "height": 24});
+        assert!(read(value).is_err());
+    }
+}
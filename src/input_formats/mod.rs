@@ -0,0 +1,247 @@
+use crate::terminal::{Height, Width};
+use ::asciicast::Header;
+use chrono::{TimeZone, Utc};
+use failure::{Error, Fail};
+use std::io::{BufRead, Cursor, Read};
+
+pub mod asciicast;
+pub mod asciicast_v1;
+pub mod script;
+pub mod ttyrec;
+
+#[derive(Debug, Fail)]
+enum InputFailure {
+    #[fail(display = "header not found")]
+    HeaderNotFound,
+    #[fail(display = "unsupported asciicast version {}", version)]
+    UnsupportedVersion { version: u64 },
+    #[fail(display = "unsupported format: ttyrec (expected asciicast v2)")]
+    UnsupportedTtyrec,
+}
+
+/// The formats recordings are read from.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Kind {
+    Asciicast,
+    AsciicastV1,
+    Ttyrec,
+}
+
+/// An event read back from a recording.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event {
+    Output(f64, String),
+    Input(f64, String),
+    Resize(f64, Width, Height),
+    Marker(f64, String),
+    Exit(f64, i32),
+}
+
+impl Event {
+    pub fn time(&self) -> f64 {
+        match *self {
+            Event::Output(t, _)
+            | Event::Input(t, _)
+            | Event::Resize(t, _, _)
+            | Event::Marker(t, _)
+            | Event::Exit(t, _) => t,
+        }
+    }
+}
+
+/// The events of a recording, in order.
+pub type EventStream = Box<dyn Iterator<Item = Result<Event, Error>>>;
+
+/// Returns the terminal size to read recordings without a header with, 80x24 unless given.
+pub fn headerless_size(cols: Option<u32>, rows: Option<u32>) -> (Width, Height) {
+    (Width(cols.unwrap_or(80)), Height(rows.unwrap_or(24)))
+}
+
+/// Reads a recording in whichever format it is in.
+///
+/// A v2 header is a document on the first line, the events follow on lines
+/// of their own. A v1 recording is a single document, possibly spread over
+/// many lines. The header's `version` says which one it was.
+///
+/// ttyrec recordings have no header, so they get one with `headerless_size`
+/// and are read like v2.
+pub fn read_recording<R: BufRead + 'static>(
+    reader: R,
+    headerless_size: (Width, Height),
+) -> Result<(Header, EventStream), Error> {
+    let (_, header, events) = read(reader, headerless_size)?;
+    Ok((header, events))
+}
+
+/// Reads an asciicast v2 recording, refusing any other format.
+///
+/// For recordings that are continued or rewritten line by line rather than
+/// only played back.
+pub fn read_asciicast<R: BufRead + 'static>(reader: R) -> Result<(Header, EventStream), Error> {
+    match read(reader, headerless_size(None, None))? {
+        (Kind::Asciicast, header, events) => Ok((header, events)),
+        (Kind::AsciicastV1, ..) => Err(InputFailure::UnsupportedVersion { version: 1 }.into()),
+        (Kind::Ttyrec, ..) => Err(InputFailure::UnsupportedTtyrec.into()),
+    }
+}
+
+fn read<R: BufRead + 'static>(
+    mut reader: R,
+    headerless_size: (Width, Height),
+) -> Result<(Kind, Header, EventStream), Error> {
+    // Read the first frame header up front, the buffer may hold less of it.
+    let mut head = vec![];
+    reader
+        .by_ref()
+        .take(ttyrec::FRAME_HEADER_LEN as u64)
+        .read_to_end(&mut head)?;
+    let ttyrec_start = if ttyrec::is_ttyrec(&head) {
+        Some(u32::from_le_bytes([head[0], head[1], head[2], head[3]]))
+    } else {
+        None
+    };
+    let mut reader = Cursor::new(head).chain(reader);
+    if let Some(seconds) = ttyrec_start {
+        let (width, height) = headerless_size;
+        let header = Header {
+            version: 2,
+            width: width.0,
+            height: height.0,
+            timestamp: Utc.timestamp_opt(i64::from(seconds), 0).single(),
+            duration: None,
+            idle_time_limit: None,
+            command: None,
+            title: None,
+            env: None,
+        };
+        return Ok((Kind::Ttyrec, header, Box::new(ttyrec::Frames::new(reader))));
+    }
+
+    let mut text = String::new();
+    reader.read_line(&mut text)?;
+    let (value, on_first_line) = match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(value) => (value, true),
+        Err(_) => {
+            reader.read_to_string(&mut text)?;
+            let value = serde_json::from_str(&text).map_err(|_| InputFailure::HeaderNotFound)?;
+            (value, false)
+        }
+    };
+
+    match value.get("version").and_then(|v| v.as_u64()) {
+        Some(1) => {
+            let (header, events) = asciicast_v1::read(value)?;
+            Ok((Kind::AsciicastV1, header, events))
+        }
+        Some(2) if on_first_line => {
+            let header = serde_json::from_value(value).map_err(|_| InputFailure::HeaderNotFound)?;
+            Ok((
+                Kind::Asciicast,
+                header,
+                Box::new(asciicast::Events::new(reader)),
+            ))
+        }
+        Some(version) if version != 2 => Err(InputFailure::UnsupportedVersion { version }.into()),
+        _ => Err(InputFailure::HeaderNotFound.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(cast: &str) -> Result<(Header, Vec<Event>), Error> {
+        let (header, events) =
+            read_recording(Cursor::new(cast.to_string()), headerless_size(None, None))?;
+        Ok((header, events.collect::<Result<_, _>>()?))
+    }
+
+    #[test]
+    fn reads_v2() {
+        let (header, events) = read(
+            "{\"version\": 2, \"width\": 80, \"height\": 24}\n\
+             [0.5, \"o\", \"a\"]\n",
+        )
+        .unwrap();
+        assert_eq!(header.version, 2);
+        assert_eq!(events, vec![Event::Output(0.5, "a".to_string())]);
+    }
+
+    #[test]
+    fn reads_v1_on_one_line() {
+        let (header, events) =
+            read("{\"version\": 1, \"width\": 80, \"height\": 24, \"stdout\": [[0.5, \"a\"]]}\n")
+                .unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(events, vec![Event::Output(0.5, "a".to_string())]);
+    }
+
+    #[test]
+    fn reads_v1_over_several_lines() {
+        let (header, events) = read(
+            "{\n  \"version\": 1,\n  \"width\": 80,\n  \"height\": 24,\n  \"stdout\": [\n    \
+             [0.5, \"a\"],\n    [1.0, \"b\"]\n  ]\n}\n",
+        )
+        .unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(0.5, "a".to_string()),
+                Event::Output(1.5, "b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_ttyrec_with_the_given_size() {
+        let mut ttyrec = vec![];
+        ttyrec.extend_from_slice(&1_500_000_000u32.to_le_bytes());
+        ttyrec.extend_from_slice(&0u32.to_le_bytes());
+        ttyrec.extend_from_slice(&2u32.to_le_bytes());
+        ttyrec.extend_from_slice(b"hi");
+        let size = headerless_size(Some(120), None);
+        let (header, events) = read_recording(Cursor::new(ttyrec), size).unwrap();
+        assert_eq!((header.width, header.height), (120, 24));
+        assert_eq!(header.timestamp.map(|t| t.timestamp()), Some(1_500_000_000));
+        let events: Vec<Event> = events.collect::<Result<_, _>>().unwrap();
+        assert_eq!(events, vec![Event::Output(0.0, "hi".to_string())]);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let result = read("{\"version\": 3, \"width\": 80, \"height\": 24}\n");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "unsupported asciicast version 3"
+        );
+    }
+
+    #[test]
+    fn reads_only_v2_as_asciicast() {
+        let v2 = "{\"version\": 2, \"width\": 80, \"height\": 24}\n[0.5, \"o\", \"a\"]\n";
+        assert!(read_asciicast(Cursor::new(v2)).is_ok());
+        let v1 = "{\"version\": 1, \"width\": 80, \"height\": 24, \"stdout\": []}\n";
+        assert_eq!(
+            read_asciicast(Cursor::new(v1)).err().unwrap().to_string(),
+            "unsupported asciicast version 1"
+        );
+        let mut ttyrec = vec![0; 8];
+        ttyrec.extend_from_slice(&2u32.to_le_bytes());
+        ttyrec.extend_from_slice(b"hi");
+        assert_eq!(
+            read_asciicast(Cursor::new(ttyrec))
+                .err()
+                .unwrap()
+                .to_string(),
+            "unsupported format: ttyrec (expected asciicast v2)"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_headers() {
+        for cast in &["", "hello\n", "[0.5, \"o\", \"a\"]\n", "{\"width\": 80}\n"] {
+            assert_eq!(read(cast).unwrap_err().to_string(), "header not found");
+        }
+    }
+}
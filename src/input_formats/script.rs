@@ -0,0 +1,262 @@
+use super::{Event, EventStream};
+use crate::decoder::Utf8Decoder;
+use crate::terminal::{Height, Width};
+use asciicast::Header;
+use chrono::{DateTime, Utc};
+use failure::{Error, Fail};
+use std::collections::HashMap;
+use std::io::{BufRead, Read};
+
+const STARTED: &str = "Script started on ";
+
+#[derive(Debug, Fail)]
+enum ScriptFailure {
+    #[fail(display = "invalid timing on line {}", line)]
+    InvalidTiming { line: usize },
+    #[fail(display = "typescript ends before the timing on line {}", line)]
+    TruncatedTypescript { line: usize },
+}
+
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(text.trim(), "%Y-%m-%d %H:%M:%S%:z")
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Parses the `Script started on TIME [KEY="VALUE" ...]` line of a typescript.
+fn parse_started(line: &str) -> (Option<DateTime<Utc>>, HashMap<String, String>) {
+    let line = line.trim_end();
+    let (time, attributes) = match line.find(" [") {
+        Some(i) if line.ends_with(']') => (&line[..i], &line[i + 2..line.len() - 1]),
+        _ => (line, ""),
+    };
+    let mut parsed = HashMap::new();
+    let mut rest = attributes;
+    while let Some(eq) = rest.find("=\"") {
+        let key = rest[..eq].trim().to_string();
+        let value = &rest[eq + 2..];
+        let end = value.find('"').unwrap_or(value.len());
+        parsed.insert(key, value[..end].to_string());
+        rest = value.get(end + 1..).unwrap_or("");
+    }
+    (parse_time(time), parsed)
+}
+
+/// Reads a typescript and its timing file as written by util-linux `script`,
+/// in the classic (`--timing`) or the advanced (`--log-timing`) format.
+///
+/// The typescript is taken to hold only output, like `--log-out` writes it.
+/// The terminal size comes from the recording if it has it, from `size`
+/// otherwise.
+pub fn read<D, T>(
+    mut typescript: D,
+    timing: T,
+    size: (Width, Height),
+) -> Result<(Header, EventStream), Error>
+where
+    D: BufRead,
+    T: BufRead,
+{
+    let (width, height) = size;
+    let mut header = Header {
+        version: 2,
+        width: width.0,
+        height: height.0,
+        timestamp: None,
+        duration: None,
+        idle_time_limit: None,
+        command: None,
+        title: None,
+        env: None,
+    };
+    let mut env = HashMap::new();
+
+    // The first line is only there for people reading the typescript.
+    let mut first = vec![];
+    typescript.read_until(b'\n', &mut first)?;
+    let mut pending = if first.starts_with(STARTED.as_bytes()) {
+        let (time, attributes) = parse_started(&String::from_utf8_lossy(&first[STARTED.len()..]));
+        header.timestamp = time;
+        for (key, value) in attributes {
+            match key.as_str() {
+                "COLUMNS" => header.width = value.parse().unwrap_or(header.width),
+                "LINES" => header.height = value.parse().unwrap_or(header.height),
+                "COMMAND" => header.command = Some(value),
+                "TERM" => {
+                    env.insert(key, value);
+                }
+                _ => (),
+            }
+        }
+        vec![]
+    } else {
+        first
+    };
+
+    let mut decoder = Utf8Decoder::new();
+    let mut events = vec![];
+    let mut time = 0.0;
+    for (i, line) in timing.lines().enumerate() {
+        let line = line?;
+        let invalid = || ScriptFailure::InvalidTiming { line: i + 1 };
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        // Classic entries are all output, without a type.
+        let (kind, fields) = match fields[0].parse::<f64>() {
+            Ok(_) => ("O", &fields[..]),
+            Err(_) => (fields[0], &fields[1..]),
+        };
+        let delay: f64 = fields
+            .first()
+            .and_then(|d| d.parse().ok())
+            .ok_or_else(invalid)?;
+        time += delay;
+        let argument = fields.get(1).ok_or_else(invalid)?;
+        match kind {
+            "O" => {
+                let len: usize = argument.parse().map_err(|_| invalid())?;
+                let mut data = std::mem::take(&mut pending);
+                let wanted = len.saturating_sub(data.len());
+                (&mut typescript)
+                    .take(wanted as u64)
+                    .read_to_end(&mut data)?;
+                if data.len() < len {
+                    return Err(ScriptFailure::TruncatedTypescript { line: i + 1 }.into());
+                }
+                pending = data.split_off(len);
+                let text = decoder.decode(&data);
+                if !text.is_empty() {
+                    events.push(Event::Output(time, text));
+                }
+            }
+            "S" if *argument == "SIGWINCH" => {
+                let mut size = (None, None);
+                for field in &fields[2..] {
+                    if let Some(rows) = field.strip_prefix("ROWS=") {
+                        size.1 = rows.parse().ok().map(Height);
+                    } else if let Some(cols) = field.strip_prefix("COLS=") {
+                        size.0 = cols.parse().ok().map(Width);
+                    }
+                }
+                match size {
+                    (Some(width), Some(height)) => events.push(Event::Resize(time, width, height)),
+                    _ => return Err(invalid().into()),
+                }
+            }
+            "H" => {
+                let value = fields[2..].join(" ");
+                match *argument {
+                    "START_TIME" => header.timestamp = parse_time(&value).or(header.timestamp),
+                    "COLUMNS" => header.width = value.parse().map_err(|_| invalid())?,
+                    "LINES" => header.height = value.parse().map_err(|_| invalid())?,
+                    "COMMAND" => header.command = Some(value),
+                    "TERM" | "SHELL" => {
+                        env.insert(argument.to_string(), value);
+                    }
+                    "EXIT_CODE" => {
+                        events.push(Event::Exit(time, value.parse().map_err(|_| invalid())?))
+                    }
+                    _ => (),
+                }
+            }
+            // Input is logged to a file of its own, other signals don't
+            // change what the terminal shows.
+            "I" | "S" => (),
+            _ => return Err(invalid().into()),
+        }
+    }
+    let rest = decoder.finish();
+    if !rest.is_empty() {
+        events.push(Event::Output(time, rest));
+    }
+    if !env.is_empty() {
+        header.env = Some(env);
+    }
+
+    let events: Vec<Result<Event, Error>> = events.into_iter().map(Ok).collect();
+    Ok((header, Box::new(events.into_iter())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_formats::headerless_size;
+    use std::io::Cursor;
+
+    const TYPESCRIPT: &str = "Script started on 2017-07-14 02:40:00+00:00 \
+                              [COMMAND=\"ls -l\" TERM=\"xterm\" COLUMNS=\"100\" LINES=\"40\"]\n\
+                              héllo!\n\
+                              Script done on 2017-07-14 02:40:01+00:00 [COMMAND_EXIT_CODE=\"0\"]\n";
+
+    fn read_all(typescript: &str, timing: &str) -> Result<(Header, Vec<Event>), Error> {
+        let (header, events) = read(
+            Cursor::new(typescript.to_string()),
+            Cursor::new(timing.to_string()),
+            headerless_size(None, None),
+        )?;
+        Ok((header, events.collect::<Result<_, _>>()?))
+    }
+
+    #[test]
+    fn reads_classic_timing() {
+        // The split multibyte character is put back together.
+        let (header, events) = read_all(TYPESCRIPT, "0.5 2\n0.25 5\n").unwrap();
+        assert_eq!((header.width, header.height), (100, 40));
+        assert_eq!(header.command, Some("ls -l".to_string()));
+        assert_eq!(header.timestamp.map(|t| t.timestamp()), Some(1_500_000_000));
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(0.5, "h".to_string()),
+                Event::Output(0.75, "éllo!".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_advanced_timing() {
+        let timing = "H 0.000000 START_TIME 2017-07-14 02:40:00+00:00\n\
+                      H 0.000000 COLUMNS 120\n\
+                      H 0.000000 LINES 50\n\
+                      O 0.500000 6\n\
+                      I 0.100000 1\n\
+                      S 0.400000 SIGWINCH ROWS=30 COLS=90\n\
+                      O 0.250000 1\n\
+                      H 0.250000 DURATION 1.500000\n\
+                      H 0.000000 EXIT_CODE 2\n";
+        let (header, events) = read_all(TYPESCRIPT, timing).unwrap();
+        assert_eq!((header.width, header.height), (120, 50));
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(0.5, "héllo".to_string()),
+                Event::Resize(1.0, Width(90), Height(30)),
+                Event::Output(1.25, "!".to_string()),
+                Event::Exit(1.5, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_typescripts_without_started_line() {
+        let (header, events) = read_all("hello", "0.1 5\n").unwrap();
+        assert_eq!((header.width, header.height), (80, 24));
+        assert_eq!(events, vec![Event::Output(0.1, "hello".to_string())]);
+    }
+
+    #[test]
+    fn rejects_bad_timing() {
+        assert_eq!(
+            read_all(TYPESCRIPT, "0.5 2\nX 0.1 1\n")
+                .unwrap_err()
+                .to_string(),
+            "invalid timing on line 2"
+        );
+        assert_eq!(
+            read_all(TYPESCRIPT, "0.5 500\n").unwrap_err().to_string(),
+            "typescript ends before the timing on line 1"
+        );
+    }
+}
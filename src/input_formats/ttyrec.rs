@@ -0,0 +1,164 @@
+use super::Event;
+use crate::decoder::Utf8Decoder;
+use failure::{Error, Fail};
+use std::io::Read;
+
+/// The size of a frame header: seconds, microseconds and data length, all
+/// little-endian 32-bit integers.
+pub const FRAME_HEADER_LEN: usize = 12;
+
+/// The most data a frame is expected to hold, to tell ttyrec from other files.
+const MAX_FRAME_LEN: u32 = 16 * 1024 * 1024;
+
+#[derive(Debug, Fail)]
+enum TtyrecFailure {
+    #[fail(display = "truncated ttyrec frame")]
+    TruncatedFrame,
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+/// Whether `data` looks like the start of a ttyrec recording.
+///
+/// ttyrec has no header or magic bytes, so this goes by whether the first
+/// frame header makes sense.
+pub fn is_ttyrec(data: &[u8]) -> bool {
+    if data.len() < FRAME_HEADER_LEN || data[0] == b'{' {
+        return false;
+    }
+    read_u32(&data[4..8]) < 1_000_000 && read_u32(&data[8..12]) <= MAX_FRAME_LEN
+}
+
+/// Iterates over the frames of a ttyrec recording as output events.
+///
+/// Frames are timed in seconds since the epoch, events are timed from the
+/// first frame.
+pub struct Frames<R> {
+    reader: R,
+    start: Option<f64>,
+    last_time: f64,
+    decoder: Utf8Decoder,
+    done: bool,
+}
+
+impl<R: Read> Frames<R> {
+    pub fn new(reader: R) -> Self {
+        Frames {
+            reader,
+            start: None,
+            last_time: 0.0,
+            decoder: Utf8Decoder::new(),
+            done: false,
+        }
+    }
+
+    /// Reads the next frame, `None` at the end of the recording.
+    fn read_frame(&mut self) -> Result<Option<(f64, Vec<u8>)>, Error> {
+        let mut header = vec![];
+        (&mut self.reader)
+            .take(FRAME_HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        match header.len() {
+            0 => return Ok(None),
+            FRAME_HEADER_LEN => (),
+            _ => return Err(TtyrecFailure::TruncatedFrame.into()),
+        }
+        let time = f64::from(read_u32(&header[0..4])) + f64::from(read_u32(&header[4..8])) / 1e6;
+        let len = read_u32(&header[8..12]);
+        let mut data = vec![];
+        (&mut self.reader)
+            .take(u64::from(len))
+            .read_to_end(&mut data)?;
+        if data.len() != len as usize {
+            return Err(TtyrecFailure::TruncatedFrame.into());
+        }
+        Ok(Some((time, data)))
+    }
+}
+
+impl<R: Read> Iterator for Frames<R> {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let (time, data) = match self.read_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => {
+                    self.done = true;
+                    let rest = self.decoder.finish();
+                    if rest.is_empty() {
+                        return None;
+                    }
+                    return Some(Ok(Event::Output(self.last_time, rest)));
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+            let start = *self.start.get_or_insert(time);
+            // Clocks can go backwards, recordings shouldn't.
+            self.last_time = (time - start).max(self.last_time);
+            // Multibyte characters may be split between frames.
+            let text = self.decoder.decode(&data);
+            if !text.is_empty() {
+                return Some(Ok(Event::Output(self.last_time, text)));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn frame(sec: u32, usec: u32, data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![];
+        frame.extend_from_slice(&sec.to_le_bytes());
+        frame.extend_from_slice(&usec.to_le_bytes());
+        frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        frame.extend_from_slice(data);
+        frame
+    }
+
+    #[test]
+    fn reads_frames_as_output_events() {
+        let data = [
+            frame(1_500_000_000, 250_000, b"a"),
+            frame(1_500_000_001, 0, b"b\xc3"),
+            frame(1_500_000_002, 500_000, b"\xa9"),
+        ]
+        .concat();
+        let events: Vec<Event> = Frames::new(Cursor::new(data))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(0.0, "a".to_string()),
+                Event::Output(0.75, "b".to_string()),
+                Event::Output(2.25, "é".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_truncated_frames() {
+        let mut data = frame(1_500_000_000, 0, b"hello");
+        data.truncate(data.len() - 2);
+        let events: Vec<_> = Frames::new(Cursor::new(data)).collect();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].is_err());
+    }
+
+    #[test]
+    fn detects_ttyrec() {
+        assert!(is_ttyrec(&frame(1_500_000_000, 250_000, b"a")));
+        assert!(!is_ttyrec(b"{\"version\": 2, \"width\": 80}"));
+        assert!(!is_ttyrec(b"hello"));
+    }
+}
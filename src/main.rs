@@ -1,14 +1,22 @@
 mod api;
 mod clock;
 mod commands;
+mod compression;
+mod decoder;
+mod export;
+mod hotkeys;
+mod input_formats;
 mod output_formats;
+mod redact;
 mod session;
 mod settings;
 mod terminal;
 mod uploader;
+mod vt;
 
 use api::Api;
-use commands::record::RecordLocation;
+use commands::record::{RecordLocation, Recording};
+use commands::repair::Repair;
 use failure::Error;
 use settings::install::InstallInfo;
 use settings::{Action, Settings};
@@ -18,8 +26,13 @@ use url::Url;
 enum CommandResult {
     Authenticate(Result<Url, Error>),
     Concatenate(Result<(), Error>),
+    Export(Result<(), Error>),
+    Join(Result<(), Error>),
     Play(Result<(), Error>),
-    Record(Result<RecordLocation, Error>),
+    Poster(Result<(), Error>),
+    Record(Result<Recording, Error>),
+    Repair(Result<Repair, Error>),
+    Snapshot(Result<(), Error>),
     Upload(Result<Url, Error>),
 }
 
@@ -42,13 +55,20 @@ fn main() {
         Action::Concatenate => {
             CommandResult::Concatenate(commands::concatenate::go(&settings.concatenate.unwrap()))
         }
+        Action::Export => CommandResult::Export(commands::export::go(&settings.export.unwrap())),
+        Action::Join => CommandResult::Join(commands::join::go(&settings.join.unwrap())),
         Action::Play => CommandResult::Play(commands::play::go(&settings.play.unwrap())),
+        Action::Poster => CommandResult::Poster(commands::poster::go(&settings.poster.unwrap())),
         Action::Record => CommandResult::Record(commands::record::go(
             &settings.record.unwrap(),
             UploadBuilder::default()
                 .api(api)
                 .install_id(install_info.id),
         )),
+        Action::Repair => CommandResult::Repair(commands::repair::go(&settings.repair.unwrap())),
+        Action::Snapshot => {
+            CommandResult::Snapshot(commands::snapshot::go(&settings.snapshot.unwrap()))
+        }
         Action::Upload => CommandResult::Upload(commands::upload::go(
             &settings.upload.unwrap(),
             UploadBuilder::default()
@@ -73,19 +93,45 @@ fn main() {
             ),
             Err(x) => handle_error(&x),
         },
-        CommandResult::Concatenate(x) | CommandResult::Play(x) => match x {
+        CommandResult::Concatenate(x)
+        | CommandResult::Export(x)
+        | CommandResult::Join(x)
+        | CommandResult::Play(x)
+        | CommandResult::Poster(x)
+        | CommandResult::Snapshot(x) => match x {
             Ok(()) => 0,
             Err(x) => handle_error(&x),
         },
         CommandResult::Record(x) => match x {
-            Ok(location) => {
-                let location_output = match location {
-                    RecordLocation::Local(f) => {
-                        format!("asciicast saved to: {}", f.to_string_lossy())
-                    }
+            Ok(recording) => {
+                let location_output = match recording.location {
+                    RecordLocation::Local(files) => files
+                        .iter()
+                        .map(|f| format!("asciicast saved to: {}", f.to_string_lossy()))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
                     RecordLocation::Remote(url) => format!("{}", url),
                 };
-                handle_output(location_output.as_str())
+                handle_output(location_output.as_str());
+                // Exit like the recorded command did, e.g. to fail CI jobs.
+                recording.exit_code
+            }
+            Err(x) => handle_error(&x),
+        },
+        CommandResult::Repair(x) => match x {
+            Ok(repair) => {
+                let file = repair.file.to_string_lossy();
+                let report = if repair.changes.is_empty() {
+                    format!("{}: nothing to repair", file)
+                } else {
+                    repair
+                        .changes
+                        .iter()
+                        .map(|change| format!("{}: {}", file, change))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                handle_output(report.as_str())
             }
             Err(x) => handle_error(&x),
         },
@@ -1,19 +1,41 @@
-use super::{LoopAction, Msg, Output};
+use super::{LoopAction, Msg, Output, Sink};
 use failure::Error;
-use std::fs::File;
-use std::io::Write;
 use std::sync::mpsc::{channel, Receiver, Sender};
 
 pub struct AsciicastOutput {
     rx: Receiver<Msg>,
     tx: Sender<Msg>,
-    output: File,
+    output: Sink,
+    exit_event: bool,
 }
 
 impl AsciicastOutput {
-    pub fn new(output: File) -> Self {
+    pub fn new(output: Sink) -> Self {
         let (tx, rx) = channel();
-        AsciicastOutput { rx, tx, output }
+        AsciicastOutput {
+            rx,
+            tx,
+            output,
+            exit_event: false,
+        }
+    }
+
+    /// Like `new`, but also writes the recorded command's exit status as an
+    /// `"x"` event, which isn't part of asciicast v2.
+    pub fn with_exit_event(output: Sink) -> Self {
+        AsciicastOutput {
+            exit_event: true,
+            ..Self::new(output)
+        }
+    }
+
+    /// Writes one line of the recording.
+    ///
+    /// Lines are written whole with a single write, so a crash doesn't leave
+    /// half an event behind unless the write itself is cut short.
+    fn write_line(&self, json: String) -> Result<LoopAction, Error> {
+        self.output.write((json + "\n").as_bytes())?;
+        Ok(LoopAction::Continue)
     }
 }
 
@@ -24,29 +46,49 @@ impl Output for AsciicastOutput {
     fn rx(&self) -> &Receiver<Msg> {
         &self.rx
     }
+    fn tick(&self) -> Result<(), Error> {
+        self.output.tick()
+    }
     fn handle_message(&self, message: Msg) -> Result<LoopAction, Error> {
-        match message {
-            Msg::Finish => Ok(LoopAction::Stop),
-            Msg::Header(d) => {
-                let mut file_copy = self.output.try_clone()?;
-                let j = serde_json::to_string(&d)? + "\n";
-                file_copy.write_all(j.as_bytes())?;
-                Ok(LoopAction::Continue)
+        if let Msg::Exit(..) = message {
+            if !self.exit_event {
+                return Ok(LoopAction::Continue);
             }
-            Msg::Input(d) | Msg::Output(d) => {
-                let mut file_copy = self.output.try_clone()?;
-                let j = serde_json::to_string(&d)? + "\n";
-                file_copy.write_all(j.as_bytes())?;
-                Ok(LoopAction::Continue)
+        }
+        match to_line(&message)? {
+            Some(line) => self.write_line(line),
+            None => {
+                self.output.finish()?;
+                Ok(LoopAction::Stop)
             }
-            #[cfg(test)]
-            Msg::MockError => unimplemented!(),
         }
     }
 }
+
+/// Returns the asciicast line for `message`, or `None` for `Finish`.
+///
+/// `Exit` is written as an `"x"` event, callers leave it out unless asked
+/// to write it.
+pub fn to_line(message: &Msg) -> Result<Option<String>, Error> {
+    Ok(Some(match *message {
+        Msg::Finish => return Ok(None),
+        Msg::Header(ref d) => serde_json::to_string(d)?,
+        Msg::Input(ref d) | Msg::Output(ref d) => serde_json::to_string(d)?,
+        Msg::Resize(time, width, height) => {
+            let size = format!("{}x{}", width.0, height.0);
+            serde_json::to_string(&(time, "r", size))?
+        }
+        Msg::Marker(time, ref label) => serde_json::to_string(&(time, "m", label))?,
+        Msg::Exit(time, code) => serde_json::to_string(&(time, "x", code.to_string()))?,
+        #[cfg(test)]
+        Msg::MockError => unimplemented!(),
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::terminal::{Height, Width};
     use asciicast::{Entry, EventType, Header};
     use std::boxed::Box;
 
@@ -105,4 +147,49 @@ mod tests {
             "[5.0,\"o\",\"Hello world\"]".to_string()
         );
     }
+
+    #[test]
+    fn test_writes_resize_event() {
+        let line =
+            first_line_for_message!(AsciicastOutput, Msg::Resize(7.5, Width(80), Height(24)));
+        assert!(line.is_some());
+        assert_eq!(
+            line.expect("a line").unwrap(),
+            "[7.5,\"r\",\"80x24\"]".to_string()
+        );
+    }
+
+    #[test]
+    fn test_writes_marker_event() {
+        let line =
+            first_line_for_message!(AsciicastOutput, Msg::Marker(12.5, "Chapter 1".to_string()));
+        assert!(line.is_some());
+        assert_eq!(
+            line.expect("a line").unwrap(),
+            "[12.5,\"m\",\"Chapter 1\"]".to_string()
+        );
+    }
+
+    #[test]
+    fn test_skips_exit_event() {
+        let line = first_line_for_message!(AsciicastOutput, Msg::Exit(3.5, 1));
+        assert!(line.is_none());
+    }
+
+    #[test]
+    fn test_writes_exit_event_if_asked_to() {
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        let sink = Sink::new(tmpfile.reopen().unwrap(), None, Default::default()).unwrap();
+        let output = AsciicastOutput::with_exit_event(sink);
+        let channel = output.channel();
+        let thread_handle = output.spawn();
+        channel.send(Msg::Exit(3.5, 1)).unwrap();
+        channel.send(Msg::Finish).unwrap();
+        thread_handle.join().expect("join thread").unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(tmpfile.path()).unwrap(),
+            "[3.5,\"x\",\"1\"]\n"
+        );
+    }
 }
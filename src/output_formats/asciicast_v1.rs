@@ -0,0 +1,90 @@
+use super::{LoopAction, Msg, Output, Sink};
+use crate::input_formats::asciicast_v1::Asciicast;
+use failure::Error;
+use std::cell::{Cell, RefCell};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Writes asciicast v1, for players and tools that don't know v2.
+///
+/// v1 is a single document, so nothing is written until the recording is
+/// finished. Only output makes it into v1, other events are left out.
+pub struct AsciicastV1Output {
+    rx: Receiver<Msg>,
+    tx: Sender<Msg>,
+    output: Sink,
+    cast: RefCell<Option<Asciicast>>,
+    // When the last frame and the last event of any kind happened.
+    last_frame: Cell<f64>,
+    last_event: Cell<f64>,
+}
+
+impl AsciicastV1Output {
+    pub fn new(output: Sink) -> Self {
+        let (tx, rx) = channel();
+        AsciicastV1Output {
+            rx,
+            tx,
+            output,
+            cast: RefCell::new(None),
+            last_frame: Cell::new(0.0),
+            last_event: Cell::new(0.0),
+        }
+    }
+}
+
+impl Output for AsciicastV1Output {
+    fn channel(&self) -> Sender<Msg> {
+        self.tx.clone()
+    }
+    fn rx(&self) -> &Receiver<Msg> {
+        &self.rx
+    }
+    fn tick(&self) -> Result<(), Error> {
+        self.output.tick()
+    }
+    fn handle_message(&self, message: Msg) -> Result<LoopAction, Error> {
+        if let Some(time) = message.time() {
+            self.last_event.set(time);
+        }
+        let mut cast = self.cast.borrow_mut();
+        match message {
+            Msg::Header(header) => {
+                *cast = Some(Asciicast {
+                    version: 1,
+                    width: header.width,
+                    height: header.height,
+                    duration: None,
+                    command: header.command,
+                    title: header.title,
+                    env: header
+                        .env
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|(key, value)| (key, Some(value)))
+                        .collect(),
+                    stdout: vec![],
+                });
+            }
+            Msg::Output(entry) => {
+                if let Some(ref mut c) = *cast {
+                    let delay = entry.time - self.last_frame.get();
+                    self.last_frame.set(entry.time);
+                    c.stdout.push((delay, entry.event_data));
+                }
+            }
+            Msg::Input(..) | Msg::Resize(..) | Msg::Marker(..) | Msg::Exit(..) => (),
+            Msg::Finish => {
+                if let Some(mut c) = cast.take() {
+                    c.duration = Some(self.last_event.get());
+                    self.output
+                        .write((serde_json::to_string(&c)? + "\n").as_bytes())?;
+                }
+                self.output.finish()?;
+                return Ok(LoopAction::Stop);
+            }
+            #[cfg(test)]
+            Msg::MockError => unimplemented!(),
+        }
+        Ok(LoopAction::Continue)
+    }
+}
@@ -1,40 +1,358 @@
+use crate::compression::{Compression, Encoder};
+use crate::terminal::{Height, Width};
 use ::asciicast as asciicast_format;
-use failure::Error;
-use std::sync::mpsc::{Receiver, Sender};
+use failure::{Error, Fail};
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::thread;
+use std::time::{Duration, Instant};
 
 #[cfg(test)]
 #[macro_use]
 pub mod test_helpers;
 pub mod asciicast;
+pub mod asciicast_v1;
 pub mod raw;
+pub mod script;
+pub mod segmented;
+pub mod transcript;
+pub mod ttyrec;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Msg {
     Header(Box<asciicast_format::Header>),
     Input(Box<asciicast_format::Entry>),
     Output(Box<asciicast_format::Entry>),
+    Resize(f64, Width, Height),
+    Marker(f64, String),
+    Exit(f64, i32),
     Finish,
     #[cfg(test)]
     MockError,
 }
 
+impl Msg {
+    /// When the event happened, for messages that are events.
+    pub fn time(&self) -> Option<f64> {
+        match *self {
+            Msg::Input(ref e) | Msg::Output(ref e) => Some(e.time),
+            Msg::Resize(time, ..) | Msg::Marker(time, _) | Msg::Exit(time, _) => Some(time),
+            _ => None,
+        }
+    }
+
+    /// Returns the message with its time moved back by `seconds`.
+    pub fn rebased(self, seconds: f64) -> Msg {
+        match self {
+            Msg::Input(mut e) => {
+                e.time -= seconds;
+                Msg::Input(e)
+            }
+            Msg::Output(mut e) => {
+                e.time -= seconds;
+                Msg::Output(e)
+            }
+            Msg::Resize(time, width, height) => Msg::Resize(time - seconds, width, height),
+            Msg::Marker(time, label) => Msg::Marker(time - seconds, label),
+            Msg::Exit(time, code) => Msg::Exit(time - seconds, code),
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Fail)]
+enum FormatFailure {
+    #[fail(
+        display = "unknown output format: {} (expected asciicast, asciicast-v1, raw, script, script-advanced, transcript or ttyrec)",
+        name
+    )]
+    Unknown { name: String },
+    #[fail(
+        display = "unknown durability: {} (expected flush, fsync or fsync:SECONDS)",
+        mode
+    )]
+    UnknownDurability { mode: String },
+    #[fail(display = "output already finished")]
+    Finished,
+}
+
+/// A started output: where to send it messages, and its thread.
+pub type Spawned = (Sender<Msg>, thread::JoinHandle<Result<(), Error>>);
+
+/// The output formats a recording can be written in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Asciicast,
+    AsciicastV1,
+    Raw,
+    Script(script::Timing),
+    Transcript,
+    Ttyrec,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asciicast" => Ok(Format::Asciicast),
+            "asciicast-v1" => Ok(Format::AsciicastV1),
+            "raw" => Ok(Format::Raw),
+            "script" => Ok(Format::Script(script::Timing::Classic)),
+            "script-advanced" => Ok(Format::Script(script::Timing::Advanced)),
+            "transcript" => Ok(Format::Transcript),
+            "ttyrec" => Ok(Format::Ttyrec),
+            _ => Err(FormatFailure::Unknown {
+                name: s.to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+impl Format {
+    /// Returns the files this format writes when saving to `path`.
+    pub fn paths(self, path: &Path) -> Vec<PathBuf> {
+        match self {
+            Format::Script(_) => vec![path.to_path_buf(), script::timing_path(path)],
+            _ => vec![path.to_path_buf()],
+        }
+    }
+
+    /// Starts a thread writing this format to `path`, opening each of its
+    /// files with `open`. An asciicast gets an exit event if `exit_event`,
+    /// see `AsciicastOutput::with_exit_event`.
+    pub fn spawn<F>(self, path: &Path, exit_event: bool, open: F) -> Result<Spawned, Error>
+    where
+        F: Fn(&Path) -> Result<Sink, Error>,
+    {
+        Ok(match self {
+            Format::Asciicast if exit_event => {
+                let output = asciicast::AsciicastOutput::with_exit_event(open(path)?);
+                (output.channel(), output.spawn())
+            }
+            Format::Asciicast => {
+                let output = asciicast::AsciicastOutput::new(open(path)?);
+                (output.channel(), output.spawn())
+            }
+            Format::AsciicastV1 => {
+                let output = asciicast_v1::AsciicastV1Output::new(open(path)?);
+                (output.channel(), output.spawn())
+            }
+            Format::Raw => {
+                let output = raw::RawOutput::new(open(path)?);
+                (output.channel(), output.spawn())
+            }
+            Format::Script(timing) => {
+                let timing_sink = open(&script::timing_path(path))?;
+                let output = script::ScriptOutput::new(open(path)?, timing_sink, timing);
+                (output.channel(), output.spawn())
+            }
+            Format::Transcript => {
+                let output = transcript::TranscriptOutput::new(open(path)?);
+                (output.channel(), output.spawn())
+            }
+            Format::Ttyrec => {
+                let output = ttyrec::TtyrecOutput::new(open(path)?);
+                (output.channel(), output.spawn())
+            }
+        })
+    }
+}
+
+/// How hard outputs try to get events onto disk while recording.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Durability {
+    /// Write every event to the file as it happens. Nothing is buffered, so
+    /// asciinema crashing loses nothing, but the system crashing may.
+    #[default]
+    Flush,
+    /// Also sync the file to disk after an event, at most once per interval
+    /// and at the latest once it is over.
+    Fsync(Duration),
+}
+
+impl FromStr for Durability {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || FormatFailure::UnknownDurability {
+            mode: s.to_string(),
+        };
+        match s {
+            "flush" => Ok(Durability::Flush),
+            "fsync" => Ok(Durability::Fsync(Duration::from_secs(0))),
+            _ if s.starts_with("fsync:") => {
+                let secs: f64 = s["fsync:".len()..].parse().map_err(|_| unknown())?;
+                if !secs.is_finite() || secs < 0.0 {
+                    return Err(unknown().into());
+                }
+                Ok(Durability::Fsync(Duration::from_secs_f64(secs)))
+            }
+            _ => Err(unknown().into()),
+        }
+    }
+}
+
+/// Syncs an output's file to disk as its `Durability` asks for.
+struct Syncer {
+    durability: Durability,
+    last_sync: Cell<Option<Instant>>,
+    // Whether anything was written since the last sync.
+    unsynced: Cell<bool>,
+}
+
+impl Syncer {
+    fn new(durability: Durability) -> Self {
+        Syncer {
+            durability,
+            last_sync: Cell::new(None),
+            unsynced: Cell::new(false),
+        }
+    }
+
+    /// Whether what was written should be synced now.
+    fn is_due(&self) -> bool {
+        match self.durability {
+            Durability::Fsync(interval) if self.unsynced.get() => match self.last_sync.get() {
+                Some(last) => last.elapsed() >= interval,
+                None => true,
+            },
+            _ => false,
+        }
+    }
+
+    /// Called after an event was written.
+    fn written(&self) {
+        self.unsynced.set(true);
+    }
+
+    fn sync(&self, file: &File) -> io::Result<()> {
+        file.sync_data()?;
+        self.last_sync.set(Some(Instant::now()));
+        self.unsynced.set(false);
+        Ok(())
+    }
+
+    /// Called once the recording is finished, to sync what the interval held back.
+    fn finish(&self, file: &File) -> io::Result<()> {
+        if let Durability::Fsync(_) = self.durability {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// The file an output writes to, compressed and synced as asked for.
+pub struct Sink {
+    // Taken out once the compressed stream is finished.
+    encoder: RefCell<Option<Encoder>>,
+    syncer: Syncer,
+    // What was given to a compressed encoder since it was last flushed.
+    unflushed: Cell<u64>,
+}
+
+impl Sink {
+    pub fn new(
+        file: File,
+        compression: Option<Compression>,
+        durability: Durability,
+    ) -> io::Result<Self> {
+        Ok(Sink {
+            encoder: RefCell::new(Some(Encoder::new(file, compression)?)),
+            syncer: Syncer::new(durability),
+            unflushed: Cell::new(0),
+        })
+    }
+
+    /// Writes an event to the file, see `Encoder::write` for when compressed
+    /// events get there.
+    pub fn write(&self, data: &[u8]) -> Result<(), Error> {
+        let mut encoder = self.encoder.borrow_mut();
+        let encoder = encoder.as_mut().ok_or(FormatFailure::Finished)?;
+        encoder.write(data)?;
+        if !matches!(*encoder, Encoder::Plain(_)) {
+            self.unflushed.set(self.unflushed.get() + data.len() as u64);
+        }
+        self.syncer.written();
+        self.sync_if_due(encoder)
+    }
+
+    /// Syncs the file if events are waiting for it, see `Output::tick`.
+    pub fn tick(&self) -> Result<(), Error> {
+        match *self.encoder.borrow_mut() {
+            Some(ref mut encoder) => self.sync_if_due(encoder),
+            None => Ok(()),
+        }
+    }
+
+    /// Syncs what the interval held back once it is over. Compressed events
+    /// are flushed along with it, so they reach the file with every sync
+    /// rather than with every event.
+    fn sync_if_due(&self, encoder: &mut Encoder) -> Result<(), Error> {
+        if self.syncer.is_due() {
+            encoder.flush()?;
+            self.unflushed.set(0);
+            self.syncer.sync(encoder.file())?;
+        }
+        Ok(())
+    }
+
+    /// How big the file has grown, compressed or not. Compressed events
+    /// that weren't flushed yet count as big as they are uncompressed, which
+    /// they won't be once they are.
+    pub fn size(&self) -> Result<u64, Error> {
+        let encoder = self.encoder.borrow();
+        let encoder = encoder.as_ref().ok_or(FormatFailure::Finished)?;
+        Ok(encoder.file().metadata()?.len() + self.unflushed.get())
+    }
+
+    /// Finishes the file once nothing more is written to it.
+    pub fn finish(&self) -> Result<(), Error> {
+        if let Some(encoder) = self.encoder.borrow_mut().take() {
+            let file = encoder.finish()?;
+            self.syncer.finish(&file)?;
+        }
+        Ok(())
+    }
+}
+
 pub enum LoopAction {
     Stop,
     Continue,
 }
 
+/// How long an output waits for a message before it ticks.
+const TICK: Duration = Duration::from_millis(100);
+
 pub trait Output {
     fn channel(&self) -> Sender<Msg>;
     fn handle_message(&self, message: Msg) -> Result<LoopAction, Error>;
     fn rx(&self) -> &Receiver<Msg>;
+    /// Called when no message came for a while, e.g. to sync events to disk
+    /// that were held back until the durability interval was over.
+    fn tick(&self) -> Result<(), Error> {
+        Ok(())
+    }
     fn spawn(self) -> thread::JoinHandle<Result<(), Error>>
     where
         Self: Sized + Send + 'static,
     {
         thread::spawn(move || {
             let rx = self.rx();
-            for message in rx.iter() {
+            loop {
+                let message = match rx.recv_timeout(TICK) {
+                    Ok(message) => message,
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.tick()?;
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
                 match self.handle_message(message)? {
                     LoopAction::Continue => (),
                     LoopAction::Stop => break,
@@ -48,6 +366,8 @@ pub trait Output {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::input_formats::{self, headerless_size, read_recording, Event};
+    use asciicast_format::EventType;
     use failure::{Error, Fail};
     use std::sync::mpsc::channel;
 
@@ -78,6 +398,239 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_formats() {
+        assert_eq!("asciicast".parse::<Format>().unwrap(), Format::Asciicast);
+        assert_eq!(
+            "asciicast-v1".parse::<Format>().unwrap(),
+            Format::AsciicastV1
+        );
+        assert_eq!("raw".parse::<Format>().unwrap(), Format::Raw);
+        assert_eq!("ttyrec".parse::<Format>().unwrap(), Format::Ttyrec);
+        assert_eq!("transcript".parse::<Format>().unwrap(), Format::Transcript);
+        assert_eq!(
+            "script-advanced".parse::<Format>().unwrap(),
+            Format::Script(script::Timing::Advanced)
+        );
+        assert!("gif".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn parses_durabilities() {
+        assert_eq!("flush".parse::<Durability>().unwrap(), Durability::Flush);
+        assert_eq!(
+            "fsync".parse::<Durability>().unwrap(),
+            Durability::Fsync(Duration::from_secs(0))
+        );
+        assert_eq!(
+            "fsync:0.5".parse::<Durability>().unwrap(),
+            Durability::Fsync(Duration::from_millis(500))
+        );
+        assert!("fsync:".parse::<Durability>().is_err());
+        assert!("fsync:-1".parse::<Durability>().is_err());
+        assert!("sync".parse::<Durability>().is_err());
+    }
+
+    #[test]
+    fn syncs_held_back_events_once_the_interval_is_over() {
+        let syncer = Syncer::new(Durability::Fsync(Duration::from_millis(50)));
+        assert!(!syncer.is_due());
+        syncer.written();
+        assert!(syncer.is_due());
+        syncer.sync(&tempfile::tempfile().unwrap()).unwrap();
+        // Too soon after the first sync.
+        syncer.written();
+        assert!(!syncer.is_due());
+        thread::sleep(Duration::from_millis(60));
+        assert!(syncer.is_due());
+    }
+
+    #[test]
+    fn flush_never_syncs() {
+        let syncer = Syncer::new(Durability::Flush);
+        syncer.written();
+        assert!(!syncer.is_due());
+    }
+
+    #[test]
+    fn flushes_compressed_events_with_syncs() {
+        let file = tempfile::tempfile().unwrap();
+        let size = || file.metadata().unwrap().len();
+        let sink = Sink::new(
+            file.try_clone().unwrap(),
+            Some(Compression::Gzip),
+            Durability::Fsync(Duration::from_millis(50)),
+        )
+        .unwrap();
+        sink.write(b"first\n").unwrap();
+        let synced = size();
+        sink.write(b"second\n").unwrap();
+        sink.tick().unwrap();
+        assert_eq!(size(), synced);
+        // Held back events count towards the size as they are.
+        assert_eq!(sink.size().unwrap(), synced + 7);
+        thread::sleep(Duration::from_millis(60));
+        sink.tick().unwrap();
+        assert!(size() > synced);
+    }
+
+    /// Records `messages` in `format` to `path`.
+    fn record(format: Format, messages: Vec<Msg>, path: &Path) {
+        let (channel, thread_handle) = format
+            .spawn(path, false, |p| {
+                Ok(Sink::new(File::create(p)?, None, Durability::default())?)
+            })
+            .unwrap();
+        for message in messages {
+            channel.send(message).unwrap();
+        }
+        channel.send(Msg::Finish).unwrap();
+        thread_handle.join().expect("thread join").unwrap();
+    }
+
+    /// Records `messages` in `format`, then reads the recording back.
+    fn round_trip(format: Format, messages: Vec<Msg>) -> (asciicast_format::Header, Vec<Event>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording");
+        record(format, messages, &path);
+
+        let open = |p: &Path| std::io::BufReader::new(File::open(p).unwrap());
+        let size = headerless_size(Some(100), Some(40));
+        let (header, events) = match format {
+            Format::Script(_) => {
+                input_formats::script::read(open(&path), open(&script::timing_path(&path)), size)
+            }
+            _ => read_recording(open(&path), size),
+        }
+        .unwrap();
+        (header, events.collect::<Result<_, _>>().unwrap())
+    }
+
+    fn recording() -> Vec<Msg> {
+        let entry = |time, event_type, data: &str| {
+            Box::new(asciicast_format::Entry {
+                time,
+                event_type,
+                event_data: data.to_string(),
+            })
+        };
+        vec![
+            Msg::Header(Box::new(asciicast_format::Header {
+                version: 2,
+                width: 80,
+                height: 24,
+                timestamp: None,
+                duration: None,
+                idle_time_limit: None,
+                command: Some("make".to_string()),
+                title: Some("demo".to_string()),
+                env: None,
+            })),
+            Msg::Output(entry(0.5, EventType::Output, "a")),
+            Msg::Input(entry(0.75, EventType::Input, "q")),
+            Msg::Resize(1.0, Width(100), Height(40)),
+            Msg::Marker(1.25, "intro".to_string()),
+            Msg::Output(entry(1.5, EventType::Output, "b\r\n")),
+            Msg::Exit(2.0, 3),
+        ]
+    }
+
+    #[test]
+    fn round_trips_asciicast() {
+        let (header, events) = round_trip(Format::Asciicast, recording());
+        assert_eq!((header.version, header.width, header.height), (2, 80, 24));
+        assert_eq!(header.title, Some("demo".to_string()));
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(0.5, "a".to_string()),
+                Event::Input(0.75, "q".to_string()),
+                Event::Resize(1.0, Width(100), Height(40)),
+                Event::Marker(1.25, "intro".to_string()),
+                Event::Output(1.5, "b\r\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_asciicast_v1() {
+        let (header, events) = round_trip(Format::AsciicastV1, recording());
+        assert_eq!((header.version, header.width, header.height), (1, 80, 24));
+        assert_eq!(header.command, Some("make".to_string()));
+        assert_eq!(header.title, Some("demo".to_string()));
+        assert_eq!(header.duration, Some(2.0));
+        // v1 only has output.
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(0.5, "a".to_string()),
+                Event::Output(1.5, "b\r\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_ttyrec() {
+        let (header, events) = round_trip(Format::Ttyrec, recording());
+        // ttyrec has no header, the size comes from the reader.
+        assert_eq!((header.width, header.height), (100, 40));
+        assert!(header.timestamp.is_some());
+        // ttyrec only has output, timed from the first frame.
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(0.0, "a".to_string()),
+                Event::Output(1.0, "b\r\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_script() {
+        let (header, events) = round_trip(Format::Script(script::Timing::Classic), recording());
+        assert_eq!((header.width, header.height), (80, 24));
+        assert_eq!(header.command, Some("make".to_string()));
+        // Classic timing only has output.
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(0.5, "a".to_string()),
+                Event::Output(1.5, "b\r\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_script_advanced() {
+        let (header, events) = round_trip(Format::Script(script::Timing::Advanced), recording());
+        assert_eq!((header.width, header.height), (80, 24));
+        assert_eq!(header.command, Some("make".to_string()));
+        assert_eq!(
+            events,
+            vec![
+                Event::Output(0.5, "a".to_string()),
+                Event::Resize(1.0, Width(100), Height(40)),
+                Event::Output(1.5, "b\r\n".to_string()),
+                Event::Exit(2.0, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_transcript() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript");
+        let mut messages = recording();
+        messages.push(Msg::Output(Box::new(asciicast_format::Entry {
+            time: 2.5,
+            event_type: EventType::Output,
+            event_data: "\x1b[32m50%\x1b[0m\r100%\r\n$ ".to_string(),
+        })));
+        record(Format::Transcript, messages, &path);
+        // No input, and only the last state of the progress.
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "ab\n100%\n$\n");
+    }
+
     #[test]
     fn thread_stops_on_finish_message() {
         let (tx, rx) = channel();
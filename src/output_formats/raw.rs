@@ -1,17 +1,15 @@
-use super::{LoopAction, Msg, Output};
+use super::{LoopAction, Msg, Output, Sink};
 use failure::Error;
-use std::fs::File;
-use std::io::Write;
 use std::sync::mpsc::{channel, Receiver, Sender};
 
 pub struct RawOutput {
     rx: Receiver<Msg>,
     tx: Sender<Msg>,
-    output: File,
+    output: Sink,
 }
 
 impl RawOutput {
-    pub fn new(output: File) -> Self {
+    pub fn new(output: Sink) -> Self {
         let (tx, rx) = channel();
         RawOutput { rx, tx, output }
     }
@@ -24,17 +22,26 @@ impl Output for RawOutput {
     fn rx(&self) -> &Receiver<Msg> {
         &self.rx
     }
+    fn tick(&self) -> Result<(), Error> {
+        self.output.tick()
+    }
     fn handle_message(&self, message: Msg) -> Result<LoopAction, Error> {
         match message {
-            Msg::Finish => Ok(LoopAction::Stop),
+            Msg::Finish => {
+                self.output.finish()?;
+                Ok(LoopAction::Stop)
+            }
             Msg::Header(_) => {
                 // Raw doesn't write header.
                 Ok(LoopAction::Continue)
             }
+            Msg::Resize(..) | Msg::Marker(..) | Msg::Exit(..) => {
+                // Raw doesn't write resizes, markers or exits either, they aren't terminal output.
+                Ok(LoopAction::Continue)
+            }
             Msg::Input(entry) | Msg::Output(entry) => {
-                let mut file_copy = self.output.try_clone()?;
                 let data = (*entry).event_data;
-                file_copy.write_all(data.as_bytes())?;
+                self.output.write(data.as_bytes())?;
                 Ok(LoopAction::Continue)
             }
             #[cfg(test)]
@@ -46,6 +53,7 @@ impl Output for RawOutput {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::terminal::{Height, Width};
     use asciicast::{Entry, EventType, Header};
     use std::boxed::Box;
 
@@ -67,6 +75,24 @@ mod tests {
         assert!(line.is_none());
     }
 
+    #[test]
+    fn test_does_not_write_resize() {
+        let line = first_line_for_message!(RawOutput, Msg::Resize(1.0, Width(80), Height(24)));
+        assert!(line.is_none());
+    }
+
+    #[test]
+    fn test_does_not_write_marker() {
+        let line = first_line_for_message!(RawOutput, Msg::Marker(1.0, "label".to_string()));
+        assert!(line.is_none());
+    }
+
+    #[test]
+    fn test_does_not_write_exit() {
+        let line = first_line_for_message!(RawOutput, Msg::Exit(1.0, 0));
+        assert!(line.is_none());
+    }
+
     #[test]
     fn test_writes_input_event() {
         let entry = Entry {
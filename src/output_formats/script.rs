@@ -0,0 +1,275 @@
+use super::{LoopAction, Msg, Output, Sink};
+use chrono::{DateTime, Utc};
+use failure::Error;
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// The timing file formats of util-linux `script`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Timing {
+    /// `DELAY BYTES` lines, as written by `script --timing`.
+    Classic,
+    /// Lines typed by their first field, as written by `script --log-timing`.
+    /// Besides output they hold resizes and information about the session.
+    Advanced,
+}
+
+/// Returns where the timing file of the typescript at `path` is written.
+pub fn timing_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".timing");
+    PathBuf::from(path)
+}
+
+/// Formats a time like `script` does in typescripts and timing files.
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S%:z").to_string()
+}
+
+/// Writes a typescript and its timing file, for `scriptreplay` and friends.
+///
+/// Only output goes into the typescript, like `script --log-out` does, so
+/// input and markers are left out.
+pub struct ScriptOutput {
+    rx: Receiver<Msg>,
+    tx: Sender<Msg>,
+    output: Sink,
+    timing_output: Sink,
+    timing: Timing,
+    start: Cell<DateTime<Utc>>,
+    // When the last timing entry and the last event of any kind happened.
+    last_entry: Cell<f64>,
+    last_event: Cell<f64>,
+    exit_code: Cell<Option<i32>>,
+}
+
+impl ScriptOutput {
+    pub fn new(output: Sink, timing_output: Sink, timing: Timing) -> Self {
+        let (tx, rx) = channel();
+        ScriptOutput {
+            rx,
+            tx,
+            output,
+            timing_output,
+            timing,
+            start: Cell::new(Utc::now()),
+            last_entry: Cell::new(0.0),
+            last_event: Cell::new(0.0),
+            exit_code: Cell::new(None),
+        }
+    }
+
+    /// Writes a timing entry of `kind` at `time`, timed from the entry before.
+    fn write_entry(&self, time: f64, kind: char, entry: &str) -> Result<(), Error> {
+        let delay = (time - self.last_entry.get()).max(0.0);
+        self.last_entry.set(time);
+        let line = match self.timing {
+            Timing::Classic => format!("{:.6} {}\n", delay, entry),
+            Timing::Advanced => format!("{} {:.6} {}\n", kind, delay, entry),
+        };
+        self.timing_output.write(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes session information, which only the advanced format has room for.
+    fn write_info(&self, time: f64, name: &str, value: &str) -> Result<(), Error> {
+        match self.timing {
+            Timing::Classic => Ok(()),
+            Timing::Advanced => self.write_entry(time, 'H', &format!("{} {}", name, value)),
+        }
+    }
+
+    fn finish(&self) -> Result<(), Error> {
+        let time = self.last_event.get();
+        let done = self.start.get() + chrono::Duration::microseconds((time * 1e6) as i64);
+        let status = match self.exit_code.get() {
+            Some(code) => format!(" [COMMAND_EXIT_CODE=\"{}\"]", code),
+            None => String::new(),
+        };
+        self.output
+            .write(format!("\nScript done on {}{}\n", format_time(done), status).as_bytes())?;
+        self.write_info(time, "DURATION", &format!("{:.6}", time))?;
+        if let Some(code) = self.exit_code.get() {
+            self.write_info(time, "EXIT_CODE", &code.to_string())?;
+        }
+        self.output.finish()?;
+        self.timing_output.finish()
+    }
+}
+
+impl Output for ScriptOutput {
+    fn channel(&self) -> Sender<Msg> {
+        self.tx.clone()
+    }
+    fn rx(&self) -> &Receiver<Msg> {
+        &self.rx
+    }
+    fn tick(&self) -> Result<(), Error> {
+        self.output.tick()?;
+        self.timing_output.tick()
+    }
+    fn handle_message(&self, message: Msg) -> Result<LoopAction, Error> {
+        if let Some(time) = message.time() {
+            self.last_event.set(time);
+        }
+        match message {
+            Msg::Finish => {
+                self.finish()?;
+                return Ok(LoopAction::Stop);
+            }
+            Msg::Header(header) => {
+                if let Some(timestamp) = header.timestamp {
+                    self.start.set(timestamp);
+                }
+                let env = header.env.unwrap_or_default();
+                let mut attributes = vec![];
+                if let Some(ref command) = header.command {
+                    attributes.push(format!("COMMAND=\"{}\"", command));
+                }
+                if let Some(term) = env.get("TERM") {
+                    attributes.push(format!("TERM=\"{}\"", term));
+                }
+                attributes.push(format!("COLUMNS=\"{}\"", header.width));
+                attributes.push(format!("LINES=\"{}\"", header.height));
+                let start = format_time(self.start.get());
+                self.output.write(
+                    format!("Script started on {} [{}]\n", start, attributes.join(" ")).as_bytes(),
+                )?;
+
+                self.write_info(0.0, "START_TIME", &start)?;
+                if let Some(term) = env.get("TERM") {
+                    self.write_info(0.0, "TERM", term)?;
+                }
+                self.write_info(0.0, "COLUMNS", &header.width.to_string())?;
+                self.write_info(0.0, "LINES", &header.height.to_string())?;
+                if let Some(shell) = env.get("SHELL") {
+                    self.write_info(0.0, "SHELL", shell)?;
+                }
+                if let Some(ref command) = header.command {
+                    self.write_info(0.0, "COMMAND", command)?;
+                }
+            }
+            Msg::Output(entry) => {
+                let data = entry.event_data.as_bytes();
+                // Data first, so the timing never points past the end of it.
+                self.output.write(data)?;
+                self.write_entry(entry.time, 'O', &data.len().to_string())?;
+            }
+            Msg::Resize(time, width, height) => {
+                if self.timing == Timing::Advanced {
+                    let signal = format!("SIGWINCH ROWS={} COLS={}", height.0, width.0);
+                    self.write_entry(time, 'S', &signal)?;
+                }
+            }
+            Msg::Exit(_, code) => self.exit_code.set(Some(code)),
+            Msg::Input(..) | Msg::Marker(..) => (),
+            #[cfg(test)]
+            Msg::MockError => unimplemented!(),
+        }
+        Ok(LoopAction::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output_formats::Durability;
+    use crate::terminal::{Height, Width};
+    use asciicast::{Entry, EventType, Header};
+    use chrono::TimeZone;
+    use std::fs;
+
+    /// Records `messages` and returns the typescript and timing file.
+    fn record(timing: Timing, messages: Vec<Msg>) -> (String, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("typescript");
+        let sink = |p: &Path| Sink::new(fs::File::create(p).unwrap(), None, Durability::Flush);
+        let output = ScriptOutput::new(
+            sink(&path).unwrap(),
+            sink(&timing_path(&path)).unwrap(),
+            timing,
+        );
+        let channel = output.channel();
+        let thread_handle = output.spawn();
+        for message in messages {
+            channel.send(message).unwrap();
+        }
+        channel.send(Msg::Finish).unwrap();
+        thread_handle.join().expect("join thread").unwrap();
+        (
+            fs::read_to_string(&path).unwrap(),
+            fs::read_to_string(timing_path(&path)).unwrap(),
+        )
+    }
+
+    fn recording() -> Vec<Msg> {
+        let output = |time, data: &str| {
+            Msg::Output(Box::new(Entry {
+                time,
+                event_type: EventType::Output,
+                event_data: data.to_string(),
+            }))
+        };
+        vec![
+            Msg::Header(Box::new(Header {
+                version: 2,
+                width: 80,
+                height: 24,
+                timestamp: Utc.timestamp_opt(1_500_000_000, 0).single(),
+                duration: None,
+                idle_time_limit: None,
+                command: Some("make".to_string()),
+                title: None,
+                env: Some(
+                    vec![("TERM".to_string(), "xterm".to_string())]
+                        .into_iter()
+                        .collect(),
+                ),
+            })),
+            output(0.5, "héllo"),
+            Msg::Resize(1.0, Width(100), Height(40)),
+            output(1.25, "!"),
+            Msg::Exit(1.5, 2),
+        ]
+    }
+
+    #[test]
+    fn writes_classic_timing() {
+        let (typescript, timing) = record(Timing::Classic, recording());
+        assert_eq!(
+            typescript,
+            "Script started on 2017-07-14 02:40:00+00:00 \
+             [COMMAND=\"make\" TERM=\"xterm\" COLUMNS=\"80\" LINES=\"24\"]\n\
+             héllo!\n\
+             Script done on 2017-07-14 02:40:01+00:00 [COMMAND_EXIT_CODE=\"2\"]\n"
+        );
+        assert_eq!(timing, "0.500000 6\n0.750000 1\n");
+    }
+
+    #[test]
+    fn writes_advanced_timing() {
+        let (_, timing) = record(Timing::Advanced, recording());
+        assert_eq!(
+            timing,
+            "H 0.000000 START_TIME 2017-07-14 02:40:00+00:00\n\
+             H 0.000000 TERM xterm\n\
+             H 0.000000 COLUMNS 80\n\
+             H 0.000000 LINES 24\n\
+             H 0.000000 COMMAND make\n\
+             O 0.500000 6\n\
+             S 0.500000 SIGWINCH ROWS=40 COLS=100\n\
+             O 0.250000 1\n\
+             H 0.250000 DURATION 1.500000\n\
+             H 0.000000 EXIT_CODE 2\n"
+        );
+    }
+
+    #[test]
+    fn puts_timing_next_to_typescript() {
+        assert_eq!(
+            timing_path(Path::new("/tmp/typescript")),
+            PathBuf::from("/tmp/typescript.timing")
+        );
+    }
+}
@@ -0,0 +1,490 @@
+use super::asciicast::to_line;
+use super::{Durability, LoopAction, Msg, Output, Sink};
+use crate::compression::Compression;
+use crate::terminal::{Height, Width};
+use ::asciicast::Header;
+use failure::{Error, Fail};
+use std::cell::{Cell, RefCell};
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+
+#[derive(Debug, Fail)]
+enum SegmentFailure {
+    #[fail(
+        display = "invalid duration: {} (expected e.g. 90s, 30m or 1h)",
+        duration
+    )]
+    InvalidDuration { duration: String },
+    #[fail(display = "invalid size: {} (expected e.g. 500KB, 50MB or 1GiB)", size)]
+    InvalidSize { size: String },
+    #[fail(display = "header not found")]
+    HeaderNotFound,
+}
+
+/// Splits `s` into its number and its unit, e.g. `30m` into `30` and `m`.
+fn split_unit(s: &str) -> (&str, &str) {
+    let s = s.trim();
+    let at = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    (&s[..at], s[at..].trim())
+}
+
+/// Parses a duration like `90s`, `30m` or `1.5h` into seconds. Plain numbers are seconds.
+pub fn parse_duration(s: &str) -> Result<f64, Error> {
+    let invalid = || SegmentFailure::InvalidDuration {
+        duration: s.to_string(),
+    };
+    let (number, unit) = split_unit(s);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let unit = match unit {
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 60.0 * 60.0,
+        _ => return Err(invalid().into()),
+    };
+    if number <= 0.0 {
+        return Err(invalid().into());
+    }
+    Ok(number * unit)
+}
+
+/// Parses a size like `500KB`, `50MB` or `1GiB` into bytes. Plain numbers are bytes.
+pub fn parse_size(s: &str) -> Result<u64, Error> {
+    let invalid = || SegmentFailure::InvalidSize {
+        size: s.to_string(),
+    };
+    let (number, unit) = split_unit(s);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let unit: u64 = match unit.to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1000 * 1000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1000 * 1000 * 1000,
+        "gib" => 1 << 30,
+        _ => return Err(invalid().into()),
+    };
+    let bytes = (number * unit as f64) as u64;
+    if bytes == 0 {
+        return Err(invalid().into());
+    }
+    Ok(bytes)
+}
+
+/// Returns the path of segment `number` of a recording saved to `path`,
+/// e.g. `demo.001.cast` for `demo.cast` and `demo.001.cast.gz` for `demo.cast.gz`.
+pub fn segment_path(path: &Path, number: usize) -> PathBuf {
+    if Compression::from_path(path).is_some() {
+        if let Some(ext) = path.extension() {
+            let mut name = segment_path(&path.with_extension(""), number).into_os_string();
+            name.push(".");
+            name.push(ext);
+            return name.into();
+        }
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{:03}.{}", stem, number, ext.to_string_lossy()),
+        None => format!("{}.{:03}", stem, number),
+    };
+    path.with_file_name(name)
+}
+
+/// When a segmented recording moves on to its next file.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Split {
+    /// The most seconds a segment covers.
+    pub every: Option<f64>,
+    /// The most bytes a segment grows to.
+    pub max_size: Option<u64>,
+}
+
+impl Split {
+    /// Whether `segment` should end before an event at `time` of `len` bytes.
+    fn is_due(&self, segment: &Segment, time: f64, len: usize) -> Result<bool, Error> {
+        // A segment gets at least one event, however big it is.
+        if segment.events == 0 {
+            return Ok(false);
+        }
+        if let (Some(every), Some(first)) = (self.every, segment.first_time) {
+            if time - first >= every {
+                return Ok(true);
+            }
+        }
+        Ok(match self.max_size {
+            Some(max) => segment.sink.size()? + len as u64 > max,
+            None => false,
+        })
+    }
+}
+
+struct Segment {
+    sink: Sink,
+    /// Where the segment's time starts, in the time of the whole recording.
+    start: f64,
+    /// When the first event of the segment happened, in the time of the whole recording.
+    first_time: Option<f64>,
+    /// When the last event of the segment happened, in the time of the whole recording.
+    last_time: f64,
+    events: usize,
+}
+
+impl Segment {
+    fn write_line(&mut self, line: &str) -> Result<(), Error> {
+        self.sink.write(format!("{}\n", line).as_bytes())
+    }
+}
+
+/// Writes an asciicast split into numbered files, see `segment_path`.
+///
+/// Every segment is a complete asciicast with the recording's header and
+/// its own timestamps. A segment's time starts where the previous one's last
+/// event happened rather than at its own first event, so the time between
+/// them is kept and joining the segments gives back the recording. A segment
+/// covers `Split::every` seconds from its first event.
+pub struct SegmentedOutput {
+    rx: Receiver<Msg>,
+    tx: Sender<Msg>,
+    path: PathBuf,
+    overwrite: bool,
+    split: Split,
+    compression: Option<Compression>,
+    durability: Durability,
+    exit_event: bool,
+    header: RefCell<Option<Header>>,
+    // The terminal size as of the last resize, if there was one.
+    size: Cell<Option<(Width, Height)>>,
+    segment: RefCell<Option<Segment>>,
+    count: Arc<AtomicUsize>,
+}
+
+impl SegmentedOutput {
+    pub fn new(
+        path: PathBuf,
+        overwrite: bool,
+        split: Split,
+        compression: Option<Compression>,
+        durability: Durability,
+        exit_event: bool,
+    ) -> Self {
+        let (tx, rx) = channel();
+        SegmentedOutput {
+            rx,
+            tx,
+            path,
+            overwrite,
+            split,
+            compression,
+            durability,
+            exit_event,
+            header: RefCell::new(None),
+            size: Cell::new(None),
+            segment: RefCell::new(None),
+            count: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the number of segments written so far, kept up to date while recording.
+    pub fn segments(&self) -> Arc<AtomicUsize> {
+        self.count.clone()
+    }
+
+    fn open(&self, start: f64) -> Result<Segment, Error> {
+        let mut header = self
+            .header
+            .borrow()
+            .clone()
+            .ok_or(SegmentFailure::HeaderNotFound)?;
+        let number = self.count.load(Ordering::SeqCst) + 1;
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .create_new(!self.overwrite)
+            .open(segment_path(&self.path, number))?;
+        self.count.store(number, Ordering::SeqCst);
+
+        let mut segment = Segment {
+            sink: Sink::new(file, self.compression, self.durability)?,
+            start,
+            first_time: None,
+            last_time: start,
+            events: 0,
+        };
+        let offset = chrono::Duration::milliseconds((start * 1000.0) as i64);
+        header.timestamp = header.timestamp.map(|t| t + offset);
+        segment.write_line(&serde_json::to_string(&header)?)?;
+        // Players start out at the header's size, so tell them it changed since.
+        if let Some((width, height)) = self.size.get() {
+            if (width.0, height.0) != (header.width, header.height) {
+                segment.write_line(&serde_json::to_string(&(
+                    0.0,
+                    "r",
+                    format!("{}x{}", width.0, height.0),
+                ))?)?;
+            }
+        }
+        Ok(segment)
+    }
+}
+
+impl Output for SegmentedOutput {
+    fn channel(&self) -> Sender<Msg> {
+        self.tx.clone()
+    }
+    fn rx(&self) -> &Receiver<Msg> {
+        &self.rx
+    }
+    fn tick(&self) -> Result<(), Error> {
+        match *self.segment.borrow() {
+            Some(ref segment) => segment.sink.tick(),
+            None => Ok(()),
+        }
+    }
+    fn handle_message(&self, message: Msg) -> Result<LoopAction, Error> {
+        let mut segment = self.segment.borrow_mut();
+        match message {
+            Msg::Finish => {
+                if let Some(ref s) = *segment {
+                    s.sink.finish()?;
+                }
+                Ok(LoopAction::Stop)
+            }
+            Msg::Header(header) => {
+                *self.header.borrow_mut() = Some(*header);
+                *segment = Some(self.open(0.0)?);
+                Ok(LoopAction::Continue)
+            }
+            // See `AsciicastOutput::with_exit_event`.
+            Msg::Exit(..) if !self.exit_event => Ok(LoopAction::Continue),
+            message => {
+                let current = segment.as_mut().ok_or(SegmentFailure::HeaderNotFound)?;
+                let time = message.time().unwrap_or(current.last_time);
+                let line = |start| to_line(&message.clone().rebased(start));
+                let mut event = line(current.start)?.unwrap_or_default();
+                // The exit status closes the last segment rather than starting one.
+                let is_exit = matches!(message, Msg::Exit(..));
+                if !is_exit && self.split.is_due(current, time, event.len() + 1)? {
+                    current.sink.finish()?;
+                    *current = self.open(current.last_time)?;
+                    event = line(current.start)?.unwrap_or_default();
+                }
+                current.write_line(&event)?;
+                current.first_time.get_or_insert(time);
+                current.last_time = time;
+                current.events += 1;
+                if let Msg::Resize(_, width, height) = message {
+                    self.size.set(Some((width, height)));
+                }
+                Ok(LoopAction::Continue)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asciicast::{Entry, EventType};
+    use std::fs;
+
+    fn output(time: f64, data: &str) -> Msg {
+        Msg::Output(Box::new(Entry {
+            time,
+            event_type: EventType::Output,
+            event_data: data.to_string(),
+        }))
+    }
+
+    fn record(path: &Path, split: Split, messages: Vec<Msg>) -> usize {
+        let output = SegmentedOutput::new(
+            path.to_path_buf(),
+            false,
+            split,
+            None,
+            Durability::Flush,
+            true,
+        );
+        let segments = output.segments();
+        let channel = output.channel();
+        let thread_handle = output.spawn();
+        channel
+            .send(Msg::Header(Box::new(Header {
+                version: 2,
+                width: 80,
+                height: 24,
+                timestamp: None,
+                duration: None,
+                idle_time_limit: None,
+                command: None,
+                title: None,
+                env: None,
+            })))
+            .unwrap();
+        for message in messages {
+            channel.send(message).unwrap();
+        }
+        channel.send(Msg::Finish).unwrap();
+        thread_handle.join().expect("join thread").unwrap();
+        segments.load(Ordering::SeqCst)
+    }
+
+    const HEADER: &str = "{\"version\":2,\"width\":80,\"height\":24}";
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90").unwrap(), 90.0);
+        assert_eq!(parse_duration("90s").unwrap(), 90.0);
+        assert_eq!(parse_duration("30m").unwrap(), 1800.0);
+        assert_eq!(parse_duration("1.5h").unwrap(), 5400.0);
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("3d").is_err());
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("500").unwrap(), 500);
+        assert_eq!(parse_size("10KB").unwrap(), 10_000);
+        assert_eq!(parse_size("50MB").unwrap(), 50_000_000);
+        assert_eq!(parse_size("50mb").unwrap(), 50_000_000);
+        assert_eq!(parse_size("1GiB").unwrap(), 1 << 30);
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("5TB").is_err());
+    }
+
+    #[test]
+    fn numbers_segments() {
+        assert_eq!(
+            segment_path(Path::new("/tmp/demo.cast"), 1),
+            Path::new("/tmp/demo.001.cast")
+        );
+        assert_eq!(segment_path(Path::new("demo"), 12), Path::new("demo.012"));
+        assert_eq!(
+            segment_path(Path::new("demo.cast.zst"), 2),
+            Path::new("demo.002.cast.zst")
+        );
+    }
+
+    #[test]
+    fn splits_by_duration() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("demo.cast");
+        let split = Split {
+            every: Some(1.0),
+            max_size: None,
+        };
+        let messages = vec![
+            output(0.5, "a"),
+            output(1.25, "b"),
+            output(1.5, "c"),
+            Msg::Exit(3.0, 0),
+        ];
+        assert_eq!(record(&path, split, messages), 2);
+
+        let read = |n| fs::read_to_string(segment_path(&path, n)).unwrap();
+        assert_eq!(
+            read(1),
+            format!("{}\n[0.5,\"o\",\"a\"]\n[1.25,\"o\",\"b\"]\n", HEADER)
+        );
+        assert_eq!(
+            read(2),
+            format!("{}\n[0.25,\"o\",\"c\"]\n[1.75,\"x\",\"0\"]\n", HEADER)
+        );
+    }
+
+    #[test]
+    fn measures_segments_from_their_first_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("demo.cast");
+        let split = Split {
+            every: Some(1.0),
+            max_size: None,
+        };
+        // The second segment's time starts at 1.0 but it covers 2.5 to 3.5.
+        let messages = vec![
+            output(1.0, "a"),
+            output(2.5, "b"),
+            output(3.25, "c"),
+            output(3.5, "d"),
+        ];
+        assert_eq!(record(&path, split, messages), 3);
+
+        let read = |n| fs::read_to_string(segment_path(&path, n)).unwrap();
+        assert_eq!(read(1), format!("{}\n[1.0,\"o\",\"a\"]\n", HEADER));
+        assert_eq!(
+            read(2),
+            format!("{}\n[1.5,\"o\",\"b\"]\n[2.25,\"o\",\"c\"]\n", HEADER)
+        );
+        assert_eq!(read(3), format!("{}\n[0.25,\"o\",\"d\"]\n", HEADER));
+    }
+
+    #[test]
+    fn splits_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("demo.cast");
+        let split = Split {
+            every: None,
+            max_size: Some(HEADER.len() as u64 + 40),
+        };
+        let messages = vec![output(0.5, "a"), output(1.0, "b"), output(1.5, "c")];
+        assert_eq!(record(&path, split, messages), 2);
+
+        let read = |n| fs::read_to_string(segment_path(&path, n)).unwrap();
+        assert_eq!(
+            read(1),
+            format!("{}\n[0.5,\"o\",\"a\"]\n[1.0,\"o\",\"b\"]\n", HEADER)
+        );
+        assert_eq!(read(2), format!("{}\n[0.5,\"o\",\"c\"]\n", HEADER));
+    }
+
+    #[test]
+    fn starts_segments_at_the_current_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("demo.cast");
+        let split = Split {
+            every: Some(1.0),
+            max_size: None,
+        };
+        let messages = vec![Msg::Resize(0.5, Width(100), Height(40)), output(2.0, "a")];
+        assert_eq!(record(&path, split, messages), 2);
+
+        assert_eq!(
+            fs::read_to_string(segment_path(&path, 2)).unwrap(),
+            format!("{}\n[0.0,\"r\",\"100x40\"]\n[1.5,\"o\",\"a\"]\n", HEADER)
+        );
+    }
+
+    #[test]
+    fn does_not_overwrite_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("demo.cast");
+        fs::write(segment_path(&path, 1), "").unwrap();
+        let output = SegmentedOutput::new(
+            path,
+            false,
+            Split::default(),
+            None,
+            Durability::Flush,
+            false,
+        );
+        let message = Msg::Header(Box::new(Header {
+            version: 2,
+            width: 80,
+            height: 24,
+            timestamp: None,
+            duration: None,
+            idle_time_limit: None,
+            command: None,
+            title: None,
+            env: None,
+        }));
+        assert!(output.handle_message(message).is_err());
+    }
+}
@@ -9,7 +9,13 @@ macro_rules! first_line_for_message {
         use tempfile::NamedTempFile;
 
         let tmpfile = NamedTempFile::new().expect("create temp file");
-        let output = $output_type::new(tmpfile.reopen().unwrap());
+        let sink = $crate::output_formats::Sink::new(
+            tmpfile.reopen().unwrap(),
+            None,
+            $crate::output_formats::Durability::default(),
+        )
+        .unwrap();
+        let output = $output_type::new(sink);
         let channel = output.channel();
         let thread_handle = output.spawn();
 
@@ -0,0 +1,88 @@
+use super::{LoopAction, Msg, Output, Sink};
+use crate::input_formats::{headerless_size, Event};
+use crate::vt::transcript::Transcript;
+use failure::Error;
+use std::cell::RefCell;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Writes the text a recording printed as plain lines, for logs to read and
+/// grep rather than to replay.
+///
+/// Output goes through a virtual terminal so that colors are dropped and
+/// redrawn lines only show up as they last looked.
+pub struct TranscriptOutput {
+    rx: Receiver<Msg>,
+    tx: Sender<Msg>,
+    output: Sink,
+    transcript: RefCell<Option<Transcript>>,
+}
+
+impl TranscriptOutput {
+    pub fn new(output: Sink) -> Self {
+        let (tx, rx) = channel();
+        let (width, height) = headerless_size(None, None);
+        TranscriptOutput {
+            rx,
+            tx,
+            output,
+            transcript: RefCell::new(Some(Transcript::new(width, height))),
+        }
+    }
+
+    fn write_lines(&self, lines: Vec<String>) -> Result<(), Error> {
+        for line in lines {
+            self.output.write(format!("{}\n", line).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn feed(&self, event: &Event) -> Result<(), Error> {
+        let lines = match *self.transcript.borrow_mut() {
+            Some(ref mut transcript) => transcript.feed(event),
+            None => vec![],
+        };
+        self.write_lines(lines)
+    }
+}
+
+impl Output for TranscriptOutput {
+    fn channel(&self) -> Sender<Msg> {
+        self.tx.clone()
+    }
+    fn rx(&self) -> &Receiver<Msg> {
+        &self.rx
+    }
+    fn tick(&self) -> Result<(), Error> {
+        self.output.tick()
+    }
+    fn handle_message(&self, message: Msg) -> Result<LoopAction, Error> {
+        match message {
+            Msg::Finish => {
+                if let Some(transcript) = self.transcript.borrow_mut().take() {
+                    self.write_lines(transcript.finish())?;
+                }
+                self.output.finish()?;
+                Ok(LoopAction::Stop)
+            }
+            Msg::Header(header) => {
+                let (width, height) = headerless_size(Some(header.width), Some(header.height));
+                *self.transcript.borrow_mut() = Some(Transcript::new(width, height));
+                Ok(LoopAction::Continue)
+            }
+            Msg::Input(..) | Msg::Marker(..) | Msg::Exit(..) => {
+                // Typed input the program wants to show is echoed in its output.
+                Ok(LoopAction::Continue)
+            }
+            Msg::Resize(time, width, height) => {
+                self.feed(&Event::Resize(time, width, height))?;
+                Ok(LoopAction::Continue)
+            }
+            Msg::Output(entry) => {
+                self.feed(&Event::Output(entry.time, entry.event_data))?;
+                Ok(LoopAction::Continue)
+            }
+            #[cfg(test)]
+            Msg::MockError => unimplemented!(),
+        }
+    }
+}
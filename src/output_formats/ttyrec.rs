@@ -0,0 +1,99 @@
+use super::{LoopAction, Msg, Output, Sink};
+use crate::input_formats::ttyrec::FRAME_HEADER_LEN;
+use chrono::Utc;
+use failure::Error;
+use std::cell::Cell;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Writes ttyrec, for players and tools that know it rather than asciicast.
+///
+/// ttyrec frames are timed in seconds since the epoch and only hold output,
+/// so the header only gives the start time and other events are left out.
+pub struct TtyrecOutput {
+    rx: Receiver<Msg>,
+    tx: Sender<Msg>,
+    output: Sink,
+    // When the recording started, in seconds since the epoch.
+    start: Cell<i64>,
+}
+
+impl TtyrecOutput {
+    pub fn new(output: Sink) -> Self {
+        let (tx, rx) = channel();
+        TtyrecOutput {
+            rx,
+            tx,
+            output,
+            start: Cell::new(Utc::now().timestamp()),
+        }
+    }
+}
+
+/// Returns the frame for `data` written at `time` seconds since the epoch.
+fn frame(time: f64, data: &[u8]) -> Vec<u8> {
+    let micros = (time * 1e6).round() as u64;
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + data.len());
+    frame.extend_from_slice(&((micros / 1_000_000) as u32).to_le_bytes());
+    frame.extend_from_slice(&((micros % 1_000_000) as u32).to_le_bytes());
+    frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    frame.extend_from_slice(data);
+    frame
+}
+
+impl Output for TtyrecOutput {
+    fn channel(&self) -> Sender<Msg> {
+        self.tx.clone()
+    }
+    fn rx(&self) -> &Receiver<Msg> {
+        &self.rx
+    }
+    fn tick(&self) -> Result<(), Error> {
+        self.output.tick()
+    }
+    fn handle_message(&self, message: Msg) -> Result<LoopAction, Error> {
+        match message {
+            Msg::Finish => {
+                self.output.finish()?;
+                Ok(LoopAction::Stop)
+            }
+            Msg::Header(header) => {
+                if let Some(timestamp) = header.timestamp {
+                    self.start.set(timestamp.timestamp());
+                }
+                Ok(LoopAction::Continue)
+            }
+            Msg::Input(..) | Msg::Resize(..) | Msg::Marker(..) | Msg::Exit(..) => {
+                // ttyrec only has output.
+                Ok(LoopAction::Continue)
+            }
+            Msg::Output(entry) => {
+                let time = self.start.get() as f64 + entry.time;
+                // One write per frame, so a reader never sees half of one.
+                self.output
+                    .write(&frame(time, entry.event_data.as_bytes()))?;
+                Ok(LoopAction::Continue)
+            }
+            #[cfg(test)]
+            Msg::MockError => unimplemented!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_time_into_seconds_and_microseconds() {
+        assert_eq!(
+            frame(1_500_000_000.25, b"hi"),
+            [
+                &1_500_000_000u32.to_le_bytes()[..],
+                &250_000u32.to_le_bytes(),
+                &2u32.to_le_bytes(),
+                b"hi",
+            ]
+            .concat()
+        );
+    }
+}
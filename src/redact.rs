@@ -0,0 +1,295 @@
+use failure::Error;
+use regex::Regex;
+use regex_automata::hybrid::dfa::{Cache, DFA};
+use regex_automata::hybrid::LazyStateID;
+use regex_automata::{Anchored, Input};
+
+/// What redacted text is replaced with.
+const REPLACEMENT: &str = "[REDACTED]";
+
+/// The most text held back waiting to see whether it turns into a match.
+const MAX_PENDING: usize = 4096;
+
+#[derive(Clone)]
+struct Rules {
+    regex: Regex,
+    // Used to tell whether text could still turn into a match with more input.
+    dfa: DFA,
+    cache: Cache,
+    // Where unfinished matches may start in the pending text, earliest first.
+    candidates: Vec<Candidate>,
+}
+
+/// A possible start of a match, and the state the DFA reached from it.
+#[derive(Clone, Copy)]
+struct Candidate {
+    start: usize,
+    // `None` if the DFA gave up, so we can't tell whether it's still live.
+    state: Option<LazyStateID>,
+}
+
+impl Rules {
+    /// Returns where the earliest possible start of an unfinished match is,
+    /// given `text` was `text[..scanned]` the last time.
+    fn hold_from(&mut self, text: &str, mut scanned: usize) -> usize {
+        for _ in 0..2 {
+            let clears = self.cache.clear_count();
+            self.scan(text, scanned);
+            let hold = self.earliest_live(text.len());
+            if self.cache.clear_count() == clears {
+                return hold;
+            }
+            // Clearing the cache invalidated the states we kept, start over.
+            self.candidates.clear();
+            scanned = 0;
+        }
+        // We can't tell, so play it safe.
+        let window = text.len().saturating_sub(MAX_PENDING);
+        self.candidates.clear();
+        if let Some(start) = (window..text.len()).find(|&i| text.is_char_boundary(i)) {
+            self.candidates.push(Candidate { start, state: None });
+        }
+        self.earliest_live(text.len())
+    }
+
+    /// Feeds `text[from..]` to the DFA from every possible start so far.
+    fn scan(&mut self, text: &str, from: usize) {
+        let window = text.len().saturating_sub(MAX_PENDING);
+        self.candidates.retain(|c| c.start >= window);
+        for (i, &byte) in text.as_bytes().iter().enumerate().skip(from) {
+            if i >= window && text.is_char_boundary(i) {
+                let input = Input::new(text).range(i..).anchored(Anchored::Yes);
+                let state = self.dfa.start_state_forward(&mut self.cache, &input).ok();
+                self.candidates.push(Candidate { start: i, state });
+            }
+            let (dfa, cache) = (&self.dfa, &mut self.cache);
+            let mut seen = vec![];
+            self.candidates.retain_mut(|c| {
+                if let Some(state) = c.state {
+                    c.state = match dfa.next_state(cache, state, byte) {
+                        Ok(s) if s.is_dead() => return false,
+                        Ok(s) if !s.is_quit() => Some(s),
+                        _ => None,
+                    };
+                }
+                // Starts in the same state match the same, the earliest will do.
+                match c.state {
+                    Some(state) if seen.contains(&state) => false,
+                    Some(state) => {
+                        seen.push(state);
+                        true
+                    }
+                    None => true,
+                }
+            });
+        }
+    }
+
+    /// Returns the start of the earliest candidate that could continue past
+    /// the end of the text.
+    fn earliest_live(&mut self, end: usize) -> usize {
+        for i in 0..self.candidates.len() {
+            let live = match self.candidates[i].state {
+                Some(state) => self.can_grow(state),
+                None => true,
+            };
+            if live {
+                return self.candidates[i].start;
+            }
+        }
+        end
+    }
+
+    /// Forgets about the first `len` bytes of the text, they were written.
+    fn drain(&mut self, len: usize) {
+        self.candidates.retain(|c| c.start >= len);
+        for c in &mut self.candidates {
+            c.start -= len;
+        }
+    }
+
+    /// Whether any more input keeps `state` from dying.
+    fn can_grow(&mut self, state: LazyStateID) -> bool {
+        let mut finished = vec![];
+        for byte in 0..=255u8 {
+            let next = match self.dfa.next_state(&mut self.cache, state, byte) {
+                Ok(s) => s,
+                Err(_) => return true,
+            };
+            if next.is_dead() || finished.contains(&next) {
+                continue;
+            }
+            if !next.is_match() || next.is_quit() {
+                return true;
+            }
+            // Matches are reported a byte late, so a match state may only be
+            // reporting a match that already ended. It's live if it can go on.
+            let live = (0..=255u8).any(|b| match self.dfa.next_state(&mut self.cache, next, b) {
+                Ok(s) => !s.is_dead(),
+                Err(_) => true,
+            });
+            if live {
+                return true;
+            }
+            finished.push(next);
+        }
+        false
+    }
+}
+
+/// Replaces text matching any of a set of regexes in a stream of text.
+///
+/// Text that may be the start of a match is held back until more text shows
+/// whether it is one, so matches split between reads are redacted as well.
+#[derive(Clone, Default)]
+pub struct Redactor {
+    rules: Option<Rules>,
+    pending: String,
+}
+
+impl Redactor {
+    pub fn new<S: AsRef<str>>(rules: &[S]) -> Result<Self, Error> {
+        if rules.is_empty() {
+            return Ok(Default::default());
+        }
+
+        let pattern = rules
+            .iter()
+            .map(|rule| format!("(?:{})", rule.as_ref()))
+            .collect::<Vec<_>>()
+            .join("|");
+        let regex = Regex::new(&pattern)?;
+        let dfa = DFA::builder()
+            .configure(DFA::config().unicode_word_boundary(true))
+            .build(&pattern)?;
+        let cache = dfa.create_cache();
+        Ok(Redactor {
+            rules: Some(Rules {
+                regex,
+                dfa,
+                cache,
+                candidates: vec![],
+            }),
+            pending: String::new(),
+        })
+    }
+
+    pub fn redact(&mut self, text: &str) -> String {
+        let rules = match self.rules {
+            Some(ref mut r) => r,
+            None => return text.to_string(),
+        };
+        let scanned = self.pending.len();
+        self.pending.push_str(text);
+
+        let hold = rules.hold_from(&self.pending, scanned);
+        let mut redacted = String::with_capacity(hold);
+        let mut last = 0;
+        for m in rules.regex.find_iter(&self.pending) {
+            // Matches starting before `hold` can't grow any further.
+            if m.start() >= hold {
+                break;
+            }
+            if m.start() == m.end() {
+                continue;
+            }
+            redacted.push_str(&self.pending[last..m.start()]);
+            redacted.push_str(REPLACEMENT);
+            last = m.end();
+        }
+        let done = hold.max(last);
+        redacted.push_str(&self.pending[last..done]);
+        self.pending.drain(..done);
+        rules.drain(done);
+        redacted
+    }
+
+    /// Returns the text still held back, at the end of the stream.
+    pub fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.pending);
+        match self.rules {
+            Some(ref mut r) => {
+                r.candidates.clear();
+                r.regex.replace_all(&rest, REPLACEMENT).into_owned()
+            }
+            None => rest,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "ghp_[A-Za-z0-9]{8}";
+
+    #[test]
+    fn passes_through_without_rules() {
+        let mut redactor = Redactor::default();
+        assert_eq!(redactor.redact("ghp_12345678"), "ghp_12345678");
+        assert_eq!(redactor.finish(), "");
+    }
+
+    #[test]
+    fn redacts_matches() {
+        let mut redactor = Redactor::new(&[TOKEN, "AKIA[0-9A-Z]{4}"]).unwrap();
+        assert_eq!(
+            redactor.redact("token ghp_12345678, key AKIAABCD\r\n"),
+            "token [REDACTED], key [REDACTED]\r\n"
+        );
+    }
+
+    #[test]
+    fn redacts_matches_split_between_reads() {
+        let mut redactor = Redactor::new(&[TOKEN]).unwrap();
+        assert_eq!(redactor.redact("token gh"), "token ");
+        assert_eq!(redactor.redact("p_1234"), "");
+        assert_eq!(redactor.redact("5678 done"), "[REDACTED] done");
+    }
+
+    #[test]
+    fn does_not_hold_back_text_that_cannot_match() {
+        let mut redactor = Redactor::new(&[TOKEN]).unwrap();
+        assert_eq!(redactor.redact("$ "), "$ ");
+        assert_eq!(redactor.redact("ghp_nope!"), "ghp_nope!");
+    }
+
+    #[test]
+    fn does_not_hold_back_finished_matches() {
+        let mut redactor = Redactor::new(&["hunter[0-9]"]).unwrap();
+        assert_eq!(redactor.redact("pw: hunter2"), "pw: [REDACTED]");
+    }
+
+    #[test]
+    fn redacts_longest_match_across_reads() {
+        let mut redactor = Redactor::new(&["secret=\\w+"]).unwrap();
+        assert_eq!(redactor.redact("secret=ab"), "");
+        assert_eq!(redactor.redact("cd"), "");
+        assert_eq!(redactor.redact(" ok"), "[REDACTED] ok");
+    }
+
+    #[test]
+    fn redacts_matches_read_a_byte_at_a_time() {
+        let mut redactor = Redactor::new(&[TOKEN, "secret=\\w+"]).unwrap();
+        let redacted = "a ghp_12345678 b secret=xyz. c"
+            .chars()
+            .map(|c| redactor.redact(&c.to_string()))
+            .collect::<String>();
+        assert_eq!(
+            redacted + &redactor.finish(),
+            "a [REDACTED] b [REDACTED]. c"
+        );
+    }
+
+    #[test]
+    fn finish_redacts_pending_text() {
+        let mut redactor = Redactor::new(&["secret=\\w+"]).unwrap();
+        assert_eq!(redactor.redact("x secret=abc"), "x ");
+        assert_eq!(redactor.finish(), "[REDACTED]");
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(Redactor::new(&["("]).is_err());
+    }
+}
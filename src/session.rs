@@ -1,16 +1,23 @@
 use crate::clock::{get_elapsed_seconds, Clock};
+use crate::decoder::Utf8Decoder;
 use crate::output_formats::Msg;
+use crate::redact::Redactor;
 use crate::terminal::{Height, Width};
 use failure::Error;
 use std::boxed::Box;
 use std::collections::HashMap;
-use std::str;
 use std::sync::mpsc::Sender;
+use std::time::Duration;
 
 pub struct Session {
     clock: Clock,
     outputs: Vec<Sender<Msg>>,
     record_input: bool,
+    // Output and input are separate streams, each may split characters differently.
+    output_decoder: Utf8Decoder,
+    input_decoder: Utf8Decoder,
+    output_redactor: Redactor,
+    input_redactor: Redactor,
 }
 
 impl Session {
@@ -19,8 +26,26 @@ impl Session {
             clock: Clock::new(),
             outputs,
             record_input,
+            output_decoder: Utf8Decoder::new(),
+            input_decoder: Utf8Decoder::new(),
+            output_redactor: Redactor::default(),
+            input_redactor: Redactor::default(),
         }
     }
+    /// Sends a message to every output.
+    ///
+    /// An output that failed has hung up, it is dropped so the others keep
+    /// receiving messages. Its error is reported when its thread is joined.
+    fn send(&mut self, message: Msg) -> Result<(), Error> {
+        self.outputs
+            .retain(|channel| channel.send(message.clone()).is_ok());
+        Ok(())
+    }
+    /// Adds outputs that only get what is written from now on, e.g. ones
+    /// continuing a recording that already has a header.
+    pub fn add_outputs(&mut self, outputs: Vec<Sender<Msg>>) {
+        self.outputs.extend(outputs);
+    }
     fn get_elapsed_seconds(&self) -> f64 {
         get_elapsed_seconds(&self.clock.elapsed())
     }
@@ -52,47 +77,119 @@ impl Session {
             title,
             env,
         };
-        for channel in &self.outputs {
-            channel.send(Msg::Header(Box::new(header.clone())))?;
+        self.send(Msg::Header(Box::new(header)))
+    }
+    /// Continues a recording whose last event happened at `seconds`, e.g.
+    /// when appending to it, so time doesn't go backwards.
+    pub fn set_start_time(&mut self, seconds: f64) {
+        self.clock
+            .set_offset(Duration::from_secs_f64(seconds.max(0.0)));
+    }
+    /// Redacts recorded output and input with `redactor`.
+    ///
+    /// Only what is written to the outputs changes, not what the user sees.
+    pub fn set_redactor(&mut self, redactor: Redactor) {
+        self.output_redactor = redactor.clone();
+        self.input_redactor = redactor;
+    }
+    /// Pauses or resumes recording, returning whether it is now paused.
+    ///
+    /// While paused input and output are dropped and the clock stands still, so
+    /// the recording continues seamlessly once resumed.
+    pub fn toggle_pause(&mut self) -> Result<bool, Error> {
+        if self.clock.is_paused() {
+            self.clock.resume();
+        } else {
+            // Text held back for redaction was written before the pause.
+            let event_data = self.output_redactor.finish();
+            self.send_output(event_data)?;
+            if self.record_input {
+                let event_data = self.input_redactor.finish();
+                self.send_input(event_data)?;
+            }
+            self.clock.pause();
         }
-        Ok(())
+        Ok(self.clock.is_paused())
     }
     pub fn write_output(&mut self, data: &[u8]) -> Result<(), Error> {
+        // Always decode so a character split around a pause doesn't get mangled.
+        let decoded = self.output_decoder.decode(data);
+        if self.clock.is_paused() {
+            return Ok(());
+        }
+        let event_data = self.output_redactor.redact(&decoded);
+        self.send_output(event_data)
+    }
+    fn send_output(&mut self, event_data: String) -> Result<(), Error> {
+        if event_data.is_empty() {
+            return Ok(());
+        }
         // Generate asciicast entry.
         let entry = asciicast::Entry {
             time: self.get_elapsed_seconds(),
             event_type: asciicast::EventType::Output,
-            event_data: str::from_utf8(data)?.to_string(),
+            event_data,
         };
 
         // Write it out.
-        for channel in &self.outputs {
-            channel.send(Msg::Output(Box::new(entry.clone())))?;
-        }
-        Ok(())
+        self.send(Msg::Output(Box::new(entry)))
     }
     #[allow(unused)]
     pub fn write_input(&mut self, data: &[u8]) -> Result<(), Error> {
         if (self.record_input) {
-            // Generate asciicast entry.
-            let entry = asciicast::Entry {
-                time: self.get_elapsed_seconds(),
-                event_type: asciicast::EventType::Input,
-                event_data: str::from_utf8(data)?.to_string(),
-            };
-
-            // Write it out.
-            for channel in &self.outputs {
-                channel.send(Msg::Input(Box::new(entry.clone())))?;
+            let decoded = self.input_decoder.decode(data);
+            if self.clock.is_paused() {
+                return Ok(());
             }
+            let event_data = self.input_redactor.redact(&decoded);
+            return self.send_input(event_data);
         }
         Ok(())
     }
+    fn send_input(&mut self, event_data: String) -> Result<(), Error> {
+        if event_data.is_empty() {
+            return Ok(());
+        }
+        // Generate asciicast entry.
+        let entry = asciicast::Entry {
+            time: self.get_elapsed_seconds(),
+            event_type: asciicast::EventType::Input,
+            event_data,
+        };
+
+        // Write it out.
+        self.send(Msg::Input(Box::new(entry)))
+    }
+    pub fn write_resize(&mut self, height: &Height, width: &Width) -> Result<(), Error> {
+        let time = self.get_elapsed_seconds();
+        self.send(Msg::Resize(time, *width, *height))
+    }
+    pub fn write_marker(&mut self, label: Option<String>) -> Result<(), Error> {
+        let time = self.get_elapsed_seconds();
+        self.send(Msg::Marker(time, label.unwrap_or_default()))
+    }
+    /// Records how the recorded command exited.
+    pub fn write_exit(&mut self, code: i32) -> Result<(), Error> {
+        let time = self.get_elapsed_seconds();
+        self.send(Msg::Exit(time, code))
+    }
     pub fn end(&mut self) -> Result<(), Error> {
-        for channel in &self.outputs {
-            channel.send(Msg::Finish)?;
+        // Don't lose a trailing incomplete character or text held back for redaction.
+        // While paused there is neither, `toggle_pause` flushed what was held.
+        if !self.clock.is_paused() {
+            let trailing = self.output_decoder.finish();
+            let mut event_data = self.output_redactor.redact(&trailing);
+            event_data.push_str(&self.output_redactor.finish());
+            self.send_output(event_data)?;
+
+            if self.record_input {
+                let trailing = self.input_decoder.finish();
+                let mut event_data = self.input_redactor.redact(&trailing);
+                event_data.push_str(&self.input_redactor.finish());
+                self.send_input(event_data)?;
+            }
         }
-        Ok(())
+        self.send(Msg::Finish)
     }
 }
 
@@ -103,7 +200,7 @@ mod tests {
     use asciicast::{Entry, EventType, Header};
     use chrono::{DateTime, Utc};
     use std::boxed::Box;
-    use std::sync::mpsc::channel;
+    use std::sync::mpsc::{channel, Receiver};
     use std::time::Duration;
 
     fn make_mock_session(
@@ -205,4 +302,234 @@ mod tests {
             }))
         );
     }
+
+    #[test]
+    fn sends_message_for_resize() {
+        let duration = Duration::new(3, 0);
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, false, None, Some(duration));
+
+        session.write_resize(&Height(24), &Width(80)).unwrap();
+
+        let result = rx.try_recv();
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), Msg::Resize(3.0, Width(80), Height(24)));
+    }
+
+    #[test]
+    fn sends_message_for_marker() {
+        let duration = Duration::new(12, 0);
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, false, None, Some(duration));
+
+        session.write_marker(Some("intro".to_string())).unwrap();
+        session.write_marker(None).unwrap();
+
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            Msg::Marker(12.0, "intro".to_string())
+        );
+        assert_eq!(rx.try_recv().unwrap(), Msg::Marker(12.0, "".to_string()));
+    }
+
+    #[test]
+    fn drops_events_while_paused() {
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, true, None, Some(Duration::new(1, 0)));
+
+        assert!(session.toggle_pause().unwrap());
+        session.write_output("secret".as_bytes()).unwrap();
+        session.write_input("secret".as_bytes()).unwrap();
+        assert!(rx.try_recv().is_err());
+
+        assert!(!session.toggle_pause().unwrap());
+        session.write_output("public".as_bytes()).unwrap();
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            Msg::Output(Box::new(Entry {
+                event_type: EventType::Output,
+                event_data: "public".to_string(),
+                time: 1.0,
+            }))
+        );
+    }
+
+    #[test]
+    fn clock_stands_still_while_paused() {
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, false, None, Some(Duration::new(2, 0)));
+
+        session.toggle_pause().unwrap();
+        session.clock.set_duration_override(Duration::new(60, 0));
+        session.toggle_pause().unwrap();
+        session.clock.set_duration_override(Duration::new(61, 0));
+        session.write_output("after".as_bytes()).unwrap();
+
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            Msg::Output(Box::new(Entry {
+                event_type: EventType::Output,
+                event_data: "after".to_string(),
+                time: 3.0,
+            }))
+        );
+    }
+
+    fn output_data(rx: &Receiver<Msg>) -> Vec<String> {
+        rx.try_iter()
+            .filter_map(|m| match m {
+                Msg::Output(e) => Some(e.event_data),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn joins_multibyte_output_split_across_reads() {
+        let crab = "🦀".as_bytes();
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, false, None, Some(Duration::new(1, 0)));
+
+        session.write_output(&[b'a', crab[0], crab[1]]).unwrap();
+        session.write_output(&crab[2..3]).unwrap();
+        session.write_output(&[crab[3], b'b']).unwrap();
+
+        assert_eq!(output_data(&rx), vec!["a".to_string(), "🦀b".to_string()]);
+    }
+
+    #[test]
+    fn joins_multibyte_input_split_across_reads() {
+        let e_acute = "é".as_bytes();
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, true, None, Some(Duration::new(1, 0)));
+
+        session.write_input(&e_acute[..1]).unwrap();
+        assert!(rx.try_recv().is_err());
+        session.write_input(&e_acute[1..]).unwrap();
+
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            Msg::Input(Box::new(Entry {
+                event_type: EventType::Input,
+                event_data: "é".to_string(),
+                time: 1.0,
+            }))
+        );
+    }
+
+    #[test]
+    fn replaces_invalid_output() {
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, false, None, Some(Duration::new(1, 0)));
+
+        session.write_output(b"caf\xE9\r\n").unwrap();
+
+        assert_eq!(output_data(&rx), vec!["caf\u{FFFD}\r\n".to_string()]);
+    }
+
+    #[test]
+    fn flushes_incomplete_output_on_end() {
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, false, None, Some(Duration::new(1, 0)));
+
+        session.write_output(b"done\xF0\x9F").unwrap();
+        session.end().unwrap();
+
+        assert_eq!(
+            output_data(&rx),
+            vec!["done".to_string(), "\u{FFFD}".to_string()]
+        );
+    }
+
+    #[test]
+    fn sends_message_for_exit() {
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, false, None, Some(Duration::new(4, 0)));
+
+        session.write_exit(2).unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), Msg::Exit(4.0, 2));
+    }
+
+    #[test]
+    fn keeps_sending_to_remaining_outputs() {
+        let (tx1, rx1) = channel();
+        let (tx2, rx2) = channel();
+        let mut session = Session::new(vec![tx1, tx2], false);
+        drop(rx1);
+
+        session.write_marker(None).unwrap();
+        session.end().unwrap();
+
+        assert!(matches!(rx2.try_recv().unwrap(), Msg::Marker(..)));
+        assert_eq!(rx2.try_recv().unwrap(), Msg::Finish);
+    }
+
+    #[test]
+    fn continues_from_start_time() {
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, false, None, Some(Duration::new(2, 0)));
+        session.set_start_time(10.0);
+
+        session.write_marker(None).unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), Msg::Marker(12.0, "".to_string()));
+    }
+
+    #[test]
+    fn redacts_output_and_input() {
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, true, None, Some(Duration::new(1, 0)));
+        session.set_redactor(Redactor::new(&["hunter[0-9]"]).unwrap());
+
+        session.write_input(b"hunt").unwrap();
+        session.write_input(b"er2\r").unwrap();
+        session.write_output(b"pass: hunter").unwrap();
+        session.write_output(b"2\r\n").unwrap();
+        session.end().unwrap();
+
+        let mut messages = vec![];
+        while let Ok(message) = rx.try_recv() {
+            messages.push(message);
+        }
+        let data = |message: &Msg| match message {
+            Msg::Input(e) | Msg::Output(e) => e.event_data.clone(),
+            _ => String::new(),
+        };
+        assert_eq!(
+            messages.iter().map(data).collect::<Vec<_>>(),
+            vec!["[REDACTED]\r", "pass: ", "[REDACTED]\r\n", ""]
+        );
+    }
+
+    #[test]
+    fn flushes_redacted_text_when_paused() {
+        let (tx, rx) = channel();
+        let mut session = make_mock_session(tx, true, None, Some(Duration::new(1, 0)));
+        session.set_redactor(Redactor::new(&["secret=\\w+"]).unwrap());
+
+        session.write_output(b"x secret=abc").unwrap();
+        session.write_input(b"secret=").unwrap();
+        session.toggle_pause().unwrap();
+        session.clock.set_duration_override(Duration::new(5, 0));
+        session.end().unwrap();
+
+        let messages = rx.try_iter().collect::<Vec<_>>();
+        let entry = |event_type, event_data: &str| {
+            Box::new(Entry {
+                event_type,
+                event_data: event_data.to_string(),
+                time: 1.0,
+            })
+        };
+        assert_eq!(
+            messages,
+            vec![
+                Msg::Output(entry(EventType::Output, "x ")),
+                Msg::Output(entry(EventType::Output, "[REDACTED]")),
+                Msg::Input(entry(EventType::Input, "secret=")),
+                Msg::Finish,
+            ]
+        );
+    }
 }
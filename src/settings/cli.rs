@@ -1,5 +1,6 @@
 use super::{
-    AuthenticateSettings, ConcatenateSettings, PlaySettings, RecordSettings, UploadSettings,
+    AuthenticateSettings, ConcatenateSettings, ExportSettings, JoinSettings, PlaySettings,
+    PosterSettings, RecordSettings, RepairSettings, SnapshotSettings, UploadSettings,
 };
 use structopt::clap::AppSettings;
 use structopt::StructOpt;
@@ -13,21 +14,36 @@ use structopt::StructOpt;
 pub enum CommandLine {
     /// Manage recordings on asciinema.org account
     #[structopt(name = "authenticate")]
-    #[structopt(alias = r#""auth""#)]
+    #[structopt(alias = "auth")]
     Authenticate(AuthenticateSettings),
     /// Print full output of terminal session
     #[structopt(name = "concatenate")]
-    #[structopt(alias = r#""cat""#)]
+    #[structopt(alias = "cat")]
     Concatenate(ConcatenateSettings),
+    /// Export a recording to share it outside of a terminal
+    #[structopt(name = "export")]
+    Export(ExportSettings),
+    /// Join recordings, e.g. the segments of a split recording, into one
+    #[structopt(name = "join")]
+    Join(JoinSettings),
     /// Replay recorded asciicast in a terminal
     #[structopt(name = "play")]
     Play(PlaySettings),
+    /// Draw the screen of a recording at a given moment to a PNG image
+    #[structopt(name = "poster")]
+    Poster(PosterSettings),
     /// Record terminal session
     #[structopt(name = "record")]
-    #[structopt(alias = r#""rec""#)]
-    Record(RecordSettings),
+    #[structopt(alias = "rec")]
+    Record(Box<RecordSettings>),
+    /// Fix a recording left truncated or out of order, e.g. by a crash
+    #[structopt(name = "repair")]
+    Repair(RepairSettings),
+    /// Print the screen of a recording at a given moment
+    #[structopt(name = "snapshot")]
+    Snapshot(SnapshotSettings),
     /// Upload locally saved terminal session to asciinema.org
     #[structopt(name = "upload")]
-    #[structopt(alias = r#""up""#)]
+    #[structopt(alias = "up")]
     Upload(UploadSettings),
 }
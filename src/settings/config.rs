@@ -3,6 +3,7 @@ use config::{Config, ConfigError, Environment, File};
 use failure::Error;
 use failure::Fail;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::env;
 use std::path::PathBuf;
 
@@ -24,6 +25,8 @@ enum ConfigFailure {
 pub struct AsciinemaConfig {
     pub api: Option<ApiSettings>,
     pub record: Option<RecordSettings>,
+    /// Named regexes to redact from recordings, e.g. `github = ghp_\w{36}`.
+    pub redact: Option<BTreeMap<String, String>>,
     // TODO: `play` settings
 }
 
@@ -34,12 +37,23 @@ impl AsciinemaConfig {
         let mut s = Config::new();
         s.merge(File::from(location.0).required(false))?;
         s.merge(Environment::with_prefix("asciinema").separator("_"))?;
+        Self::from_config(s)
+    }
 
+    fn from_config(s: Config) -> Result<Self, Error> {
         match s.try_into() {
             Ok(x) => Ok(x),
             Err(e) => Err(ConfigFailure::ConfigFileParsingError(e).into()),
         }
     }
+
+    /// Returns the redaction rules, ordered by name.
+    pub fn redact_rules(&self) -> Vec<String> {
+        self.redact
+            .as_ref()
+            .map(|rules| rules.values().cloned().collect())
+            .unwrap_or_default()
+    }
 }
 
 /// Finds the location in home directory to write configuration to using
@@ -134,4 +148,16 @@ mod tests {
             Url::parse("http://www.example.com").unwrap()
         );
     }
+
+    #[test]
+    fn orders_redact_rules_by_name() {
+        let mut s = Config::new();
+        s.set("redact.github", "ghp_\\w+").unwrap();
+        s.set("redact.aws", "AKIA\\w+").unwrap();
+        let c = AsciinemaConfig::from_config(s).unwrap();
+        assert_eq!(
+            c.redact_rules(),
+            vec!["AKIA\\w+".to_string(), "ghp_\\w+".to_string()]
+        );
+    }
 }
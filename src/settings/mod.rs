@@ -15,8 +15,13 @@ use serde::Deserialize;
 pub enum Action {
     Authenticate,
     Concatenate,
+    Export,
+    Join,
     Play,
+    Poster,
     Record,
+    Repair,
+    Snapshot,
     Upload,
 }
 
@@ -25,8 +30,13 @@ pub struct Settings {
     pub api_url: Url,
     pub authenticate: Option<AuthenticateSettings>,
     pub concatenate: Option<ConcatenateSettings>,
+    pub export: Option<ExportSettings>,
+    pub join: Option<JoinSettings>,
     pub play: Option<PlaySettings>,
+    pub poster: Option<PosterSettings>,
     pub record: Option<RecordSettings>,
+    pub repair: Option<RepairSettings>,
+    pub snapshot: Option<SnapshotSettings>,
     pub upload: Option<UploadSettings>,
 }
 
@@ -34,6 +44,7 @@ impl Settings {
     pub fn new() -> Result<Self, Error> {
         // Load saved config.
         let config = AsciinemaConfig::new()?;
+        let redact_rules = config.redact_rules();
 
         // Api settings cannot be entered on the command line, only
         // via the config file.
@@ -49,8 +60,13 @@ impl Settings {
                 api_url,
                 authenticate: Some(AuthenticateSettings { ..x }),
                 concatenate: None,
+                export: None,
+                join: None,
                 play: None,
+                poster: None,
                 record: None,
+                repair: None,
+                snapshot: None,
                 upload: None,
             }),
             CommandLine::Concatenate { 0: x } => Ok(Settings {
@@ -58,8 +74,41 @@ impl Settings {
                 api_url,
                 authenticate: None,
                 concatenate: Some(ConcatenateSettings { ..x }),
+                export: None,
+                join: None,
                 play: None,
+                poster: None,
                 record: None,
+                repair: None,
+                snapshot: None,
+                upload: None,
+            }),
+            CommandLine::Export { 0: x } => Ok(Settings {
+                action: Action::Export,
+                api_url,
+                authenticate: None,
+                concatenate: None,
+                export: Some(ExportSettings { ..x }),
+                join: None,
+                play: None,
+                poster: None,
+                record: None,
+                repair: None,
+                snapshot: None,
+                upload: None,
+            }),
+            CommandLine::Join { 0: x } => Ok(Settings {
+                action: Action::Join,
+                api_url,
+                authenticate: None,
+                concatenate: None,
+                export: None,
+                join: Some(JoinSettings { ..x }),
+                play: None,
+                poster: None,
+                record: None,
+                repair: None,
+                snapshot: None,
                 upload: None,
             }),
             CommandLine::Play { 0: x } => Ok(Settings {
@@ -67,8 +116,27 @@ impl Settings {
                 api_url,
                 authenticate: None,
                 concatenate: None,
+                export: None,
+                join: None,
                 play: Some(PlaySettings { ..x }),
+                poster: None,
                 record: None,
+                repair: None,
+                snapshot: None,
+                upload: None,
+            }),
+            CommandLine::Poster { 0: x } => Ok(Settings {
+                action: Action::Poster,
+                api_url,
+                authenticate: None,
+                concatenate: None,
+                export: None,
+                join: None,
+                play: None,
+                poster: Some(PosterSettings { ..x }),
+                record: None,
+                repair: None,
+                snapshot: None,
                 upload: None,
             }),
             CommandLine::Record { 0: x } => Ok(Settings {
@@ -76,8 +144,45 @@ impl Settings {
                 api_url,
                 authenticate: None,
                 concatenate: None,
+                export: None,
+                join: None,
+                play: None,
+                poster: None,
+                record: Some(RecordSettings {
+                    // Redaction rules from the config file always apply.
+                    redact: redact_rules.into_iter().chain(x.redact.clone()).collect(),
+                    ..*x
+                }),
+                repair: None,
+                snapshot: None,
+                upload: None,
+            }),
+            CommandLine::Repair { 0: x } => Ok(Settings {
+                action: Action::Repair,
+                api_url,
+                authenticate: None,
+                concatenate: None,
+                export: None,
+                join: None,
                 play: None,
-                record: Some(RecordSettings { ..x }),
+                poster: None,
+                record: None,
+                repair: Some(RepairSettings { ..x }),
+                snapshot: None,
+                upload: None,
+            }),
+            CommandLine::Snapshot { 0: x } => Ok(Settings {
+                action: Action::Snapshot,
+                api_url,
+                authenticate: None,
+                concatenate: None,
+                export: None,
+                join: None,
+                play: None,
+                poster: None,
+                record: None,
+                repair: None,
+                snapshot: Some(SnapshotSettings { ..x }),
                 upload: None,
             }),
             CommandLine::Upload { 0: x } => Ok(Settings {
@@ -85,8 +190,13 @@ impl Settings {
                 api_url,
                 authenticate: None,
                 concatenate: None,
+                export: None,
+                join: None,
                 play: None,
+                poster: None,
                 record: None,
+                repair: None,
+                snapshot: None,
                 upload: Some(UploadSettings { ..x }),
             }),
         }
@@ -104,6 +214,15 @@ pub struct PlaySettings {
     #[structopt(long = "hide-cursor")]
     /// Hide the cursor during playback
     pub hide_cursor: bool,
+    /// Timing file of a typescript recorded with `script --timing` or `--log-timing`
+    #[structopt(long = "timing", name = "TIMING", parse(from_os_str))]
+    pub timing: Option<PathBuf>,
+    /// Terminal width of recordings without a header, e.g. ttyrec
+    #[structopt(long = "cols")]
+    pub cols: Option<u32>,
+    /// Terminal height of recordings without a header, e.g. ttyrec
+    #[structopt(long = "rows")]
+    pub rows: Option<u32>,
     /// Location can be either local recording or remote recording
     #[structopt(name = "LOCATION", parse(from_os_str))]
     pub location: PathBuf,
@@ -114,7 +233,9 @@ pub struct RecordSettings {
     /// Title of the asciicast
     #[structopt(short = "t", long = "title")]
     pub title: Option<String>,
-    // TODO: command
+    /// Command to record, defaults to $SHELL
+    #[structopt(short = "c", long = "command")]
+    pub command: Option<String>,
     /// Limit recorded idle time to given number of seconds
     #[structopt(name = "IDLE_TIME_LIMIT", short = "i", long = "idle-time-limit")]
     pub idle_time_limit: Option<f64>,
@@ -130,9 +251,57 @@ pub struct RecordSettings {
     /// Enable stdin (keyboard) recording, disabled by default
     #[structopt(long = "stdin")]
     pub record_stdin: bool,
+    /// Prefix key for hotkeys while recording, e.g. "C-\" (then "m" adds a marker, "M" a labelled one, "p" pauses)
+    #[structopt(long = "prefix-key")]
+    pub prefix_key: Option<String>,
+    /// Terminal width to record with, implies headless recording
+    #[structopt(long = "cols")]
+    pub cols: Option<u16>,
+    /// Terminal height to record with, implies headless recording
+    #[structopt(long = "rows")]
+    pub rows: Option<u16>,
     /// Save only raw stdout output
     #[structopt(long = "raw", requires = "FILE")]
     pub raw: bool,
+    /// Format to save the recording in: asciicast (default), asciicast-v1, raw, script, script-advanced, transcript or ttyrec
+    #[structopt(
+        long = "format",
+        name = "FORMAT",
+        requires = "FILE",
+        conflicts_with = "raw"
+    )]
+    pub format: Option<String>,
+    /// Redact text matching REGEX in the recording, can be repeated
+    #[structopt(long = "redact", name = "REGEX", number_of_values = 1)]
+    pub redact: Vec<String>,
+    /// Also write the recording to PATH in FORMAT (see --format), can be repeated
+    #[structopt(long = "output", name = "FORMAT:PATH", number_of_values = 1)]
+    pub outputs: Vec<String>,
+    /// How often to sync the recording to disk: flush (default), fsync or fsync:SECONDS
+    #[structopt(long = "durability", name = "MODE")]
+    pub durability: Option<String>,
+    /// Compress the saved recording with gzip or zstd, also implied by a .gz or .zst FILE
+    #[structopt(long = "compress", name = "COMPRESSION", requires = "FILE")]
+    pub compress: Option<String>,
+    /// Split the recording into numbered files, starting a new one every DURATION (e.g. 30m)
+    #[structopt(
+        long = "split-every",
+        name = "DURATION",
+        requires = "FILE",
+        conflicts_with_all = &["append", "raw"]
+    )]
+    pub split_every: Option<String>,
+    /// Split the recording into numbered files of at most SIZE (e.g. 50MB)
+    #[structopt(
+        long = "max-segment-size",
+        name = "SIZE",
+        requires = "FILE",
+        conflicts_with_all = &["append", "raw"]
+    )]
+    pub max_segment_size: Option<String>,
+    /// Also save the recorded command's exit status in an asciicast as an "x" event, which isn't part of asciicast v2
+    #[structopt(long = "exit-event")]
+    pub exit_event: bool,
     /// Filename/path to save the recording to
     #[structopt(name = "FILE", parse(from_os_str))]
     pub file: Option<PathBuf>,
@@ -158,8 +327,112 @@ pub struct UploadSettings {
     pub file: PathBuf,
 }
 
+#[derive(StructOpt, Clone, Debug, Deserialize)]
+pub struct JoinSettings {
+    /// Keep the recordings' exit events ("x"), which aren't part of asciicast v2
+    #[structopt(long = "exit-event")]
+    pub exit_event: bool,
+    /// Filenames/paths of local recordings, in order
+    #[structopt(name = "FILE", parse(from_os_str), required = true)]
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(StructOpt, Clone, Debug, Deserialize)]
+pub struct RepairSettings {
+    /// Filename/path of local recording to repair in place
+    #[structopt(name = "FILE", parse(from_os_str))]
+    pub file: PathBuf,
+}
+
+#[derive(StructOpt, Clone, Debug, Deserialize)]
+pub struct ExportSettings {
+    /// Write a web page playing the recording to HTML
+    #[structopt(long = "html", name = "HTML", parse(from_os_str))]
+    pub html: Option<PathBuf>,
+    /// Write an animated SVG of the recording, named after it unless given as --svg=SVG
+    #[structopt(long = "svg", name = "SVG", require_equals = true)]
+    pub svg: Option<Option<PathBuf>>,
+    /// Write an animated GIF of the recording, named after it unless given as --gif=GIF
+    #[structopt(long = "gif", name = "GIF", require_equals = true)]
+    pub gif: Option<Option<PathBuf>>,
+    /// Colors of the export: asciinema (default), dracula, monokai, nord, solarized-dark, solarized-light, tango or BG,FG,COLORS... in hex
+    #[structopt(long = "theme", name = "THEME")]
+    pub theme: Option<String>,
+    /// Limit exported terminal inactivity to max seconds
+    #[structopt(short = "i", long = "idle-time-limit")]
+    pub idle_time_limit: Option<f64>,
+    /// Playback speed of the export
+    #[structopt(short = "s", long = "speed")]
+    pub speed: Option<f64>,
+    /// Terminal width of recordings without a header, e.g. ttyrec
+    #[structopt(long = "cols")]
+    pub cols: Option<u32>,
+    /// Terminal height of recordings without a header, e.g. ttyrec
+    #[structopt(long = "rows")]
+    pub rows: Option<u32>,
+    /// Location can be either local recording or remote recording
+    #[structopt(name = "LOCATION", parse(from_os_str))]
+    pub location: PathBuf,
+}
+
+#[derive(StructOpt, Clone, Debug, Deserialize)]
+pub struct SnapshotSettings {
+    /// Moment to show the screen at, in seconds, "end" or "marker:N"
+    #[structopt(long = "at", name = "TIME", default_value = "end")]
+    pub at: String,
+    /// Keep the colors and attributes of the text as ANSI escape sequences
+    #[structopt(long = "color")]
+    pub color: bool,
+    /// Terminal width of recordings without a header, e.g. ttyrec
+    #[structopt(long = "cols")]
+    pub cols: Option<u32>,
+    /// Terminal height of recordings without a header, e.g. ttyrec
+    #[structopt(long = "rows")]
+    pub rows: Option<u32>,
+    /// Location can be either local recording or remote recording
+    #[structopt(name = "LOCATION", parse(from_os_str))]
+    pub location: PathBuf,
+}
+
+#[derive(StructOpt, Clone, Debug, Deserialize)]
+pub struct PosterSettings {
+    /// Moment to draw the screen at, in seconds, "end" or "marker:N"
+    #[structopt(long = "at", name = "TIME", default_value = "end")]
+    pub at: String,
+    /// Write the image to OUTPUT instead of naming it after the recording
+    #[structopt(short = "o", long = "output", name = "OUTPUT", parse(from_os_str))]
+    pub output: Option<PathBuf>,
+    /// Colors of the image: asciinema (default), dracula, monokai, nord, solarized-dark, solarized-light, tango or BG,FG,COLORS... in hex
+    #[structopt(long = "theme", name = "THEME")]
+    pub theme: Option<String>,
+    /// Draw each pixel of the font as SCALE by SCALE pixels
+    #[structopt(long = "font-scale", name = "SCALE", default_value = "1")]
+    pub font_scale: usize,
+    /// Terminal width of recordings without a header, e.g. ttyrec
+    #[structopt(long = "cols")]
+    pub cols: Option<u32>,
+    /// Terminal height of recordings without a header, e.g. ttyrec
+    #[structopt(long = "rows")]
+    pub rows: Option<u32>,
+    /// Location can be either local recording or remote recording
+    #[structopt(name = "LOCATION", parse(from_os_str))]
+    pub location: PathBuf,
+}
+
 #[derive(StructOpt, Clone, Debug, Deserialize)]
 pub struct ConcatenateSettings {
+    /// List the markers of the recording instead of its output
+    #[structopt(long = "markers")]
+    pub markers: bool,
+    /// Print the text left on screen as plain lines, without colors or redraws
+    #[structopt(long = "plain", conflicts_with = "markers")]
+    pub plain: bool,
+    /// Terminal width of recordings without a header, e.g. ttyrec
+    #[structopt(long = "cols")]
+    pub cols: Option<u32>,
+    /// Terminal height of recordings without a header, e.g. ttyrec
+    #[structopt(long = "rows")]
+    pub rows: Option<u32>,
     /// Location can be either local recording or remote recording
     #[structopt(name = "LOCATION", parse(from_os_str))]
     pub location: PathBuf,
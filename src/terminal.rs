@@ -1,2 +1,4 @@
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Height(pub u32);
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Width(pub u32);
@@ -0,0 +1,127 @@
+/// A color as set by SGR sequences.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Color {
+    /// The terminal's own foreground or background color.
+    #[default]
+    Default,
+    /// One of the 256 palette colors, the first 16 being the ANSI ones.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// How text is drawn: its colors and attributes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Pen {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub faint: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+    pub hidden: bool,
+    pub strikethrough: bool,
+}
+
+impl Color {
+    /// Returns the SGR parameters selecting this color, `base` being 30 for
+    /// the foreground and 40 for the background.
+    fn sgr(self, base: u16) -> String {
+        match self {
+            Color::Default => (base + 9).to_string(),
+            Color::Indexed(n) if n < 8 => (base + u16::from(n)).to_string(),
+            Color::Indexed(n) if n < 16 => (base + 60 + u16::from(n) - 8).to_string(),
+            Color::Indexed(n) => format!("{};5;{}", base + 8, n),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        }
+    }
+}
+
+impl Pen {
+    /// Returns the SGR sequence that switches any pen to this one.
+    pub fn sgr(&self) -> String {
+        let mut params = vec!["0".to_string()];
+        let attributes = [
+            (self.bold, "1"),
+            (self.faint, "2"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.blink, "5"),
+            (self.inverse, "7"),
+            (self.hidden, "8"),
+            (self.strikethrough, "9"),
+        ];
+        for &(set, param) in &attributes {
+            if set {
+                params.push(param.to_string());
+            }
+        }
+        if self.fg != Color::Default {
+            params.push(self.fg.sgr(30));
+        }
+        if self.bg != Color::Default {
+            params.push(self.bg.sgr(40));
+        }
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
+
+/// A character cell of the screen.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Cell {
+    pub ch: char,
+    /// How many columns the character takes: 2 for wide characters, whose
+    /// second column is a cell of width 0, and 1 for everything else.
+    pub width: u8,
+    pub pen: Pen,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell::blank(Pen::default())
+    }
+}
+
+impl Cell {
+    /// Returns an erased cell. Like xterm, erasing keeps only the background.
+    pub fn blank(pen: Pen) -> Self {
+        Cell {
+            ch: ' ',
+            width: 1,
+            pen: Pen {
+                bg: pen.bg,
+                ..Pen::default()
+            },
+        }
+    }
+}
+
+/// A line of the screen.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Line {
+    pub cells: Vec<Cell>,
+    /// Whether the text goes on on the next line because it didn't fit,
+    /// rather than because of a newline.
+    pub wrapped: bool,
+}
+
+impl Line {
+    pub fn blank(width: usize, pen: Pen) -> Self {
+        Line {
+            cells: vec![Cell::blank(pen); width],
+            wrapped: false,
+        }
+    }
+
+    /// Returns the text of the line, without trailing blanks.
+    pub fn text(&self) -> String {
+        let text: String = self
+            .cells
+            .iter()
+            .filter(|cell| cell.width > 0)
+            .map(|cell| cell.ch)
+            .collect();
+        text.trim_end().to_string()
+    }
+}
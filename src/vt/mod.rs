@@ -0,0 +1,333 @@
+//! A virtual terminal, to know what the screen of a recording looks like at
+//! any point of it.
+//!
+//! Output is parsed by `vte` and applied to a grid of cells the way xterm
+//! does it, including scroll regions and the alternate screen.
+
+use crate::input_formats::Event;
+use crate::terminal::{Height, Width};
+use cell::Line;
+use terminal::Terminal;
+
+pub mod cell;
+mod terminal;
+pub mod transcript;
+
+/// Where the cursor is, and whether it is shown.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Cursor {
+    pub col: usize,
+    pub row: usize,
+    pub visible: bool,
+}
+
+pub struct Vt {
+    parser: vte::Parser,
+    terminal: Terminal,
+}
+
+impl Vt {
+    pub fn new(width: Width, height: Height) -> Self {
+        Vt {
+            parser: vte::Parser::new(),
+            terminal: Terminal::new(width.0 as usize, height.0 as usize),
+        }
+    }
+
+    /// Returns a terminal that keeps the lines scrolling off the top of the
+    /// screen until they are taken with `take_scrolled`.
+    ///
+    /// Callers have to take them as they go, or they pile up for as long as
+    /// the recording lasts.
+    pub fn with_scrollback(width: Width, height: Height) -> Self {
+        let mut vt = Vt::new(width, height);
+        vt.terminal.scrolled = Some(vec![]);
+        vt
+    }
+
+    /// Applies output to the screen.
+    pub fn feed_str(&mut self, data: &str) {
+        for byte in data.bytes() {
+            self.parser.advance(&mut self.terminal, byte);
+        }
+    }
+
+    /// Applies an event of a recording: output and resizes change the
+    /// screen, other events don't.
+    pub fn feed(&mut self, event: &Event) {
+        match *event {
+            Event::Output(_, ref data) => self.feed_str(data),
+            Event::Resize(_, width, height) => self.resize(width, height),
+            _ => (),
+        }
+    }
+
+    pub fn resize(&mut self, width: Width, height: Height) {
+        self.terminal.resize(width.0 as usize, height.0 as usize);
+    }
+
+    pub fn size(&self) -> (Width, Height) {
+        (
+            Width(self.terminal.width as u32),
+            Height(self.terminal.height as u32),
+        )
+    }
+
+    /// The lines on screen, from the top.
+    pub fn lines(&self) -> &[Line] {
+        &self.terminal.lines
+    }
+
+    /// The text of the lines on screen, without trailing blanks.
+    pub fn text(&self) -> Vec<String> {
+        self.lines().iter().map(Line::text).collect()
+    }
+
+    pub fn cursor(&self) -> Cursor {
+        Cursor {
+            col: self.terminal.col,
+            row: self.terminal.row,
+            visible: self.terminal.cursor_visible,
+        }
+    }
+
+    /// Whether a full-screen program switched to the alternate screen.
+    #[cfg(test)]
+    pub fn is_alternate_screen(&self) -> bool {
+        self.terminal.alternate
+    }
+
+    /// Returns the lines that scrolled off the top of the screen since the
+    /// last call, oldest first. The alternate screen has no scrollback, and
+    /// neither do terminals not made `with_scrollback`.
+    pub fn take_scrolled(&mut self) -> Vec<Line> {
+        match self.terminal.scrolled {
+            Some(ref mut scrolled) => std::mem::take(scrolled),
+            None => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cell::{Color, Pen};
+    use super::*;
+    use crate::input_formats::{headerless_size, read_recording};
+    use std::io::Cursor as IoCursor;
+
+    fn run(width: u32, height: u32, data: &str) -> Vt {
+        let mut vt = Vt::with_scrollback(Width(width), Height(height));
+        vt.feed_str(data);
+        vt
+    }
+
+    /// Plays a fixture recording to its end.
+    fn play(fixture: &str) -> Vt {
+        let reader = IoCursor::new(fixture.to_string());
+        let (header, events) = read_recording(reader, headerless_size(None, None)).unwrap();
+        let mut vt = Vt::with_scrollback(Width(header.width), Height(header.height));
+        for event in events {
+            vt.feed(&event.unwrap());
+        }
+        vt
+    }
+
+    #[test]
+    fn prints_and_moves_the_cursor() {
+        let vt = run(10, 3, "ab\r\ncd\x1b[1;5Hx\x1b[3;2Hy");
+        assert_eq!(vt.text(), vec!["ab  x", "cd", " y"]);
+        assert_eq!(
+            vt.cursor(),
+            Cursor {
+                col: 2,
+                row: 2,
+                visible: true
+            }
+        );
+    }
+
+    #[test]
+    fn wraps_long_lines() {
+        let mut vt = run(4, 3, "abcdef");
+        assert_eq!(vt.text(), vec!["abcd", "ef", ""]);
+        assert!(vt.lines()[0].wrapped);
+        assert!(!vt.lines()[1].wrapped);
+
+        // Without autowrap the last column is overwritten.
+        vt.feed_str("\x1b[?7l\r\nabcdef");
+        assert_eq!(vt.text(), vec!["abcd", "ef", "abcf"]);
+    }
+
+    #[test]
+    fn waits_to_wrap_until_the_next_character() {
+        let vt = run(4, 2, "abcd\r\nx");
+        assert_eq!(vt.text(), vec!["abcd", "x"]);
+    }
+
+    #[test]
+    fn scrolls_off_the_top() {
+        let mut vt = run(5, 2, "1\r\n2\r\n3\r\n4");
+        assert_eq!(vt.text(), vec!["3", "4"]);
+        let scrolled: Vec<String> = vt.take_scrolled().iter().map(Line::text).collect();
+        assert_eq!(scrolled, vec!["1", "2"]);
+        assert!(vt.take_scrolled().is_empty());
+
+        // Unless asked for, nothing is kept.
+        let mut vt = Vt::new(Width(5), Height(2));
+        vt.feed_str("1\r\n2\r\n3\r\n4");
+        assert!(vt.take_scrolled().is_empty());
+    }
+
+    #[test]
+    fn handles_wide_characters() {
+        let vt = run(5, 2, "a世界b\x1b[1;3Hx");
+        assert_eq!(vt.text(), vec!["a x界", "b"]);
+        assert_eq!(vt.lines()[0].cells[3].width, 2);
+        assert_eq!(vt.lines()[0].cells[4].width, 0);
+    }
+
+    #[test]
+    fn erases() {
+        let vt = run(
+            5,
+            3,
+            "aaaaa\r\nbbbbb\r\nccccc\x1b[2;3H\x1b[K\x1b[1;2H\x1b[1K",
+        );
+        assert_eq!(vt.text(), vec!["  aaa", "bb", "ccccc"]);
+        let vt = run(5, 3, "aaaaa\r\nbbbbb\r\nccccc\x1b[2;3H\x1b[J");
+        assert_eq!(vt.text(), vec!["aaaaa", "bb", ""]);
+        let vt = run(5, 1, "abcde\x1b[1;2H\x1b[2P\x1b[1@\x1b[1;5H\x1b[X");
+        assert_eq!(vt.text(), vec!["a de"]);
+
+        // Clearing the screen doesn't touch the scrollback, clearing the
+        // scrollback doesn't touch the screen.
+        let mut vt = run(5, 2, "1\r\n2\r\n3\x1b[2J");
+        assert_eq!(vt.text(), vec!["", ""]);
+        vt.feed_str("4\x1b[3J");
+        assert_eq!(vt.text(), vec!["", " 4"]);
+        assert!(vt.take_scrolled().is_empty());
+    }
+
+    #[test]
+    fn sets_colors_and_attributes() {
+        let vt = run(
+            10,
+            1,
+            "\x1b[1;31ma\x1b[22;38;5;200;48;2;1;2;3mb\x1b[38:2::4:5:6;7mc\x1b[0md",
+        );
+        let pens: Vec<Pen> = vt.lines()[0].cells[..4].iter().map(|c| c.pen).collect();
+        assert_eq!(
+            pens[0],
+            Pen {
+                fg: Color::Indexed(1),
+                bold: true,
+                ..Pen::default()
+            }
+        );
+        assert_eq!(
+            pens[1],
+            Pen {
+                fg: Color::Indexed(200),
+                bg: Color::Rgb(1, 2, 3),
+                ..Pen::default()
+            }
+        );
+        assert_eq!(
+            pens[2],
+            Pen {
+                fg: Color::Rgb(4, 5, 6),
+                bg: Color::Rgb(1, 2, 3),
+                inverse: true,
+                ..Pen::default()
+            }
+        );
+        assert_eq!(pens[3], Pen::default());
+    }
+
+    #[test]
+    fn draws_lines_with_dec_graphics() {
+        let vt = run(5, 1, "\x1b(0lqqk\x1b(B!");
+        assert_eq!(vt.text(), vec!["┌──┐!"]);
+    }
+
+    #[test]
+    fn resizes() {
+        let mut vt = run(4, 3, "1\r\n2\r\n3");
+        vt.resize(Width(2), Height(2));
+        assert_eq!(vt.text(), vec!["2", "3"]);
+        assert_eq!(vt.cursor().row, 1);
+        vt.resize(Width(3), Height(3));
+        assert_eq!(vt.text(), vec!["2", "3", ""]);
+        assert_eq!(vt.size(), (Width(3), Height(3)));
+    }
+
+    #[test]
+    fn plays_progress_bars() {
+        let vt = play(include_str!("fixtures/progress.cast"));
+        assert_eq!(
+            vt.text(),
+            vec![
+                "$ cargo build",
+                "   Compiling demo v0.1.0",
+                "[##########] 100% done",
+                "    Finished dev in 1.20s",
+                "$",
+                "",
+            ]
+        );
+        assert_eq!(vt.lines()[1].cells[3].pen.fg, Color::Indexed(2));
+    }
+
+    #[test]
+    fn plays_full_screen_programs() {
+        let mut vt = play(include_str!("fixtures/fullscreen.cast"));
+        // Leaving the alternate screen brings back what was there before.
+        assert!(!vt.is_alternate_screen());
+        assert_eq!(vt.text(), vec!["$ top", "$", "", ""]);
+        assert_eq!(vt.cursor().row, 1);
+        assert!(vt.cursor().visible);
+        // Nothing of the full screen program made it into the scrollback.
+        assert!(vt.take_scrolled().is_empty());
+    }
+
+    #[test]
+    fn plays_full_screen_programs_partially() {
+        let fixture = include_str!("fixtures/fullscreen.cast");
+        // Everything up to the program quitting.
+        let end = fixture.find("[2.0").unwrap();
+        let vt = play(&fixture[..end]);
+        assert!(vt.is_alternate_screen());
+        assert_eq!(
+            vt.text(),
+            vec![
+                "top - 10:00:02",
+                "  PID COMMAND",
+                "    2 cargo",
+                "    1 init"
+            ]
+        );
+        assert!(!vt.cursor().visible);
+    }
+
+    #[test]
+    fn plays_scroll_regions() {
+        let mut vt = play(include_str!("fixtures/scroll_region.cast"));
+        // The status line stays put while the region above it scrolls.
+        assert_eq!(
+            vt.text(),
+            vec!["line 3", "line 4", "line 5", "-- status: 5 lines --"]
+        );
+        // Like in xterm, lines scrolled off a region at the top of the
+        // screen go to the scrollback.
+        let scrolled: Vec<String> = vt.take_scrolled().iter().map(Line::text).collect();
+        assert_eq!(scrolled, vec!["line 1", "line 2"]);
+    }
+
+    #[test]
+    fn plays_resizes() {
+        let vt = play(include_str!("fixtures/resize.cast"));
+        assert_eq!(vt.size(), (Width(12), Height(3)));
+        // Lines aren't reflowed.
+        assert_eq!(vt.text(), vec!["0123456789", "abcd", "$"]);
+    }
+}
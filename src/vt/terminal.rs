@@ -0,0 +1,715 @@
+use super::cell::{Cell, Color, Line, Pen};
+use std::mem;
+use unicode_width::UnicodeWidthChar;
+use vte::{Params, Perform};
+
+/// The character sets that can be designated as G0 and G1.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Charset {
+    Ascii,
+    /// DEC special graphics, used for line drawing by e.g. ncurses.
+    DecSpecial,
+}
+
+impl Charset {
+    fn map(self, c: char) -> char {
+        if self == Charset::Ascii {
+            return c;
+        }
+        match c {
+            '`' => '◆',
+            'a' => '▒',
+            'f' => '°',
+            'g' => '±',
+            'j' => '┘',
+            'k' => '┐',
+            'l' => '┌',
+            'm' => '└',
+            'n' => '┼',
+            'o' => '⎺',
+            'p' => '⎻',
+            'q' => '─',
+            'r' => '⎼',
+            's' => '⎽',
+            't' => '├',
+            'u' => '┤',
+            'v' => '┴',
+            'w' => '┬',
+            'x' => '│',
+            'y' => '≤',
+            'z' => '≥',
+            '{' => 'π',
+            '|' => '≠',
+            '}' => '£',
+            '~' => '·',
+            _ => c,
+        }
+    }
+}
+
+/// What DECSC saves and DECRC restores.
+#[derive(Debug, Clone, Copy)]
+struct SavedCursor {
+    col: usize,
+    row: usize,
+    pending_wrap: bool,
+    pen: Pen,
+    origin: bool,
+    charsets: [Charset; 2],
+    active_charset: usize,
+}
+
+impl Default for SavedCursor {
+    fn default() -> Self {
+        SavedCursor {
+            col: 0,
+            row: 0,
+            pending_wrap: false,
+            pen: Pen::default(),
+            origin: false,
+            charsets: [Charset::Ascii; 2],
+            active_charset: 0,
+        }
+    }
+}
+
+fn default_tabs(width: usize) -> Vec<bool> {
+    (0..width).map(|col| col > 0 && col % 8 == 0).collect()
+}
+
+/// The state of the emulated terminal, changed by what `vte` parses.
+pub struct Terminal {
+    pub(super) width: usize,
+    pub(super) height: usize,
+    /// The lines of the buffer on screen.
+    pub(super) lines: Vec<Line>,
+    /// The lines of the other buffer, primary or alternate.
+    other_lines: Vec<Line>,
+    pub(super) alternate: bool,
+    pub(super) col: usize,
+    pub(super) row: usize,
+    /// Set after printing in the last column: the next character goes on
+    /// the next line, unless the cursor is moved first.
+    pending_wrap: bool,
+    pen: Pen,
+    /// Saved cursors of the primary and the alternate buffer.
+    saved: [SavedCursor; 2],
+    /// The scroll region, top and bottom lines included.
+    top: usize,
+    bottom: usize,
+    origin: bool,
+    autowrap: bool,
+    insert: bool,
+    pub(super) cursor_visible: bool,
+    tabs: Vec<bool>,
+    charsets: [Charset; 2],
+    active_charset: usize,
+    last_char: Option<char>,
+    /// Lines that scrolled off the top of the primary buffer, if they are
+    /// kept at all.
+    pub(super) scrolled: Option<Vec<Line>>,
+}
+
+impl Terminal {
+    pub fn new(width: usize, height: usize) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+        Terminal {
+            width,
+            height,
+            lines: vec![Line::blank(width, Pen::default()); height],
+            other_lines: vec![Line::blank(width, Pen::default()); height],
+            alternate: false,
+            col: 0,
+            row: 0,
+            pending_wrap: false,
+            pen: Pen::default(),
+            saved: Default::default(),
+            top: 0,
+            bottom: height - 1,
+            origin: false,
+            autowrap: true,
+            insert: false,
+            cursor_visible: true,
+            tabs: default_tabs(width),
+            charsets: [Charset::Ascii; 2],
+            active_charset: 0,
+            last_char: None,
+            scrolled: None,
+        }
+    }
+
+    fn reset(&mut self) {
+        let scrolled = self.scrolled.take();
+        *self = Terminal::new(self.width, self.height);
+        self.scrolled = scrolled;
+    }
+
+    /// Keeps lines gone off the top of the primary buffer, if they are kept.
+    fn scroll_off<I: IntoIterator<Item = Line>>(&mut self, lines: I) {
+        if let (Some(ref mut scrolled), false) = (&mut self.scrolled, self.alternate) {
+            scrolled.extend(lines);
+        }
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let width = width.max(1);
+        let height = height.max(1);
+
+        // Keep the cursor on screen, scrolling lines above it off if needed.
+        if self.row >= height {
+            let shift = self.row + 1 - height;
+            let gone: Vec<Line> = self.lines.drain(..shift).collect();
+            self.scroll_off(gone);
+            self.row -= shift;
+        }
+        for lines in &mut [&mut self.lines, &mut self.other_lines] {
+            lines.truncate(height);
+            lines.resize(height, Line::blank(width, Pen::default()));
+            for line in lines.iter_mut() {
+                line.cells.resize(width, Cell::default());
+                if line.cells[width - 1].width == 2 {
+                    line.cells[width - 1] = Cell::blank(line.cells[width - 1].pen);
+                }
+            }
+        }
+
+        self.tabs.truncate(width);
+        let old_width = self.tabs.len();
+        self.tabs
+            .extend(default_tabs(width).into_iter().skip(old_width));
+        self.width = width;
+        self.height = height;
+        self.top = 0;
+        self.bottom = height - 1;
+        self.col = self.col.min(width - 1);
+        self.pending_wrap = false;
+        for saved in &mut self.saved {
+            saved.col = saved.col.min(width - 1);
+            saved.row = saved.row.min(height - 1);
+        }
+    }
+
+    fn blank_line(&self) -> Line {
+        Line::blank(self.width, self.pen)
+    }
+
+    fn print_char(&mut self, c: char) {
+        let c = self.charsets[self.active_charset].map(c);
+        let width = match c.width() {
+            Some(w) if w > 0 => w.min(2),
+            // Combining and other zero-width characters are dropped.
+            _ => return,
+        };
+        if self.pending_wrap && self.autowrap {
+            self.wrap();
+        }
+        if width == 2 && self.col + 1 >= self.width {
+            // A wide character doesn't fit in the last column.
+            if self.width < 2 {
+                return;
+            }
+            if self.autowrap {
+                self.erase_cells(self.col, self.width);
+                self.wrap();
+            } else {
+                self.col = self.width - 2;
+            }
+        }
+        if self.insert {
+            self.insert_cells(width);
+        }
+
+        let col = self.col;
+        self.clear_wide_char(col);
+        self.lines[self.row].cells[col] = Cell {
+            ch: c,
+            width: width as u8,
+            pen: self.pen,
+        };
+        if width == 2 {
+            self.clear_wide_char(col + 1);
+            self.lines[self.row].cells[col + 1] = Cell {
+                ch: ' ',
+                width: 0,
+                pen: self.pen,
+            };
+        }
+
+        if col + width >= self.width {
+            self.col = self.width - 1;
+            self.pending_wrap = self.autowrap;
+        } else {
+            self.col = col + width;
+        }
+    }
+
+    /// Blanks the other half of a wide character about to be overwritten at `col`.
+    fn clear_wide_char(&mut self, col: usize) {
+        let cells = &mut self.lines[self.row].cells;
+        match cells[col].width {
+            0 if col > 0 => cells[col - 1] = Cell::blank(cells[col - 1].pen),
+            2 if col + 1 < cells.len() => cells[col + 1] = Cell::blank(cells[col + 1].pen),
+            _ => (),
+        }
+    }
+
+    fn wrap(&mut self) {
+        self.lines[self.row].wrapped = true;
+        self.col = 0;
+        self.pending_wrap = false;
+        self.index();
+    }
+
+    /// Moves the cursor down, scrolling the region if it is at its bottom.
+    fn index(&mut self) {
+        if self.row == self.bottom {
+            self.scroll_up(1);
+        } else if self.row + 1 < self.height {
+            self.row += 1;
+        }
+    }
+
+    /// Moves the cursor up, scrolling the region if it is at its top.
+    fn reverse_index(&mut self) {
+        if self.row == self.top {
+            self.scroll_down(1);
+        } else if self.row > 0 {
+            self.row -= 1;
+        }
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        for _ in 0..n.min(self.bottom - self.top + 1) {
+            let line = self.lines.remove(self.top);
+            self.lines.insert(self.bottom, self.blank_line());
+            if self.top == 0 {
+                self.scroll_off(Some(line));
+            }
+        }
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        for _ in 0..n.min(self.bottom - self.top + 1) {
+            self.lines.remove(self.bottom);
+            self.lines.insert(self.top, self.blank_line());
+        }
+    }
+
+    fn move_to(&mut self, col: usize, row: usize) {
+        self.col = col.min(self.width - 1);
+        self.row = row.min(self.height - 1);
+        self.pending_wrap = false;
+    }
+
+    /// Moves the cursor to `row` of the screen, or of the scroll region in origin mode.
+    fn move_to_row(&mut self, row: usize) {
+        let row = if self.origin {
+            (self.top + row).min(self.bottom)
+        } else {
+            row
+        };
+        self.move_to(self.col, row);
+    }
+
+    fn move_up(&mut self, n: usize) {
+        let limit = if self.row >= self.top { self.top } else { 0 };
+        self.move_to(self.col, self.row.saturating_sub(n).max(limit));
+    }
+
+    fn move_down(&mut self, n: usize) {
+        let limit = if self.row <= self.bottom {
+            self.bottom
+        } else {
+            self.height - 1
+        };
+        self.move_to(self.col, (self.row + n).min(limit));
+    }
+
+    fn next_tab(&mut self, n: usize) {
+        for _ in 0..n {
+            self.col = (self.col + 1..self.width)
+                .find(|&col| self.tabs[col])
+                .unwrap_or(self.width - 1);
+        }
+        self.pending_wrap = false;
+    }
+
+    fn previous_tab(&mut self, n: usize) {
+        for _ in 0..n {
+            self.col = (0..self.col).rev().find(|&col| self.tabs[col]).unwrap_or(0);
+        }
+        self.pending_wrap = false;
+    }
+
+    /// Erases the cells from `from` up to `to` of the cursor line.
+    fn erase_cells(&mut self, from: usize, to: usize) {
+        let blank = Cell::blank(self.pen);
+        let cells = &mut self.lines[self.row].cells;
+        let to = to.min(cells.len());
+        for cell in &mut cells[from..to] {
+            *cell = blank;
+        }
+        // Don't leave half of a wide character behind.
+        if from > 0 && cells[from - 1].width == 2 {
+            cells[from - 1] = blank;
+        }
+        if to < cells.len() && cells[to].width == 0 {
+            cells[to] = blank;
+        }
+    }
+
+    fn erase_in_line(&mut self, mode: u16) {
+        match mode {
+            0 => self.erase_cells(self.col, self.width),
+            1 => self.erase_cells(0, self.col + 1),
+            2 => {
+                self.erase_cells(0, self.width);
+                self.lines[self.row].wrapped = false;
+            }
+            _ => (),
+        }
+    }
+
+    fn erase_in_display(&mut self, mode: u16) {
+        match mode {
+            0 => {
+                self.erase_in_line(0);
+                for row in self.row + 1..self.height {
+                    self.lines[row] = self.blank_line();
+                }
+            }
+            1 => {
+                for row in 0..self.row {
+                    self.lines[row] = self.blank_line();
+                }
+                self.erase_in_line(1);
+            }
+            2 => {
+                for row in 0..self.height {
+                    self.lines[row] = self.blank_line();
+                }
+            }
+            // Clears the scrollback, which the screen doesn't show.
+            3 => {
+                if let Some(ref mut scrolled) = self.scrolled {
+                    scrolled.clear();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn insert_cells(&mut self, n: usize) {
+        self.clear_wide_char(self.col);
+        let blank = Cell::blank(self.pen);
+        let width = self.width;
+        let cells = &mut self.lines[self.row].cells;
+        let n = n.min(width - self.col);
+        cells.splice(self.col..self.col, (0..n).map(|_| blank));
+        cells.truncate(width);
+        if cells[width - 1].width == 2 {
+            cells[width - 1] = blank;
+        }
+    }
+
+    fn delete_cells(&mut self, n: usize) {
+        self.clear_wide_char(self.col);
+        let blank = Cell::blank(self.pen);
+        let cells = &mut self.lines[self.row].cells;
+        let n = n.min(cells.len() - self.col);
+        cells.drain(self.col..self.col + n);
+        cells.extend((0..n).map(|_| blank));
+        if cells[self.col].width == 0 {
+            cells[self.col] = blank;
+        }
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        if self.row < self.top || self.row > self.bottom {
+            return;
+        }
+        for _ in 0..n.min(self.bottom - self.row + 1) {
+            self.lines.remove(self.bottom);
+            self.lines.insert(self.row, self.blank_line());
+        }
+        self.move_to(0, self.row);
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        if self.row < self.top || self.row > self.bottom {
+            return;
+        }
+        for _ in 0..n.min(self.bottom - self.row + 1) {
+            self.lines.remove(self.row);
+            self.lines.insert(self.bottom, self.blank_line());
+        }
+        self.move_to(0, self.row);
+    }
+
+    fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.height - 1);
+        if top < bottom {
+            self.top = top;
+            self.bottom = bottom;
+            self.move_to(0, 0);
+            self.move_to_row(0);
+        }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved[self.alternate as usize] = SavedCursor {
+            col: self.col,
+            row: self.row,
+            pending_wrap: self.pending_wrap,
+            pen: self.pen,
+            origin: self.origin,
+            charsets: self.charsets,
+            active_charset: self.active_charset,
+        };
+    }
+
+    fn restore_cursor(&mut self) {
+        let saved = self.saved[self.alternate as usize];
+        self.move_to(saved.col, saved.row);
+        self.pending_wrap = saved.pending_wrap;
+        self.pen = saved.pen;
+        self.origin = saved.origin;
+        self.charsets = saved.charsets;
+        self.active_charset = saved.active_charset;
+    }
+
+    fn switch_buffer(&mut self, alternate: bool, clear: bool) {
+        if self.alternate != alternate {
+            mem::swap(&mut self.lines, &mut self.other_lines);
+            self.alternate = alternate;
+        }
+        if alternate && clear {
+            self.lines = vec![self.blank_line(); self.height];
+        }
+    }
+
+    fn set_private_mode(&mut self, mode: u16, on: bool) {
+        match mode {
+            6 => {
+                self.origin = on;
+                self.move_to(0, 0);
+                self.move_to_row(0);
+            }
+            7 => self.autowrap = on,
+            25 => self.cursor_visible = on,
+            47 => self.switch_buffer(on, false),
+            1047 => self.switch_buffer(on, on),
+            1048 if on => self.save_cursor(),
+            1048 => self.restore_cursor(),
+            1049 if on => {
+                self.save_cursor();
+                self.switch_buffer(true, true);
+            }
+            1049 => {
+                self.switch_buffer(false, false);
+                self.restore_cursor();
+            }
+            _ => (),
+        }
+    }
+
+    fn set_mode(&mut self, mode: u16, on: bool) {
+        if mode == 4 {
+            self.insert = on;
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params: &[Vec<u16>]) {
+        if params.is_empty() {
+            self.pen = Pen::default();
+            return;
+        }
+        let mut i = 0;
+        while i < params.len() {
+            let param = &params[i];
+            i += 1;
+            match param[0] {
+                0 => self.pen = Pen::default(),
+                1 => self.pen.bold = true,
+                2 => self.pen.faint = true,
+                3 => self.pen.italic = true,
+                4 => self.pen.underline = param.get(1) != Some(&0),
+                5 | 6 => self.pen.blink = true,
+                7 => self.pen.inverse = true,
+                8 => self.pen.hidden = true,
+                9 => self.pen.strikethrough = true,
+                21 => self.pen.underline = true,
+                22 => {
+                    self.pen.bold = false;
+                    self.pen.faint = false;
+                }
+                23 => self.pen.italic = false,
+                24 => self.pen.underline = false,
+                25 => self.pen.blink = false,
+                27 => self.pen.inverse = false,
+                28 => self.pen.hidden = false,
+                29 => self.pen.strikethrough = false,
+                n @ 30..=37 => self.pen.fg = Color::Indexed(n as u8 - 30),
+                38 => self.pen.fg = extended_color(param, params, &mut i),
+                39 => self.pen.fg = Color::Default,
+                n @ 40..=47 => self.pen.bg = Color::Indexed(n as u8 - 40),
+                48 => self.pen.bg = extended_color(param, params, &mut i),
+                49 => self.pen.bg = Color::Default,
+                n @ 90..=97 => self.pen.fg = Color::Indexed(n as u8 - 90 + 8),
+                n @ 100..=107 => self.pen.bg = Color::Indexed(n as u8 - 100 + 8),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Reads the color of an SGR 38 or 48, given either with colons as one
+/// parameter (`38:5:n`) or with semicolons as the parameters after (`38;5;n`).
+fn extended_color(param: &[u16], params: &[Vec<u16>], i: &mut usize) -> Color {
+    let values: Vec<u16> = if param.len() > 1 {
+        param[1..].to_vec()
+    } else {
+        let kind = params.get(*i).map(|p| p[0]);
+        let len = match kind {
+            Some(5) => 2,
+            Some(2) => 4,
+            _ => 1,
+        };
+        let values = params[*i..].iter().take(len).map(|p| p[0]).collect();
+        *i = (*i + len).min(params.len());
+        values
+    };
+    let byte = |v: u16| v.min(255) as u8;
+    match values[..] {
+        [5, n, ..] => Color::Indexed(byte(n)),
+        // With colons there may be a color space id before the components.
+        [2, _, r, g, b] if param.len() > 1 => Color::Rgb(byte(r), byte(g), byte(b)),
+        [2, r, g, b, ..] => Color::Rgb(byte(r), byte(g), byte(b)),
+        _ => Color::Default,
+    }
+}
+
+impl Perform for Terminal {
+    fn print(&mut self, c: char) {
+        self.print_char(c);
+        self.last_char = Some(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            0x08 => {
+                self.col = self.col.saturating_sub(1);
+                self.pending_wrap = false;
+            }
+            0x09 => self.next_tab(1),
+            0x0a..=0x0c | 0x84 => {
+                self.pending_wrap = false;
+                self.index();
+            }
+            0x0d => {
+                self.col = 0;
+                self.pending_wrap = false;
+            }
+            0x0e => self.active_charset = 1,
+            0x0f => self.active_charset = 0,
+            0x85 => {
+                self.move_to(0, self.row);
+                self.index();
+            }
+            0x88 => self.tabs[self.col] = true,
+            0x8d => {
+                self.pending_wrap = false;
+                self.reverse_index();
+            }
+            _ => (),
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], ignore: bool, action: char) {
+        if ignore {
+            return;
+        }
+        let params: Vec<Vec<u16>> = params.iter().map(|p| p.to_vec()).collect();
+        // The n-th parameter, with 0 and missing ones meaning `default`.
+        let arg = |n: usize, default: u16| match params.get(n).map(|p| p[0]) {
+            Some(0) | None => default as usize,
+            Some(v) => v as usize,
+        };
+        match (intermediates, action) {
+            ([], '@') => self.insert_cells(arg(0, 1)),
+            ([], 'A') => self.move_up(arg(0, 1)),
+            ([], 'B') | ([], 'e') => self.move_down(arg(0, 1)),
+            ([], 'C') | ([], 'a') => self.move_to(self.col + arg(0, 1), self.row),
+            ([], 'D') => self.move_to(self.col.saturating_sub(arg(0, 1)), self.row),
+            ([], 'E') => {
+                self.move_down(arg(0, 1));
+                self.col = 0;
+            }
+            ([], 'F') => {
+                self.move_up(arg(0, 1));
+                self.col = 0;
+            }
+            ([], 'G') | ([], '`') => self.move_to(arg(0, 1) - 1, self.row),
+            ([], 'H') | ([], 'f') => {
+                self.move_to(arg(1, 1) - 1, self.row);
+                self.move_to_row(arg(0, 1) - 1);
+            }
+            ([], 'I') => self.next_tab(arg(0, 1)),
+            ([], 'J') => self.erase_in_display(arg(0, 0) as u16),
+            ([], 'K') => self.erase_in_line(arg(0, 0) as u16),
+            ([], 'L') => self.insert_lines(arg(0, 1)),
+            ([], 'M') => self.delete_lines(arg(0, 1)),
+            ([], 'P') => self.delete_cells(arg(0, 1)),
+            ([], 'S') => self.scroll_up(arg(0, 1)),
+            ([], 'T') => self.scroll_down(arg(0, 1)),
+            ([], 'X') => self.erase_cells(self.col, self.col + arg(0, 1)),
+            ([], 'Z') => self.previous_tab(arg(0, 1)),
+            ([], 'b') => {
+                if let Some(c) = self.last_char {
+                    for _ in 0..arg(0, 1).min(self.width * self.height) {
+                        self.print_char(c);
+                    }
+                }
+            }
+            ([], 'd') => self.move_to_row(arg(0, 1) - 1),
+            ([], 'g') => match arg(0, 0) {
+                0 => self.tabs[self.col] = false,
+                3 => self.tabs.iter_mut().for_each(|tab| *tab = false),
+                _ => (),
+            },
+            ([], 'h') | ([], 'l') => {
+                for param in &params {
+                    self.set_mode(param[0], action == 'h');
+                }
+            }
+            ([b'?'], 'h') | ([b'?'], 'l') => {
+                for param in &params {
+                    self.set_private_mode(param[0], action == 'h');
+                }
+            }
+            ([], 'm') => self.select_graphic_rendition(&params),
+            ([], 'r') => self.set_scroll_region(arg(0, 1) - 1, arg(1, self.height as u16) - 1),
+            ([], 's') => self.save_cursor(),
+            ([], 'u') => self.restore_cursor(),
+            _ => (),
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        match (intermediates, byte) {
+            ([], b'7') => self.save_cursor(),
+            ([], b'8') => self.restore_cursor(),
+            ([], b'D') => self.execute(0x84),
+            ([], b'E') => self.execute(0x85),
+            ([], b'H') => self.execute(0x88),
+            ([], b'M') => self.execute(0x8d),
+            ([], b'c') => self.reset(),
+            ([b'('], b'0') => self.charsets[0] = Charset::DecSpecial,
+            ([b'('], _) => self.charsets[0] = Charset::Ascii,
+            ([b')'], b'0') => self.charsets[1] = Charset::DecSpecial,
+            ([b')'], _) => self.charsets[1] = Charset::Ascii,
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use super::cell::Line;
+use super::Vt;
+use crate::input_formats::Event;
+use crate::terminal::{Height, Width};
+use std::mem;
+
+/// Turns the output of a recording into the lines of text it left behind,
+/// as a log would have them.
+///
+/// A line is only final once it scrolls off the screen, so redrawn progress
+/// bars and cursor movement come out as they last looked, without colors.
+/// Lines that were wrapped because they didn't fit are joined back. Cleared
+/// screens, e.g. of a program redrawing everything in a loop, are left out
+/// like any other redraw.
+pub struct Transcript {
+    vt: Vt,
+    // The start of a wrapped line whose end is still on screen.
+    partial: String,
+}
+
+impl Transcript {
+    pub fn new(width: Width, height: Height) -> Self {
+        Transcript {
+            vt: Vt::with_scrollback(width, height),
+            partial: String::new(),
+        }
+    }
+
+    /// Applies an event and returns the lines it finished.
+    pub fn feed(&mut self, event: &Event) -> Vec<String> {
+        self.vt.feed(event);
+        let scrolled = self.vt.take_scrolled();
+        self.join(scrolled)
+    }
+
+    /// Returns the lines left on screen, without the blank ones at the bottom.
+    pub fn finish(mut self) -> Vec<String> {
+        let mut lines = self.vt.take_scrolled();
+        let screen = self.vt.lines();
+        let used = screen
+            .iter()
+            .rposition(|line| !line.text().is_empty())
+            .map_or(0, |row| row + 1);
+        lines.extend(screen[..used].iter().cloned());
+        let mut text = self.join(lines);
+        if !self.partial.is_empty() {
+            text.push(mem::take(&mut self.partial));
+        }
+        text
+    }
+
+    fn join(&mut self, lines: Vec<Line>) -> Vec<String> {
+        let mut text = vec![];
+        for line in lines {
+            if line.wrapped {
+                // The text goes on, blanks at the end included.
+                let cells = line.cells.iter().filter(|cell| cell.width > 0);
+                self.partial.extend(cells.map(|cell| cell.ch));
+            } else {
+                self.partial.push_str(&line.text());
+                text.push(mem::take(&mut self.partial));
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(width: u32, height: u32, outputs: &[&str]) -> Vec<String> {
+        let mut transcript = Transcript::new(Width(width), Height(height));
+        let mut text = vec![];
+        for (i, data) in outputs.iter().enumerate() {
+            text.extend(transcript.feed(&Event::Output(i as f64, data.to_string())));
+        }
+        text.extend(transcript.finish());
+        text
+    }
+
+    #[test]
+    fn strips_colors() {
+        assert_eq!(
+            transcript(20, 3, &["$ ls\r\n\x1b[1;34mdocs\x1b[0m  README\r\n$ "]),
+            vec!["$ ls", "docs  README", "$"]
+        );
+    }
+
+    #[test]
+    fn collapses_progress_bars() {
+        let outputs = ["[    ] 0%", "\r[##  ] 50%", "\r[####] 100%\r\n", "done\r\n"];
+        assert_eq!(transcript(20, 2, &outputs), vec!["[####] 100%", "done"]);
+    }
+
+    #[test]
+    fn resolves_redraws() {
+        let outputs = [
+            "a: waiting\r\nb: waiting\r\n",
+            "\x1b[2A\x1b[2Ka: done\r\n\x1b[2Kb: done\r\n",
+            "ok\r\n",
+        ];
+        assert_eq!(
+            transcript(20, 4, &outputs),
+            vec!["a: done", "b: done", "ok"]
+        );
+    }
+
+    #[test]
+    fn joins_wrapped_lines() {
+        assert_eq!(
+            transcript(4, 2, &["abcd efgh\r\n", "1\r\n2\r\n"]),
+            vec!["abcd efgh", "1", "2"]
+        );
+    }
+
+    #[test]
+    fn leaves_out_cleared_screens() {
+        assert_eq!(
+            transcript(10, 3, &["one\r\ntwo", "\x1b[H\x1b[2J\x1b[3Jthree"]),
+            vec!["three"]
+        );
+        // Only the last frame of a clear-redraw loop, e.g. `watch`, is left.
+        let frames = [
+            "\x1b[H\x1b[2Jtick 1\r\nstatus",
+            "\x1b[H\x1b[2Jtick 2\r\nstatus",
+            "\x1b[H\x1b[2Jtick 3\r\nstatus",
+        ];
+        assert_eq!(transcript(10, 3, &frames), vec!["tick 3", "status"]);
+    }
+
+    #[test]
+    fn leaves_out_full_screen_programs() {
+        let outputs = ["$ top\r\n", "\x1b[?1049h\x1b[Htop - 10:00", "\x1b[?1049l$ "];
+        assert_eq!(transcript(20, 3, &outputs), vec!["$ top", "$"]);
+    }
+}